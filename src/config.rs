@@ -0,0 +1,228 @@
+//! Contains the [`Config`], which holds the user's preferences
+//! and is stored in `config.toml` in the config directory.
+
+use std::{ffi::OsStr, path::PathBuf, time::Duration};
+
+use eyre::eyre;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+use serde::Deserialize;
+use tokio::{
+    fs,
+    sync::mpsc::{self, Sender, UnboundedReceiver},
+    time::sleep,
+};
+use toml_edit::{DocumentMut, Item, Value};
+
+#[cfg(feature = "equalizer")]
+use crate::player::equalizer;
+#[cfg(feature = "mqtt")]
+use crate::player::mqtt;
+use crate::{
+    play,
+    player::{
+        ambience,
+        hooks::Hooks,
+        lofi,
+        ui::{theme::Theme, Cue},
+        Messages,
+    },
+};
+
+/// How long to wait for `config.toml` to stop changing before loading it again,
+/// since saving a file usually shows up as a few changes in a row.
+const SETTLE: Duration = Duration::from_millis(100);
+
+/// Retrieves the config directory, which is usually `~/.config/lowfi`.
+///
+/// Unlike [`crate::play::data_dir`], this also creates the directory if it doesn't exist.
+pub async fn dir() -> eyre::Result<PathBuf> {
+    let config = dirs::config_dir()
+        .ok_or_else(|| eyre!("Couldn't find config directory"))?
+        .join("lowfi");
+
+    if !config.exists() {
+        fs::create_dir_all(&config).await?;
+    }
+
+    Ok(config)
+}
+
+/// The user's preferences, which act as the defaults for the matching flags.
+///
+/// Any keys which are missing from `config.toml` are just left as their defaults.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Whether to hide the bottom control bar.
+    pub minimalist: bool,
+
+    /// Whether to draw the window without borders.
+    pub borderless: bool,
+
+    /// Whether the progress bar shows the time that's left of the track, toggled with `r`.
+    pub remaining: bool,
+
+    /// Whether to scroll track names which are too long to fit, like `--marquee`.
+    pub marquee: bool,
+
+    /// Whether to always pick up where the last session left off, like `--resume`.
+    pub resume: bool,
+
+    /// What to do when a new track starts, like `--cue`.
+    pub cue: Option<Cue>,
+
+    /// The line to show at the top of the window, like `--title`.
+    pub title: Option<String>,
+
+    /// The maximum size of the cache in MB, like `--cache-size`.
+    pub cache_size: Option<u64>,
+
+    /// The user token to submit listens to ListenBrainz with.
+    #[cfg(feature = "scrobble")]
+    pub listenbrainz_token: Option<String>,
+
+    /// The colors & styles of the UI, from the `[theme]` section.
+    pub theme: Theme,
+
+    /// The shell commands to run when something happens, from the `[hooks]` section.
+    pub hooks: Hooks,
+
+    /// The MQTT broker to publish what's playing to, from the `[mqtt]` section.
+    #[cfg(feature = "mqtt")]
+    pub mqtt: Option<mqtt::Settings>,
+
+    /// The sound to loop underneath the music, from the `[ambience]` section.
+    pub ambience: ambience::Settings,
+
+    /// The settings of the lofi filter, from the `[lofi]` section.
+    pub lofi: lofi::Settings,
+
+    /// The gain of each band of the EQ, from the `[equalizer]` section.
+    #[cfg(feature = "equalizer")]
+    pub equalizer: equalizer::Settings,
+}
+
+impl Config {
+    /// Retrieves the path to `config.toml`.
+    async fn path() -> eyre::Result<PathBuf> {
+        Ok(dir().await?.join("config.toml"))
+    }
+
+    /// Loads the [`Config`], or the defaults if there isn't a `config.toml`.
+    pub async fn load() -> eyre::Result<Self> {
+        let path = Self::path().await?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        toml::from_str(&fs::read_to_string(path).await?)
+            .map_err(|error| eyre!("config.toml is invalid: {error}"))
+    }
+
+    /// Sets `key` in `document` to `value`.
+    ///
+    /// If the key already exists, the comments around it are kept as they were.
+    fn set(document: &mut DocumentMut, key: &str, value: impl Into<Value>) {
+        let mut value = value.into();
+
+        if let Some(existing) = document.get_mut(key).and_then(Item::as_value_mut) {
+            *value.decor_mut() = existing.decor().clone();
+            *existing = value;
+        } else {
+            document[key] = Item::Value(value);
+        }
+    }
+
+    /// Writes the [`Config`] back to `config.toml`.
+    ///
+    /// This only touches the keys that lowfi knows about, so any comments
+    /// or formatting that the user added are preserved.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path().await?;
+        let contents = if path.exists() {
+            fs::read_to_string(&path).await?
+        } else {
+            String::new()
+        };
+
+        let mut document: DocumentMut = contents
+            .parse()
+            .map_err(|error| eyre!("config.toml is invalid: {error}"))?;
+
+        Self::set(&mut document, "minimalist", self.minimalist);
+        Self::set(&mut document, "borderless", self.borderless);
+        Self::set(&mut document, "remaining", self.remaining);
+        Self::set(&mut document, "resume", self.resume);
+
+        play::write(path, document.to_string()).await?;
+
+        Ok(())
+    }
+}
+
+/// Watches `config.toml` for changes, so that they can be applied while lowfi is running.
+pub struct Watcher {
+    /// The watcher itself, which stops watching once it's dropped.
+    _watcher: RecommendedWatcher,
+
+    /// Recieves a message whenever `config.toml` might have changed.
+    rx: UnboundedReceiver<()>,
+}
+
+impl Watcher {
+    /// Starts watching `config.toml`.
+    ///
+    /// The whole config directory is watched rather than just the file, since
+    /// a lot of editors save by replacing the file with a new one, which a
+    /// watcher on the old file would never hear about.
+    pub async fn new() -> eyre::Result<Self> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let relevant = event.is_ok_and(|event| {
+                !event.kind.is_access()
+                    && event
+                        .paths
+                        .iter()
+                        .any(|x| x.file_name() == Some(OsStr::new("config.toml")))
+            });
+
+            if relevant {
+                let _ = tx.send(());
+            }
+        })?;
+
+        watcher.watch(&dir().await?, RecursiveMode::NonRecursive)?;
+
+        Ok(Self {
+            _watcher: watcher,
+            rx,
+        })
+    }
+
+    /// Waits until `config.toml` changes, and then stops changing for a moment.
+    ///
+    /// This is [`None`] if the watcher stopped, which shouldn't really happen.
+    pub async fn changed(&mut self) -> Option<()> {
+        self.rx.recv().await?;
+
+        sleep(SETTLE).await;
+        while self.rx.try_recv().is_ok() {}
+
+        Some(())
+    }
+}
+
+/// Sends [`Messages::ReloadConfig`] to `sender` whenever `config.toml` changes.
+///
+/// Not being able to watch the config isn't worth stopping for, it just won't be reloaded.
+pub async fn watch(sender: Sender<Messages>) -> eyre::Result<()> {
+    let Ok(mut watcher) = Watcher::new().await else {
+        return Ok(());
+    };
+
+    while watcher.changed().await.is_some() {
+        sender.send(Messages::ReloadConfig).await?;
+    }
+
+    Ok(())
+}
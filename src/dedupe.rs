@@ -0,0 +1,254 @@
+//! Has the `dedupe` command, which listens to the start of every track
+//! in a list to find the ones which are probably the same track twice.
+//!
+//! Each track gets a small fingerprint in the style of Haitsma & Kalker, where
+//! every frame of audio becomes 16 bits that say whether the energy difference
+//! between neighbouring frequency bands went up or down since the last frame.
+//! Those bits survive re-encoding & changes in volume, so two copies of a track
+//! end up with nearly the same bits, while different tracks differ in about half.
+
+use std::io::Cursor;
+
+use bytes::Bytes;
+use futures::{stream, StreamExt};
+use rodio::{Decoder, Source};
+use tokio::task;
+
+use crate::{
+    download::progress,
+    info,
+    player::{visualizer::power, TIMEOUT},
+    tracks::{cache::Cache, fetch::Fetcher},
+};
+
+/// How much of the start of each track is downloaded, in bytes.
+///
+/// At the usual bitrates, this is over half a minute of audio.
+const PREFIX: usize = 1024 * 1024;
+
+/// The most audio that's fingerprinted from each track, in seconds.
+const SECONDS: usize = 30;
+
+/// How many frequency bands the energy is measured in,
+/// which is one more than the number of bits per frame.
+const BANDS: usize = 17;
+
+/// The lowest band, in Hz.
+const LOWEST: f32 = 300.0;
+
+/// The highest band, in Hz.
+const HIGHEST: f32 = 3000.0;
+
+/// How quiet a sample has to be to count as silence, which is skipped at the start of tracks.
+const SILENCE: f32 = 0.01;
+
+/// How many frames two fingerprints have to overlap by to be compared, which is 5 seconds.
+const MIN_FRAMES: usize = 100;
+
+/// How many frames one fingerprint can be shifted by against the other, which is 1 second.
+///
+/// This lines up copies of a track which start a little earlier or later.
+const MAX_SHIFT: usize = 20;
+
+/// How many frames are checked before giving up on a shift early.
+const PEEK: usize = 40;
+
+/// The fraction of bits which can differ in the first [`PEEK`] frames before giving up on a shift.
+const PEEK_THRESHOLD: f32 = 0.4;
+
+/// The fraction of bits which can differ for two tracks to count as duplicates.
+const THRESHOLD: f32 = 0.3;
+
+/// Works out the fingerprint of the start of a track, with one [`u16`] per frame.
+///
+/// Frames are a fifth of a second long, and start every twentieth of a second.
+fn fingerprint(data: Bytes) -> eyre::Result<Vec<u16>> {
+    let decoder = Decoder::new(Cursor::new(data))?;
+    let rate = decoder.sample_rate();
+    let channels = usize::from(decoder.channels().max(1));
+
+    let samples: Vec<f32> = decoder
+        .convert_samples::<f32>()
+        .take(rate as usize * channels * SECONDS)
+        .collect();
+
+    let mono: Vec<f32> = samples
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect();
+
+    Ok(frames(&mono, rate as f32))
+}
+
+/// Works out the bits of each frame of `samples`, which are mono, skipping any silence at the start.
+fn frames(samples: &[f32], rate: f32) -> Vec<u16> {
+    let start = samples
+        .iter()
+        .position(|x| x.abs() > SILENCE)
+        .unwrap_or(samples.len());
+
+    let frequencies: Vec<f32> = (0..BANDS)
+        .map(|band| LOWEST * (HIGHEST / LOWEST).powf(band as f32 / (BANDS - 1) as f32))
+        .collect();
+
+    let window = (rate / 5.0) as usize;
+    let hop = (rate / 20.0) as usize;
+    let energies: Vec<Vec<f32>> = samples[start..]
+        .windows(window.max(1))
+        .step_by(hop.max(1))
+        .map(|window| {
+            frequencies
+                .iter()
+                .map(|frequency| power(window, *frequency, rate))
+                .collect()
+        })
+        .collect();
+
+    energies
+        .windows(2)
+        .map(|pair| {
+            (0..BANDS - 1).fold(0u16, |bits, band| {
+                let now = pair[1][band] - pair[1][band + 1];
+                let before = pair[0][band] - pair[0][band + 1];
+
+                (bits << 1u8) | u16::from(now > before)
+            })
+        })
+        .collect()
+}
+
+/// The fraction of bits which differ between two fingerprints of the same length.
+fn errors(first: &[u16], second: &[u16]) -> f32 {
+    let differing: u32 = first
+        .iter()
+        .zip(second)
+        .map(|(x, y)| (x ^ y).count_ones())
+        .sum();
+
+    differing as f32 / (first.len() * 16) as f32
+}
+
+/// The fraction of bits which differ between two fingerprints, at the shift where they line up best.
+///
+/// This is [`None`] if they never overlap by [`MIN_FRAMES`], or if they didn't come close at any shift.
+fn distance(first: &[u16], second: &[u16]) -> Option<f32> {
+    let shifts = (0..=MAX_SHIFT)
+        .map(|shift| (shift, 0))
+        .chain((1..=MAX_SHIFT).map(|shift| (0, shift)));
+
+    shifts
+        .filter_map(|(skip_first, skip_second)| {
+            let (first, second) = (first.get(skip_first..)?, second.get(skip_second..)?);
+            let len = first.len().min(second.len());
+            if len < MIN_FRAMES || errors(&first[..PEEK], &second[..PEEK]) > PEEK_THRESHOLD {
+                return None;
+            }
+
+            Some(errors(&first[..len], &second[..len]))
+        })
+        .min_by(f32::total_cmp)
+}
+
+/// Finds the group that track `i` is in, where `groups` has the index of another track
+/// in the same group for every track, and the first track of a group points at itself.
+fn find(groups: &mut [usize], mut i: usize) -> usize {
+    while groups[i] != i {
+        groups[i] = groups[groups[i]];
+        i = groups[i];
+    }
+
+    i
+}
+
+/// Fingerprints the start of every track in a list, `workers` at a time,
+/// and shows the groups of tracks which are probably duplicates.
+///
+/// Nothing is removed from the list, since the fingerprints can be wrong,
+/// so it's up to the user to decide which copies to keep.
+pub async fn dedupe(tracks: Option<String>, workers: u8) -> eyre::Result<()> {
+    let list = info::load(tracks).await?;
+    let paths: Vec<String> = list.paths().collect();
+
+    // Cached tracks don't have to be downloaded again, but the prefixes aren't cached.
+    let fetcher = Fetcher::new(TIMEOUT, Cache::new(true, None)?, None)?;
+    let mut fingerprints = stream::iter(paths.iter().enumerate())
+        .map(|(i, url)| {
+            let (list, fetcher) = (&list, &fetcher);
+            async move {
+                let result: eyre::Result<Vec<u16>> = async {
+                    let data = list.prefix(url, fetcher, PREFIX).await?;
+                    task::spawn_blocking(move || fingerprint(data)).await?
+                }
+                .await;
+
+                (i, result)
+            }
+        })
+        .buffer_unordered(usize::from(workers));
+
+    let total = paths.len();
+    let mut prints = vec![Vec::new(); total];
+    let mut failed = Vec::new();
+    let mut done = 0;
+
+    progress(done, failed.len(), total)?;
+    while let Some((i, result)) = fingerprints.next().await {
+        match result {
+            Ok(print) => {
+                prints[i] = print;
+                done += 1;
+            }
+            Err(error) => failed.push((&paths[i], error)),
+        }
+
+        progress(done, failed.len(), total)?;
+    }
+
+    println!();
+
+    for (url, error) in &failed {
+        eprintln!("couldn't fingerprint {url}: {error}");
+    }
+
+    let mut groups: Vec<usize> = (0..total).collect();
+    for i in 0..total {
+        for j in (i + 1)..total {
+            if distance(&prints[i], &prints[j]).is_some_and(|x| x <= THRESHOLD) {
+                let (first, second) = (find(&mut groups, i), find(&mut groups, j));
+                groups[first.max(second)] = first.min(second);
+            }
+        }
+    }
+
+    let mut duplicates: Vec<Vec<&str>> = vec![Vec::new(); total];
+    for (i, path) in paths.iter().enumerate() {
+        let group = find(&mut groups, i);
+        duplicates[group].push(path);
+    }
+    duplicates.retain(|x| x.len() > 1);
+
+    if duplicates.is_empty() {
+        println!("no probable duplicates in {}", list.name);
+        return Ok(());
+    }
+
+    println!(
+        "{} groups of probable duplicates in {}:",
+        duplicates.len(),
+        list.name
+    );
+
+    for group in &duplicates {
+        println!();
+        for path in group {
+            println!(" {path}");
+        }
+    }
+
+    println!(
+        "\nafter checking by ear, extra copies can be removed with `lowfi list rm {} <pattern>`",
+        list.name
+    );
+
+    Ok(())
+}
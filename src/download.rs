@@ -0,0 +1,96 @@
+//! Has the `download` command, which saves every track of a list
+//! to the cache so that it can be played without an internet connection.
+
+use std::{
+    fmt::Write as _,
+    io::{stdout, Write},
+};
+
+use eyre::bail;
+use futures::{stream, StreamExt};
+
+use crate::{
+    info,
+    player::TIMEOUT,
+    tracks::{cache::Cache, fetch::Fetcher},
+};
+
+/// The width of the progress bar, in characters.
+const WIDTH: usize = 24;
+
+/// Redraws the progress bar on the current line.
+pub fn progress(done: usize, failed: usize, total: usize) -> eyre::Result<()> {
+    let filled = (done + failed) * WIDTH / total.max(1);
+
+    let mut line = format!(
+        "\r [{}{}] {done}/{total}",
+        "/".repeat(filled),
+        " ".repeat(WIDTH - filled)
+    );
+
+    if failed > 0 {
+        write!(line, " ({failed} failed)")?;
+    }
+
+    let mut stdout = stdout();
+    stdout.write_all(line.as_bytes())?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Downloads every track in a list which isn't cached yet, `workers` at a time.
+///
+/// Since tracks which are already cached are skipped, an interrupted
+/// download can be picked up again just by running the command again.
+pub async fn download(tracks: Option<String>, workers: u8) -> eyre::Result<()> {
+    let list = info::load(tracks).await?;
+
+    // There's no size limit here, since evicting tracks while
+    // downloading the rest of the list would defeat the point.
+    let cache = Cache::new(true, None)?;
+
+    let mut missing = Vec::new();
+    for path in list.paths() {
+        if cache.size(&path).await.is_none() {
+            missing.push(path);
+        }
+    }
+
+    let total = list.len();
+    let mut done = total - missing.len();
+    let mut failed = Vec::new();
+
+    let fetcher = Fetcher::new(TIMEOUT, cache, None)?;
+    let mut downloads = stream::iter(&missing)
+        .map(|url| {
+            let (list, fetcher) = (&list, &fetcher);
+            async move { (url, list.download(url, fetcher, false).await) }
+        })
+        .buffer_unordered(usize::from(workers));
+
+    progress(done, failed.len(), total)?;
+    while let Some((url, result)) = downloads.next().await {
+        match result {
+            Ok(_) => done += 1,
+            Err(error) => failed.push((url, error)),
+        }
+
+        progress(done, failed.len(), total)?;
+    }
+
+    println!();
+
+    for (url, error) in &failed {
+        eprintln!("couldn't download {url}: {error}");
+    }
+
+    if !failed.is_empty() {
+        bail!(
+            "{} tracks couldn't be downloaded, run this again to retry them",
+            failed.len()
+        );
+    }
+
+    Ok(())
+}
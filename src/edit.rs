@@ -0,0 +1,198 @@
+//! Has all of the functions for the `list` command, which
+//! manages the track lists in the data directory.
+
+use std::path::PathBuf;
+
+use eyre::{bail, eyre};
+use tokio::fs;
+use url::Url;
+
+use crate::{tracks::list::List, ListCommands};
+
+/// A track list file that's being edited.
+///
+/// This works on the raw lines of the file, so that
+/// the formatting of entries which aren't touched is preserved.
+struct Editor {
+    /// The path to the list file.
+    path: PathBuf,
+
+    /// The raw lines of the file, where `lines[0]` is the header/base.
+    lines: Vec<String>,
+}
+
+impl Editor {
+    /// Makes sure that a header is either empty or a valid URL.
+    fn validate_header(header: &str) -> eyre::Result<()> {
+        if header.is_empty() {
+            return Ok(());
+        }
+
+        let url =
+            Url::parse(header).map_err(|error| eyre!("invalid header {header:?}: {error}"))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("invalid header {header:?}: the base URL should use http or https");
+        }
+
+        Ok(())
+    }
+
+    /// Opens an existing list from the data directory, validating it's header.
+    async fn open(name: &str) -> eyre::Result<Self> {
+        let path = List::data_path(name)?;
+        if !path.exists() {
+            bail!("the list {name:?} doesn't exist, create it with `lowfi list new {name} <base>`");
+        }
+
+        let raw = fs::read_to_string(&path).await?;
+        let lines: Vec<String> = raw.lines().map(ToOwned::to_owned).collect();
+        Self::validate_header(lines.first().map_or("", |x| x.trim()))?;
+
+        Ok(Self { path, lines })
+    }
+
+    /// Writes the list back to the data directory.
+    async fn save(&self) -> eyre::Result<()> {
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+
+        fs::write(&self.path, contents).await?;
+
+        Ok(())
+    }
+
+    /// Returns the indices of all of the tracks which contain `pattern`.
+    ///
+    /// This is case insensitive, and skips the header, comments & disabled tracks.
+    fn matches(&self, pattern: &str) -> Vec<usize> {
+        let pattern = pattern.to_lowercase();
+
+        self.lines
+            .iter()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, line)| {
+                !line.trim().is_empty()
+                    && !List::is_directive(line)
+                    && !List::is_comment(line)
+                    && line.to_lowercase().contains(&pattern)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Formats a track entry from a path and an optional custom name.
+    fn entry(path: &str, name: Option<&str>) -> String {
+        name.map_or_else(|| path.to_owned(), |name| format!("{path}!{name}"))
+    }
+}
+
+/// Creates a new, empty list in the data directory.
+async fn new(name: &str, base: &str) -> eyre::Result<()> {
+    let path = List::data_path(name)?;
+    if path.exists() {
+        bail!("the list {name:?} already exists");
+    }
+
+    Editor::validate_header(base)?;
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    fs::write(&path, format!("{base}\n")).await?;
+    println!("created {}", path.display());
+
+    Ok(())
+}
+
+/// Adds a track to a list, making sure it isn't already in there.
+async fn add(name: &str, track: &str, custom: Option<&str>) -> eyre::Result<()> {
+    let mut editor = Editor::open(name).await?;
+    let Some(base) = editor.lines.first() else {
+        bail!("{name:?} is empty, so it doesn't have a base URL to add {track:?} to");
+    };
+    let base = base.trim().to_owned();
+
+    let track = track.trim();
+    let track = if base.is_empty() {
+        track
+    } else {
+        // If the full URL was given, then we only need the part after the base.
+        track.strip_prefix(&base).unwrap_or(track)
+    };
+
+    if base.is_empty() && !track.contains("://") {
+        bail!("{name:?} doesn't have a base URL, so {track:?} has to be a full URL");
+    }
+
+    let duplicate = editor.lines.iter().skip(1).any(|line| {
+        if List::is_directive(line) || List::is_comment(line) {
+            return false;
+        }
+
+        let (path, _) = List::split_entry(line);
+        !path.is_empty() && path == track
+    });
+
+    if duplicate {
+        bail!("{track:?} is already in {name:?}");
+    }
+
+    let entry = Editor::entry(track, custom);
+    println!("added {entry}");
+    editor.lines.push(entry);
+    editor.save().await
+}
+
+/// Removes every track which contains `pattern` from a list.
+async fn remove(name: &str, pattern: &str) -> eyre::Result<()> {
+    let mut editor = Editor::open(name).await?;
+    let matches = editor.matches(pattern);
+
+    if matches.is_empty() {
+        bail!("no tracks in {name:?} match {pattern:?}");
+    }
+
+    // Removing in reverse so that the indices stay valid.
+    for i in matches.into_iter().rev() {
+        println!("removed {}", editor.lines.remove(i));
+    }
+
+    editor.save().await
+}
+
+/// Gives the single track which contains `pattern` a new display name.
+async fn rename(name: &str, pattern: &str, new: &str) -> eyre::Result<()> {
+    let mut editor = Editor::open(name).await?;
+
+    let i = match editor.matches(pattern).as_slice() {
+        [] => bail!("no tracks in {name:?} match {pattern:?}"),
+        [i] => *i,
+        matches => bail!(
+            "{} tracks in {name:?} match {pattern:?}, be more specific",
+            matches.len()
+        ),
+    };
+
+    let (path, _) = List::split_entry(&editor.lines[i]);
+    let entry = Editor::entry(path, Some(new));
+
+    println!("renamed {} to {entry}", editor.lines[i]);
+    editor.lines[i] = entry;
+    editor.save().await
+}
+
+/// Runs one of the `list` subcommands.
+pub async fn edit(command: ListCommands) -> eyre::Result<()> {
+    match command {
+        ListCommands::New { list, base } => new(&list, &base.unwrap_or_default()).await,
+        ListCommands::Add { list, track, name } => add(&list, &track, name.as_deref()).await,
+        ListCommands::Rm { list, pattern } => remove(&list, &pattern).await,
+        ListCommands::Rename {
+            list,
+            pattern,
+            name,
+        } => rename(&list, &pattern, &name).await,
+    }
+}
@@ -0,0 +1,84 @@
+//! Has the `info` command, which shows a summary of a track list.
+
+use reqwest::header::HeaderName;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{
+    config::Config,
+    player::{
+        bookmarks::{self, Bookmarks},
+        ui::{environment::CAPABILITIES, format, Window},
+    },
+    tracks::{cache::Cache, list::List},
+};
+
+/// Loads a list the same way `--tracks` does, including the bookmarks.
+pub async fn load(tracks: Option<String>) -> eyre::Result<List> {
+    if tracks.as_deref() == Some(bookmarks::NAME) {
+        Bookmarks::load().await?.list().await
+    } else {
+        List::load(&tracks).await
+    }
+}
+
+/// Prints a summary of a track list in a window, like the one used by the player.
+///
+/// The total size is only an estimate based on the tracks which are
+/// already cached, since getting the real size would mean downloading everything.
+pub async fn info(tracks: Option<String>) -> eyre::Result<()> {
+    let list = load(tracks).await?;
+    let cache = Cache::new(false, None)?;
+
+    let mut cached = 0;
+    let mut size = 0;
+    for path in list.paths() {
+        if let Some(x) = cache.size(&path).await {
+            cached += 1;
+            size += x;
+        }
+    }
+
+    let mut rows = vec![
+        ("name", list.name.clone()),
+        ("tracks", list.len().to_string()),
+    ];
+
+    if !list.base().is_empty() {
+        rows.push(("base", list.base().to_owned()));
+    }
+
+    if !list.headers().is_empty() {
+        let names: Vec<&str> = list.headers().keys().map(HeaderName::as_str).collect();
+        rows.push(("headers", names.join(", ")));
+    }
+
+    if let Some(username) = list.username() {
+        rows.push(("auth", username.to_owned()));
+    }
+
+    rows.push(("cached", format!("{cached} ({})", format::size(size))));
+
+    if let Some(average) = size.checked_div(cached) {
+        let estimate = average * list.len() as u64;
+        rows.push(("size", format!("~{}", format::size(estimate))));
+    }
+
+    print(rows).await
+}
+
+/// Prints `rows` of labels & values in a window, padded so that they all line up.
+pub async fn print(rows: Vec<(&str, String)>) -> eyre::Result<()> {
+    let lines: Vec<String> = rows
+        .into_iter()
+        .map(|(label, value)| format!("{label}: {value}"))
+        .collect();
+
+    let width = lines.iter().map(|x| x.width()).max().unwrap_or_default();
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|x| format!("{x}{:1$}", "", width - x.width()))
+        .collect();
+
+    let theme = CAPABILITIES.theme(Config::load().await?.theme);
+    Window::new(width, false, theme).print(&lines)
+}
@@ -25,6 +25,7 @@
     clippy::partial_pub_fields,
     clippy::unseparated_literal_suffix,
     clippy::self_named_module_files,
+    clippy::missing_trait_methods,
     // TODO: Disallow these lints later.
     clippy::unwrap_used,
     clippy::pattern_type_mismatch,
@@ -38,10 +39,25 @@
     clippy::cast_lossless,
 )]
 
+use std::{path::PathBuf, time::Duration};
+
 use clap::{Parser, Subcommand};
+use player::ui::{
+    environment::{self, Images},
+    theme::Preset,
+    Cue,
+};
+use tracks::deck::Shuffle;
 
+mod config;
+mod dedupe;
+mod download;
+mod edit;
+mod info;
 mod play;
 mod player;
+mod stats;
+mod storage;
 mod tracks;
 
 #[allow(clippy::all, clippy::pedantic, clippy::nursery, clippy::restriction)]
@@ -59,20 +75,195 @@ struct Args {
     #[clap(long, short)]
     minimalist: bool,
 
+    /// Whether to draw the player without borders.
+    #[clap(long)]
+    borderless: bool,
+
+    /// Whether to slowly scroll track names which are too long to fit,
+    /// rather than cutting them off.
+    #[clap(long)]
+    marquee: bool,
+
+    /// Whether to show a small spectrum of the audio that's playing.
+    #[clap(long)]
+    visualizer: bool,
+
+    /// A line to show at the top of the window, like "{list} · {time}".
+    /// The variables are {list}, {time}, {played}, {volume}, {track},
+    /// {artist}, {album}, {codec}, {elapsed} & {duration}.
+    #[clap(long)]
+    title: Option<String>,
+
+    /// Whether to show the album art from the tracks' tags above the window,
+    /// in terminals which support the kitty, iTerm2 or sixel image protocols.
+    #[clap(long)]
+    art: bool,
+
+    /// Which image protocol to draw `--art` with, for terminals
+    /// where it can't be guessed from the environment.
+    #[clap(long, value_enum)]
+    art_backend: Option<Images>,
+
+    /// Whether to leave the mouse alone, for terminals where
+    /// capturing it gets in the way of selecting text.
+    #[clap(long)]
+    no_mouse: bool,
+
+    /// Whether to draw everything with plain ASCII, for terminals & serial consoles
+    /// which can't show box drawing characters. This is automatic if the locale isn't UTF-8.
+    #[clap(long)]
+    ascii: bool,
+
+    /// How to pick the next track. "deck" shuffles the whole list and plays through it,
+    /// so nothing repeats until everything has been played.
+    #[clap(long, value_enum, default_value_t)]
+    shuffle: Shuffle,
+
     /// Whether to start lowfi paused.
     #[clap(long, short)]
     paused: bool,
 
+    /// Whether to pick up where the last session left off, starting paused
+    /// if it was paused and using the same track list unless `--tracks` is set.
+    #[clap(long)]
+    resume: bool,
+
+    /// Whether to save downloaded tracks in the data directory, so that they
+    /// don't have to be downloaded again. Cached tracks are always used.
+    #[clap(long)]
+    cache: bool,
+
+    /// The maximum size of the cache in MB. When it's full,
+    /// the tracks which were played least recently are removed.
+    /// This can also be set with `cache_size` in config.toml.
+    #[clap(long, value_name = "MB")]
+    cache_size: Option<u64>,
+
+    /// The maximum download speed in KB/s, to avoid saturating
+    /// a metered or shared connection.
+    #[clap(long, value_name = "KB/s")]
+    max_bandwidth: Option<u64>,
+
+    /// Serves an HTTP API on this port, for controlling lowfi from
+    /// home automation or a web dashboard.
+    #[cfg(feature = "api")]
+    #[clap(long, value_name = "PORT")]
+    api_port: Option<u16>,
+
+    /// The address that the HTTP API listens on, which is only
+    /// reachable from this computer by default.
+    #[cfg(feature = "api")]
+    #[clap(long, value_name = "ADDRESS", default_value = "127.0.0.1")]
+    api_host: String,
+
+    /// Whether to save UI preferences, like `--minimalist`, to `config.toml`
+    /// in the config directory when quitting, so they're used next time.
+    #[clap(long)]
+    save_on_exit: bool,
+
+    /// Stops downloading tracks in advance after being paused for this many minutes,
+    /// which saves bandwidth & battery. Downloading resumes as soon as lowfi is unpaused.
+    #[clap(long)]
+    idle_after: Option<u64>,
+
+    /// Whether to start playing tracks while they're still downloading,
+    /// if there aren't any already downloaded ones to play.
+    #[clap(long)]
+    stream: bool,
+
+    /// Whether to treat the connection as unmetered, even if it's detected as metered.
+    /// Otherwise, lowfi downloads fewer tracks in advance on metered connections.
+    #[clap(long)]
+    assume_unmetered: bool,
+
+    /// How many tracks to keep downloaded in advance, so that skipping is instant.
+    /// This defaults to 5, or 1 on a metered connection.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(1..=10))]
+    buffer_size: Option<u8>,
+
+    /// How many tracks to download at the same time when filling up the buffer.
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
+    workers: u8,
+
+    /// How long to wait before retrying a failed download, in seconds.
+    /// This doubles with each retry in a row, up to a minute.
+    #[clap(long, default_value_t = 5)]
+    retry_delay: u64,
+
+    /// How many times in a row to retry a failed download before giving up.
+    /// By default, lowfi will keep trying forever.
+    #[clap(long)]
+    max_retries: Option<u32>,
+
+    /// A sound to loop underneath the music, which is either "rain", "fire",
+    /// or the path to an audio file. This can also be set in `config.toml`.
+    #[clap(long, value_name = "SOUND")]
+    ambience: Option<String>,
+
+    /// Whether to pause when the audio device goes away, like when headphones are
+    /// unplugged, rather than carrying on with whichever device is the default.
+    #[clap(long)]
+    pause_on_disconnect: bool,
+
+    /// How many frames of audio to give the output device at a time. Raising this
+    /// can fix crackling on slower machines & with Bluetooth, at the cost of latency.
+    #[clap(long, value_name = "FRAMES", value_parser = clap::value_parser!(u32).range(1..))]
+    audio_buffer: Option<u32>,
+
+    /// The sample rate to play at in Hz, instead of the output device's default.
+    #[clap(long, value_name = "HZ", value_parser = clap::value_parser!(u32).range(1..))]
+    sample_rate: Option<u32>,
+
     /// Whether to include ALSA & other logs.
     #[clap(long, short)]
     debug: bool,
 
+    /// How long to play for before quitting, like `25m` or `1h30m`.
+    /// If lowfi isn't run in a terminal, this also hides the UI.
+    #[clap(long, value_parser = play::parse_duration)]
+    duration: Option<Duration>,
+
+    /// Prints a line of JSON whenever something changes instead of showing the UI,
+    /// for use as a custom module in waybar.
+    #[clap(long)]
+    status_line: bool,
+
+    /// Writes "artist – title" to this file whenever a new track starts, as well as
+    /// all of the track's metadata to a JSON file next to it, for OBS text sources.
+    #[clap(long, value_name = "PATH")]
+    now_playing_file: Option<PathBuf>,
+
+    /// How long to fade out the audio for when quitting, in milliseconds.
+    /// Set this to 0 to stop immediately, which is also what happens without the UI.
+    #[clap(long, default_value_t = 250)]
+    fade: u64,
+
+    /// Lets you know when a new track starts, either by ringing
+    /// the terminal bell or by flashing the window's borders.
+    #[clap(long, value_enum)]
+    cue: Option<Cue>,
+
+    /// A built in theme to use, instead of the one in `config.toml`.
+    #[clap(long, value_enum)]
+    theme: Option<Preset>,
+
     /// The width of the player, from 0 to 32.
     #[clap(long, short, default_value_t = 3)]
     width: usize,
 
+    /// Whether to make the player as wide as the terminal, and to keep
+    /// it that way when the terminal is resized. This overrides `--width`.
+    #[clap(long)]
+    auto_width: bool,
+
+    /// The locale used to format numbers, like `fr_FR`.
+    /// This defaults to the system locale.
+    #[clap(long)]
+    locale: Option<String>,
+
     /// This is either a path, or a name of a file in the data directory (eg. ~/.local/share/lowfi).
-    #[clap(long, short, alias = "list", short_alias = 'l')]
+    /// Use "bookmarks" to play your bookmarked tracks.
+    #[clap(long, short, aliases = ["list", "track-list"], short_alias = 'l')]
     tracks: Option<String>,
 
     /// The command that was ran.
@@ -84,28 +275,233 @@ struct Args {
 /// Defines all of the extra commands lowfi can run.
 #[derive(Subcommand)]
 enum Commands {
-    /// Scrapes the lofi girl website file server for files.
-    Scrape {
-        /// The file extension to search for, defaults to mp3.
-        #[clap(long, short, default_value = "mp3")]
-        extension: String,
+    /// Scrapes tracks from the lofi girl website file server, or any of the other
+    /// sources listed in `lowfi help scrape`, into a track list.
+    Scrape(scrape::Options),
+
+    /// Shows a summary of a track list, without playing anything.
+    Info {
+        /// This works the same as `--tracks`, and defaults to the built in list.
+        tracks: Option<String>,
+    },
+
+    /// Shows how much has been listened to, along with the most played tracks & artists,
+    /// the most skipped tracks, and how much of each list was played.
+    Stats {
+        /// How far back to look.
+        #[clap(long, short, value_enum, default_value_t)]
+        range: stats::Range,
+
+        /// How many of the most played tracks, artists & skipped tracks to show.
+        #[clap(long, short, default_value_t = 3)]
+        top: usize,
+    },
+
+    /// Downloads every track of a list into the cache, so it can be played offline.
+    Download {
+        /// This works the same as `--tracks`, and defaults to the built in list.
+        tracks: Option<String>,
+
+        /// How many tracks to download at the same time.
+        #[clap(long, short, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=16))]
+        workers: u8,
+    },
+
+    /// Looks for tracks which are probably in a list more than once, by how they sound.
+    Dedupe {
+        /// This works the same as `--tracks`, and defaults to the built in list.
+        tracks: Option<String>,
+
+        /// How many tracks to fingerprint at the same time.
+        #[clap(long, short, default_value_t = 4, value_parser = clap::value_parser!(u8).range(1..=16))]
+        workers: u8,
+    },
+
+    /// Shows how much space lowfi's data takes up, split up by what it's for.
+    Storage {
+        /// Removes the least recently played tracks from the cache until
+        /// it fits in `--cache-size`, or `cache_size` in config.toml.
+        #[clap(long)]
+        clean: bool,
+    },
 
-        /// Whether to include the full HTTP URL or just the distinguishing part.
+    /// Manages the cache of downloaded tracks.
+    Cache {
+        /// The action to perform on the cache.
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+
+    /// Manages the track lists in the data directory.
+    List {
+        /// The action to perform on the list.
+        #[command(subcommand)]
+        command: ListCommands,
+    },
+
+    /// Controls the running instance of lowfi, which is handy for keybindings.
+    #[cfg(unix)]
+    Ctl {
+        /// What to tell lowfi to do.
+        #[command(subcommand)]
+        command: CtlCommands,
+    },
+
+    /// Prints what the running instance of lowfi is playing, which is handy for status bars.
+    #[cfg(unix)]
+    NowPlaying {
+        /// What to print, which can have the same variables as `--title`,
+        /// like "{artist} - {title} {elapsed}/{duration}".
+        #[clap(long, short, default_value = "{track}")]
+        format: String,
+    },
+
+    /// Logs in to or out of Last.fm, so that lowfi can scrobble what you listen to.
+    #[cfg(feature = "scrobble")]
+    Scrobble {
+        /// The action to perform.
+        #[command(subcommand)]
+        command: ScrobbleCommands,
+    },
+}
+
+/// Defines the different ways the cache can be managed.
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Removes every track from the cache.
+    Purge,
+}
+
+/// Defines the different ways that a running instance of lowfi can be controlled.
+#[cfg(unix)]
+#[derive(Subcommand)]
+enum CtlCommands {
+    /// Skips to the next track.
+    Next,
+
+    /// Goes back to the track that played before the current one.
+    Previous,
+
+    /// Unpauses playback.
+    Play,
+
+    /// Pauses playback.
+    Pause,
+
+    /// Pauses playback, or unpauses it if it's paused.
+    PlayPause,
+
+    /// Bookmarks the current track, or removes the bookmark.
+    Bookmark,
+
+    /// Quits lowfi.
+    Quit,
+
+    /// Changes the volume.
+    Volume {
+        /// The new volume in percent, or a change to it like `+10` or `-10`.
+        #[clap(allow_hyphen_values = true)]
+        change: String,
+    },
+
+    /// Prints what's playing as JSON.
+    NowPlaying,
+}
+
+/// Defines the different ways the Last.fm session can be managed.
+#[cfg(feature = "scrobble")]
+#[derive(Subcommand)]
+enum ScrobbleCommands {
+    /// Logs in with your own Last.fm API account, which you can make for free.
+    Login {
+        /// The API key of the API account.
+        api_key: String,
+
+        /// The shared secret of the API account.
+        secret: String,
+    },
+
+    /// Logs out, so that lowfi stops scrobbling.
+    Logout,
+}
+
+/// Defines the different ways track lists in the data directory can be edited.
+#[derive(Subcommand)]
+enum ListCommands {
+    /// Creates a new, empty list.
+    New {
+        /// The name of the list, without the `.txt` extension.
+        list: String,
+
+        /// The base URL which is prepended to each track.
+        /// This can be left out if all of the tracks are full URLs.
+        base: Option<String>,
+    },
+
+    /// Adds a track to a list.
+    Add {
+        /// The name of the list, without the `.txt` extension.
+        list: String,
+
+        /// The path or full URL of the track.
+        track: String,
+
+        /// A custom display name for the track.
         #[clap(long, short)]
-        include_full: bool,
+        name: Option<String>,
+    },
+
+    /// Removes all of the tracks which contain a pattern from a list.
+    Rm {
+        /// The name of the list, without the `.txt` extension.
+        list: String,
+
+        /// The text to search for, which is case insensitive.
+        pattern: String,
+    },
+
+    /// Sets the display name of the track which contains a pattern.
+    Rename {
+        /// The name of the list, without the `.txt` extension.
+        list: String,
+
+        /// The text to search for, which is case insensitive.
+        pattern: String,
+
+        /// The new display name.
+        name: String,
     },
 }
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
     let cli = Args::parse();
+    if cli.ascii {
+        environment::force_ascii();
+    }
+
+    if let Some(images) = cli.art_backend {
+        environment::force_images(images);
+    }
 
     if let Some(command) = cli.command {
         match command {
-            Commands::Scrape {
-                extension,
-                include_full,
-            } => scrape::scrape(extension, include_full).await,
+            Commands::Scrape(options) => scrape::scrape(options).await,
+            Commands::Info { tracks } => info::info(tracks).await,
+            Commands::Stats { range, top } => stats::stats(range, top).await,
+            Commands::Download { tracks, workers } => download::download(tracks, workers).await,
+            Commands::Dedupe { tracks, workers } => dedupe::dedupe(tracks, workers).await,
+            Commands::Storage { clean } => storage::storage(clean, cli.cache_size).await,
+            Commands::Cache { command: cache } => tracks::cache::command(cache).await,
+            Commands::List { command: list } => edit::edit(list).await,
+            #[cfg(unix)]
+            Commands::Ctl { command } => player::ipc::ctl(command).await,
+            #[cfg(unix)]
+            Commands::NowPlaying { format } => {
+                player::ipc::now_playing(&format, cli.locale.as_deref()).await
+            }
+            #[cfg(feature = "scrobble")]
+            Commands::Scrobble { command } => player::scrobbler::command(command).await,
         }
     } else {
         play::play(cli).await
@@ -1,16 +1,60 @@
 //! Responsible for the basic initialization & shutdown of the audio server & frontend.
 
-use std::path::PathBuf;
+use std::env;
+use std::io::{stdout, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::process;
 use std::sync::Arc;
+use std::time::Duration;
 
 use eyre::eyre;
+use serde::{Deserialize, Serialize};
 use tokio::fs;
-use tokio::{sync::mpsc, task};
+use tokio::{sync::mpsc, task, time::sleep};
 
+use crate::config::{self, Config};
 use crate::player::Player;
-use crate::player::{ui, Messages};
+use crate::player::{audio, overlay::Overlay, ui, ui::status::Printer, Messages};
+use crate::tracks::log::End;
 use crate::Args;
 
+#[cfg(feature = "api")]
+use crate::player::api;
+#[cfg(unix)]
+use crate::player::ipc;
+#[cfg(feature = "mqtt")]
+use crate::player::mqtt::Publisher;
+#[cfg(feature = "scrobble")]
+use crate::player::scrobbler::Scrobbler;
+
+/// Retrieves the data directory, which is usually `~/.local/share/lowfi`.
+///
+/// This doesn't create the directory, so that has to be done before writing to it.
+/// Tests get a temporary one instead, so that they never touch the real one.
+pub fn data_dir() -> eyre::Result<PathBuf> {
+    if cfg!(test) {
+        return Ok(env::temp_dir().join(format!("lowfi-test-{}", process::id())));
+    }
+
+    Ok(dirs::data_dir()
+        .ok_or_else(|| eyre!("Couldn't find data directory"))?
+        .join("lowfi"))
+}
+
+/// Writes `contents` to `path` without ever leaving it half written.
+///
+/// This works by writing to a temporary file next to it first, and then renaming
+/// that over the original, so a crash or power loss leaves either the old or new version.
+pub async fn write(path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> eyre::Result<()> {
+    let path = path.as_ref();
+    let temporary = path.with_extension("part");
+
+    fs::write(&temporary, contents).await?;
+    fs::rename(temporary, path).await?;
+
+    Ok(())
+}
+
 /// This is the representation of the persistent volume,
 /// which is loaded at startup and saved on shutdown.
 #[derive(Clone, Copy)]
@@ -20,27 +64,14 @@ pub struct PersistentVolume {
 }
 
 impl PersistentVolume {
-    /// Retrieves the config directory.
-    async fn config() -> eyre::Result<PathBuf> {
-        let config = dirs::config_dir()
-            .ok_or(eyre!("Couldn't find config directory"))?
-            .join(PathBuf::from("lowfi"));
-
-        if !config.exists() {
-            fs::create_dir_all(&config).await?;
-        }
-
-        Ok(config)
-    }
-
     /// Returns the volume as a float from 0 to 1.
     pub fn float(self) -> f32 {
         self.inner as f32 / 100.0
     }
 
-    /// Loads the [`PersistentVolume`] from [`dirs::config_dir()`].
+    /// Loads the [`PersistentVolume`] from [`config::dir()`].
     pub async fn load() -> eyre::Result<Self> {
-        let config = Self::config().await?;
+        let config = config::dir().await?;
         let volume = config.join(PathBuf::from("volume.txt"));
 
         // Basically just read from the volume file if it exists, otherwise return 100.
@@ -61,23 +92,247 @@ impl PersistentVolume {
 
     /// Saves `volume` to `volume.txt`.
     pub async fn save(volume: f32) -> eyre::Result<()> {
-        let config = Self::config().await?;
+        let config = config::dir().await?;
         let path = config.join(PathBuf::from("volume.txt"));
 
-        fs::write(path, ((volume * 100.0).abs().round() as u16).to_string()).await?;
+        write(path, ((volume * 100.0).abs().round() as u16).to_string()).await?;
+
+        Ok(())
+    }
+}
+
+/// How the last session ended, which is saved on shutdown so that
+/// `--resume` can pick up where it left off.
+///
+/// The volume isn't included, since that's always kept in `volume.txt`.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Session {
+    /// Whether lowfi was paused when it quit.
+    pub paused: bool,
+
+    /// The `--tracks` argument that was used, if any.
+    pub tracks: Option<String>,
+}
+
+impl Session {
+    /// Retrieves the path to `session.toml` in the data directory.
+    fn path() -> eyre::Result<PathBuf> {
+        Ok(data_dir()?.join("session.toml"))
+    }
+
+    /// Loads the last [`Session`], or the defaults if there wasn't one.
+    ///
+    /// A broken `session.toml` is also just ignored, since it's not worth failing over.
+    pub async fn load() -> eyre::Result<Self> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(toml::from_str(&fs::read_to_string(path).await?).unwrap_or_default())
+    }
+
+    /// Saves the [`Session`] to `session.toml`.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        write(path, toml::to_string(self)?).await?;
 
         Ok(())
     }
+
+    /// Applies the [`Session`] to `args`, so that lowfi starts paused if it was
+    /// paused before, and plays the same list unless another one was specified.
+    pub fn resume(self, args: &mut Args) {
+        args.paused |= self.paused;
+        if args.tracks.is_none() {
+            args.tracks = self.tracks;
+        }
+    }
+}
+
+/// Parses a duration for `--duration`, like `25m`, `1h30m` or `90s`.
+///
+/// A number without a unit is treated as minutes.
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    /// The error for a duration which is too long to be represented.
+    const TOO_LONG: &str = "the duration is too long";
+
+    let text = text.trim();
+    if let Ok(minutes) = text.parse::<u64>() {
+        let seconds = minutes.checked_mul(60).ok_or(TOO_LONG)?;
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total = 0;
+    let mut number = String::new();
+
+    for character in text.chars() {
+        if character.is_ascii_digit() {
+            number.push(character);
+            continue;
+        }
+
+        let multiplier = match character {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(format!("invalid unit {character:?}, expected h, m or s")),
+        };
+
+        let value: u64 = number
+            .parse()
+            .map_err(|_error| format!("expected a number before {character:?}"))?;
+
+        total = value
+            .checked_mul(multiplier)
+            .and_then(|x| x.checked_add(total))
+            .ok_or(TOO_LONG)?;
+        number.clear();
+    }
+
+    if !number.is_empty() {
+        return Err(format!("missing a unit after {number:?}"));
+    }
+
+    if total == 0 {
+        return Err("the duration has to be longer than zero".to_owned());
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Saves the volume.txt, bookmarks.txt, plays.txt, skips.txt, deck & session.toml files
+/// for the next session, where `tracks` is the `--tracks` argument that was used.
+///
+/// The track that's playing is also written to `history.log`, since it won't get to finish.
+/// If `config` is given, the UI preferences are saved to it too, for `--save-on-exit`.
+async fn save(
+    player: &Player,
+    tracks: Option<String>,
+    config: Option<&Config>,
+) -> eyre::Result<()> {
+    player.finish(End::Quit).await;
+    PersistentVolume::save(player.sink.volume()).await?;
+    player.bookmarks.save().await?;
+    player.plays.save().await?;
+    player.skips.save().await?;
+    if let Some(deck) = &player.deck {
+        deck.save().await?;
+    }
+
+    let session = Session {
+        paused: player.sink.is_paused(),
+        tracks,
+    };
+    session.save().await?;
+
+    if let Some(config) = config {
+        ui::config(config).save().await?;
+    }
+
+    Ok(())
 }
 
 /// Initializes the audio server, and then safely stops
 /// it when the frontend quits.
-pub async fn play(args: Args) -> eyre::Result<()> {
+///
+/// If `--duration` is specified, this also stops by itself once it's over.
+pub async fn play(mut args: Args) -> eyre::Result<()> {
+    // Make sure that there's actually somewhere to play audio,
+    // before loading anything or touching the terminal.
+    audio::probe(audio::silent(&args))?;
+
+    let config = Config::load().await?;
+    if args.resume || config.resume {
+        Session::load().await?.resume(&mut args);
+    }
+
+    args.cache_size = args.cache_size.or(config.cache_size);
+
     // Actually initializes the player.
     let player = Arc::new(Player::new(&args).await?);
+    player.configure(config.clone())?;
+
+    let fade = Duration::from_millis(args.fade);
+    let save_on_exit = args.save_on_exit;
+    let tracks = args.tracks.clone();
+
+    // There's no point in showing the UI when lowfi is being run by a script,
+    // as long as it'll still be able to quit by itself.
+    // `--status-line` takes the place of the UI, so it's hidden then too.
+    let interactive = !args.status_line && (args.duration.is_none() || stdout().is_terminal());
+    let status = args
+        .status_line
+        .then(|| Printer::new(&args))
+        .transpose()?
+        .map(|x| task::spawn(x.run(Arc::clone(&player))));
+
+    let overlay = args
+        .now_playing_file
+        .as_deref()
+        .map(Overlay::new)
+        .transpose()?
+        .map(|x| task::spawn(x.run(Arc::clone(&player))));
 
     let (tx, rx) = mpsc::channel(8);
-    let ui = task::spawn(ui::start(Arc::clone(&player), tx.clone(), args));
+
+    // Quits the same way as pressing `q` once the duration is over.
+    if let Some(duration) = args.duration {
+        let tx = tx.clone();
+        task::spawn(async move {
+            sleep(duration).await;
+            tx.send(Messages::Quit).await
+        });
+    }
+
+    // The API has to be started before `args` is given to the UI.
+    #[cfg(feature = "api")]
+    let api = match args.api_port {
+        Some(port) => {
+            let server = api::Server::bind(&args.api_host, port).await?;
+            Some(task::spawn(server.serve(Arc::clone(&player), tx.clone())))
+        }
+        None => None,
+    };
+    let ui = interactive.then(|| {
+        task::spawn(ui::start(
+            Arc::clone(&player),
+            tx.clone(),
+            args,
+            config.clone(),
+        ))
+    });
+
+    // Not being able to listen for `lowfi ctl` isn't worth stopping for.
+    #[cfg(unix)]
+    let ipc = task::spawn(ipc::serve(Arc::clone(&player), tx.clone()));
+
+    // Scrobbling only starts once the user has logged in to Last.fm or set a ListenBrainz token.
+    #[cfg(feature = "scrobble")]
+    let scrobbler = Scrobbler::load(config.listenbrainz_token.clone())
+        .await?
+        .map(|x| task::spawn(x.run(Arc::clone(&player))));
+
+    // Like the hooks, the broker is only read from `config.toml` once.
+    #[cfg(feature = "mqtt")]
+    let mqtt = config
+        .mqtt
+        .clone()
+        .map(|x| task::spawn(Publisher::new(x).run(Arc::clone(&player))));
+
+    // Hooks are read once, so changing them in `config.toml` needs a restart.
+    let hooks = config
+        .hooks
+        .any()
+        .then(|| task::spawn(config.hooks.clone().run(Arc::clone(&player))));
+
+    // Everything else in `config.toml` is applied again whenever it changes.
+    let watcher = task::spawn(config::watch(tx.clone()));
 
     // Sends the player an "init" signal telling it to start playing a song straight away.
     tx.send(Messages::Init).await?;
@@ -85,10 +340,49 @@ pub async fn play(args: Args) -> eyre::Result<()> {
     // Actually starts the player.
     Player::play(Arc::clone(&player), tx.clone(), rx).await?;
 
-    // Save the volume.txt file for the next session.
-    PersistentVolume::save(player.sink.volume()).await?;
+    // This has to be done before fading out, otherwise the volume would always be saved as 0.
+    save(&player, tracks, save_on_exit.then_some(&config)).await?;
+
+    if let Some(hooks) = hooks {
+        hooks.abort();
+    }
+
+    config.hooks.quit(&player).await?;
+
+    if interactive {
+        player.fade_out(fade).await;
+    }
+
     player.sink.stop();
-    ui.abort();
+
+    for task in [ui, status, Some(watcher)].into_iter().flatten() {
+        task.abort();
+    }
+
+    if let Some(overlay) = overlay {
+        overlay.abort();
+    }
+
+    #[cfg(unix)]
+    {
+        ipc.abort();
+        ipc::remove().await?;
+    }
+
+    #[cfg(feature = "api")]
+    if let Some(api) = api {
+        api.abort();
+    }
+
+    #[cfg(feature = "mqtt")]
+    if let Some(mqtt) = mqtt {
+        mqtt.abort();
+    }
+
+    #[cfg(feature = "scrobble")]
+    if let Some(scrobbler) = scrobbler {
+        scrobbler.abort();
+    }
 
     Ok(())
 }
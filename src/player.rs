@@ -2,44 +2,106 @@
 //! This also has the code for the underlying
 //! audio server which adds new tracks.
 
-use std::{collections::VecDeque, ffi::CString, sync::Arc, time::Duration};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
+use ambience::Ambience;
 use arc_swap::ArcSwapOption;
+use bookmarks::Bookmarks;
+use clock::Clock;
 use downloader::Downloader;
-use libc::freopen;
-use reqwest::Client;
-use rodio::{OutputStream, OutputStreamHandle, Sink};
+use eyre::{eyre, WrapErr};
+use history::History;
+use lofi::Lofi;
+use retry::Policy;
+use rodio::{queue::SourcesQueueOutput, Sink};
+use saver::Saver;
+use snapshot::Snapshot;
 use tokio::{
     select,
     sync::{
         mpsc::{Receiver, Sender},
-        RwLock,
+        watch, RwLock,
     },
     task,
     time::sleep,
 };
+use visualizer::Visualizer;
 
 #[cfg(feature = "mpris")]
-use mpris_server::{PlaybackStatus, PlayerInterface, Property};
+use mpris_server::{PlayerInterface, Property};
 
 use crate::{
+    config::Config,
     play::PersistentVolume,
-    tracks::{self, list::List},
+    tracks::{
+        self,
+        cache::Cache,
+        deck::{Deck, Shuffle},
+        fetch::Fetcher,
+        list::List,
+        log::{End, Log},
+        plays::Plays,
+        skips::{Reason, Skips},
+    },
     Args,
 };
 
+pub mod ambience;
+pub mod audio;
+pub mod biquad;
+pub mod bookmarks;
+pub mod clock;
 pub mod downloader;
+#[cfg(feature = "equalizer")]
+pub mod equalizer;
+pub mod history;
+pub mod hooks;
+pub mod lofi;
+pub mod metered;
+pub mod overlay;
+pub mod retry;
+pub mod saver;
+pub mod snapshot;
 pub mod ui;
+pub mod visualizer;
 
 #[cfg(feature = "mpris")]
 pub mod mpris;
 
+#[cfg(feature = "scrobble")]
+pub mod scrobbler;
+
+#[cfg(unix)]
+pub mod ipc;
+
+#[cfg(feature = "api")]
+pub mod api;
+
+#[cfg(feature = "mqtt")]
+pub mod mqtt;
+
+#[cfg(any(unix, feature = "api"))]
+pub mod remote;
+
 /// Handles communication between the frontend & audio player.
 #[derive(PartialEq, Debug, Clone, Copy)]
 pub enum Messages {
     /// Notifies the audio server that it should update the track.
+    ///
+    /// This is only sent when the user skips, so it's recorded as a [`Reason::Manual`] skip.
     Next,
 
+    /// Sent when the current track has finished playing by itself,
+    /// which otherwise works exactly the same as [`Messages::Next`].
+    End,
+
     /// Special in that this isn't sent in a "client to server" sort of way,
     /// but rather is sent by a child of the server when a song has not only
     /// been requested but also downloaded aswell.
@@ -64,14 +126,65 @@ pub enum Messages {
     /// Change the volume of playback.
     ChangeVolume(f32),
 
+    /// Sets the volume of playback to a specific level, from 0 to 1.
+    SetVolume(f32),
+
+    /// Seeks to a fraction of the way through the current track, from 0 to 1.
+    Seek(f32),
+
+    /// Seeks to a position in the current track, which is how MPRIS seeks.
+    #[cfg(feature = "mpris")]
+    SeekTo(Duration),
+
+    /// Sent by a child of the server once a seek has actually happened,
+    /// so that MPRIS can let clients know about the new position.
+    Seeked,
+
+    /// Bookmarks the current track, or removes the bookmark if it already exists.
+    Bookmark,
+
+    /// Loads `config.toml` again, which is sent whenever it changes.
+    ReloadConfig,
+
+    /// Changes the [`equalizer::Equalizer`], which is sent by its keybindings.
+    #[cfg(feature = "equalizer")]
+    Equalize(equalizer::Change),
+
+    /// Turns the [`Lofi`] filter on or off.
+    ToggleLofi,
+
+    /// Changes the [`Ambience`], which is sent by its keybindings.
+    Ambience(ambience::Change),
+
+    /// Downloads the track at this index in the [History] again,
+    /// and queues it up to be played next.
+    Replay(usize),
+
+    /// Goes back to the track that played before the current one,
+    /// which is how MPRIS goes to the previous track.
+    ///
+    /// Sending this again goes further back in the [History], until a track ends another way.
+    Previous,
+
+    /// Sent by a child of the server once the track to go back to has been queued up,
+    /// which ends the current track like [`Messages::Next`], without counting as a skip.
+    Back,
+
+    /// Sent when the current track still couldn't be downloaded
+    /// after `--max-retries` attempts, which stops lowfi with an error.
+    GiveUp,
+
     /// Quits gracefully.
     Quit,
 }
 
-/// The time to wait in between errors.
-const TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait for a request before giving up.
+pub const TIMEOUT: Duration = Duration::from_secs(5);
 
-/// The amount of songs to buffer up.
+/// How often the volume is lowered while fading out.
+const FADE_STEP: Duration = Duration::from_millis(10);
+
+/// The amount of songs to buffer up, unless the connection is [`metered`].
 const BUFFER_SIZE: usize = 5;
 
 /// Main struct responsible for queuing up & playing tracks.
@@ -89,6 +202,9 @@ pub struct Player {
     /// This is [`None`] when lowfi is buffering/loading.
     current: ArcSwapOption<tracks::Info>,
 
+    /// Keeps track of how long the current track has played for.
+    clock: Clock,
+
     /// The tracks, which is a [`VecDeque`] that holds
     /// *undecoded* [Track]s.
     ///
@@ -98,24 +214,97 @@ pub struct Player {
     /// The actual list of tracks to be played.
     list: List,
 
+    /// The user's bookmarked tracks.
+    pub bookmarks: Bookmarks,
+
+    /// The tracks which have been played during this session.
+    pub history: History,
+
+    /// How many times [`Messages::Previous`] has gone back in a row, which is
+    /// reset whenever a track ends any other way.
+    back: AtomicUsize,
+
+    /// How many times each track has been played.
+    pub plays: Plays,
+
+    /// Where each track is written once it stops playing, which is `history.log`.
+    pub log: Log,
+
+    /// How many tracks have been skipped, and why.
+    pub skips: Skips,
+
+    /// The shuffled deck that tracks are drawn from, which is [`None`] unless `--shuffle deck` is set.
+    pub deck: Option<Deck>,
+
+    /// Keeps the samples which were just played, so the UI can draw them.
+    /// This is [`None`] unless `--visualizer` is set.
+    pub visualizer: Option<Arc<Visualizer>>,
+
+    /// The EQ which every track is played through, set up from `config.toml`.
+    #[cfg(feature = "equalizer")]
+    pub equalizer: Arc<equalizer::Equalizer>,
+
+    /// The filter which muffles the tracks & adds vinyl crackle, toggled with `f`.
+    pub lofi: Arc<Lofi>,
+
+    /// The sound which loops underneath the music, like rain.
+    pub ambience: Ambience,
+
+    /// Whether album art is read from the tracks' tags, which is only when `--art`
+    /// is set, or when lowfi is built with MPRIS so that clients can show it.
+    art: bool,
+
     /// The initial volume level.
     volume: PersistentVolume,
 
-    /// The web client, which can contain a `UserAgent` & some
-    /// settings that help lowfi work more effectively.
-    client: Client,
+    /// Downloads the tracks, either from the web or from the cache.
+    fetcher: Fetcher,
+
+    /// How many tracks the [Downloader] downloads at the same time.
+    workers: usize,
+
+    /// How many tracks the [Downloader] tries to keep in the buffer,
+    /// which is lower on a [`metered`] connection.
+    buffer_size: usize,
+
+    /// How failed downloads are retried.
+    retry: Policy,
+
+    /// The retry of the current track that's in progress, if there is one.
+    /// This is [`None`] if the last attempt at downloading it didn't fail.
+    pub retrying: ArcSwapOption<retry::Status>,
+
+    /// The last download or decode that failed, which is shown in the window for a little while.
+    pub failure: ArcSwapOption<retry::Failure>,
 
-    /// The [`OutputStreamHandle`], which also can control some
-    /// playback, is for now unused and is here just to keep it
-    /// alive so the playback can function properly.
-    _handle: OutputStreamHandle,
+    /// Whether tracks should start playing while they're still downloading,
+    /// which only applies when the buffer is empty.
+    stream: bool,
 
-    /// The [`OutputStream`], which is just here to keep the playback
-    /// alive and functioning.
-    _stream: OutputStream,
+    /// When playback was paused, or [`None`] if it's playing.
+    ///
+    /// This is a [`watch`] channel, so that the [Downloader] can wait for it to change.
+    paused: watch::Sender<Option<Instant>>,
+
+    /// How long playback can be paused for before the [Downloader]
+    /// stops downloading tracks in advance, specified with `--idle-after`.
+    idle_after: Option<Duration>,
+
+    /// The output stream that the [`Sink`] is played on, which is
+    /// opened again if the device goes away.
+    ///
+    /// This is [`None`] in tests, which don't have an audio device.
+    output: Option<audio::Output>,
+
+    /// The config that was applied last, which the UI follows to pick up changes to `config.toml`.
+    pub config: watch::Sender<Arc<Config>>,
+
+    /// The latest [`Snapshot`], which is sent again by [`snapshot::publish`] whenever
+    /// it changes, so that anything reacting to the player can just wait on it.
+    pub snapshots: watch::Sender<Snapshot>,
 }
 
-// SAFETY: This is necessary because [OutputStream] does not implement [Send],
+// SAFETY: This is necessary because [rodio::OutputStream] does not implement [Send],
 // due to some limitation with Android's Audio API.
 // I'm pretty sure nobody will use lowfi with android, so this is safe.
 unsafe impl Send for Player {}
@@ -124,105 +313,402 @@ unsafe impl Send for Player {}
 unsafe impl Sync for Player {}
 
 impl Player {
-    /// This gets the output stream while also shutting up alsa with [libc].
-    fn silent_get_output_stream() -> eyre::Result<(OutputStream, OutputStreamHandle)> {
-        // Get the file descriptor to stderr from libc.
-        extern "C" {
-            static stderr: *mut libc::FILE;
+    /// Just a shorthand for setting `current`, which also adds it to the [History].
+    fn set_current(&self, info: tracks::Info) {
+        let info = Arc::new(info);
+
+        self.history.push(Arc::clone(&info));
+        self.current.store(Some(info));
+        ui::redraw();
+    }
+
+    /// A shorthand for checking if `self.current` is [Some].
+    pub fn current_exists(&self) -> bool {
+        self.current.load().is_some()
+    }
+
+    /// Bookmarks the current track, or removes the bookmark if it's already bookmarked.
+    ///
+    /// This returns whether there was a track to bookmark, since nothing happens while loading.
+    async fn bookmark(&self) -> bool {
+        let Some(current) = self.current.load_full() else {
+            return false;
+        };
+
+        self.bookmarks.toggle(&current.full_path).await;
+        ui::redraw();
+
+        true
+    }
+
+    /// Records that the user skipped the current track.
+    ///
+    /// If a track is still loading, then there's nothing to skip,
+    /// so nothing is recorded and this returns false.
+    fn skip(&self) -> bool {
+        let Some(current) = self.current.load_full() else {
+            return false;
+        };
+
+        self.skips.record(Reason::Manual);
+        self.history.skip(&current.full_path);
+        true
+    }
+
+    /// Writes the current track to the [`Log`], since it's stopped playing because of `end`.
+    ///
+    /// Not being able to write it is only shown in the window, rather than stopping playback.
+    pub async fn finish(&self, end: End) {
+        let Some(current) = self.current.load_full() else {
+            return;
+        };
+
+        let result = self
+            .log
+            .record(&current, &self.list.name, self.elapsed(), end)
+            .await;
+
+        if let Err(error) = result {
+            self.alert(format!("couldn't write to history.log: {error}"));
         }
+    }
 
-        // This is a bit of an ugly hack that basically just uses `libc` to redirect alsa's
-        // output to `/dev/null` so that it wont be shoved down our throats.
+    /// Gets how many tracks the buffer is filled up to, from `--buffer-size`.
+    pub const fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
 
-        // The mode which to redirect terminal output with.
-        let mode = CString::new("w")?;
+    /// Gets how many tracks have been downloaded and are waiting to be played.
+    pub async fn buffered(&self) -> usize {
+        self.tracks.read().await.len()
+    }
 
-        // First redirect to /dev/null, which basically silences alsa.
-        let null = CString::new("/dev/null")?;
+    /// Gets the display names of the tracks which are waiting to be played, in order.
+    #[cfg(feature = "mpris")]
+    pub async fn upcoming(&self) -> Vec<String> {
+        self.tracks
+            .read()
+            .await
+            .iter()
+            .map(tracks::Track::display_name)
+            .collect()
+    }
 
-        // SAFETY: Simple enough to be impossible to fail. Hopefully.
-        unsafe {
-            freopen(null.as_ptr(), mode.as_ptr(), stderr);
+    /// Takes a [`Snapshot`] of everything that's going on right now, for anything which shows it.
+    pub async fn snapshot(&self) -> Snapshot {
+        let current = self.current.load_full();
+
+        Snapshot {
+            track: current.as_ref().map(|x| x.name.clone()),
+            artist: current.as_ref().and_then(|x| x.artist.clone()),
+            album: current.as_ref().and_then(|x| x.album.clone()),
+            source: current.as_ref().and_then(|x| x.source.clone()),
+            codec: current.as_ref().and_then(|x| x.codec.map(String::from)),
+            elapsed: self.elapsed(),
+            duration: current.as_ref().and_then(|x| x.duration),
+            volume: self.sink.volume(),
+            paused: self.sink.is_paused(),
+            list: self.list.name.clone(),
+            queued: self.buffered().await,
+            played: self.history.played(),
         }
+    }
+
+    /// Gets how long the current track has played for, according to the [`Clock`].
+    pub fn elapsed(&self) -> Duration {
+        self.clock.elapsed()
+    }
 
-        // Make the OutputStream while stderr is still redirected to /dev/null.
-        let (stream, handle) = OutputStream::try_default()?;
+    /// Applies the audio settings from `config`, which is done again whenever it changes.
+    ///
+    /// Only the settings which are different from the last config are applied, so that
+    /// anything changed with a keybinding is kept unless it was also changed in the file.
+    /// When lowfi starts, the last config is just the defaults.
+    ///
+    /// This fails if the ambience can't be played, in which case everything else is still applied.
+    pub fn configure(&self, config: Config) -> eyre::Result<()> {
+        let old = Arc::clone(&self.config.borrow());
 
-        // Redirect back to the current terminal, so that other output isn't silenced.
-        let tty = CString::new("/dev/tty")?;
+        #[cfg(feature = "equalizer")]
+        self.equalizer.set(old.equalizer, config.equalizer);
+        self.lofi.set(old.lofi, config.lofi);
+        let ambience = self.ambience.set(&old.ambience, &config.ambience);
 
-        // SAFETY: See the first call to `freopen`.
-        unsafe {
-            freopen(tty.as_ptr(), mode.as_ptr(), stderr);
+        self.config.send_replace(Arc::new(config));
+        ambience
+    }
+
+    /// Loads `config.toml` again and applies it, see [`Player::configure`].
+    ///
+    /// If it can't be loaded, then the old config is kept and the error is
+    /// shown in the window, so a typo doesn't make lowfi quit.
+    pub async fn reload(&self) {
+        let result = match Config::load().await {
+            Ok(config) => self.configure(config),
+            Err(error) => Err(error),
+        };
+
+        // TOML errors point at the mistake on the lines after the first one.
+        if let Err(error) = result {
+            let error = error.to_string();
+            self.alert(error.lines().next().unwrap_or_default().to_owned());
+        }
+    }
+
+    /// Pauses or unpauses both the [Sink] & the [`Clock`].
+    pub fn set_paused(&self, paused: bool) {
+        if paused {
+            self.sink.pause();
+        } else {
+            self.sink.play();
         }
 
-        Ok((stream, handle))
+        self.clock.set_paused(paused);
+        self.paused.send_if_modified(|since| {
+            let changed = since.is_some() != paused;
+            if changed {
+                *since = paused.then(Instant::now);
+            }
+
+            changed
+        });
+
+        ui::redraw();
     }
 
-    /// Just a shorthand for setting `current`.
-    fn set_current(&self, info: tracks::Info) {
-        self.current.store(Some(Arc::new(info)));
+    /// Waits until the player isn't idle, which is when it's been paused
+    /// for longer than `--idle-after`. This returns straight away if it isn't.
+    pub async fn wait_until_active(&self) {
+        let Some(limit) = self.idle_after else {
+            return;
+        };
+
+        let mut paused = self.paused.subscribe();
+        loop {
+            let since = *paused.borrow_and_update();
+            match since {
+                Some(since) if since.elapsed() >= limit => (),
+                _ => return,
+            }
+
+            // Nothing else can change it back, so this waits until playback is resumed.
+            if paused.changed().await.is_err() {
+                return;
+            }
+        }
     }
 
-    /// A shorthand for checking if `self.current` is [Some].
-    pub fn current_exists(&self) -> bool {
-        self.current.load().is_some()
+    /// Seeks to `fraction` of the way through the current track, if it's length is known.
+    ///
+    /// This blocks until the [Sink] has actually seeked, which might also
+    /// involve waiting for the track to download if it's being streamed.
+    /// Returns whether the seek worked.
+    pub fn seek(&self, fraction: f32) -> bool {
+        let Some(duration) = self.current.load().as_ref().and_then(|x| x.duration) else {
+            return false;
+        };
+
+        self.seek_to(duration.mul_f32(fraction.clamp(0.0, 1.0)))
+    }
+
+    /// Seeks to `position` in the current track, which is kept within the track if it's length is known.
+    ///
+    /// Like [`Player::seek`], this blocks until the [Sink] has actually seeked,
+    /// and returns whether it worked.
+    pub fn seek_to(&self, position: Duration) -> bool {
+        if !self.current_exists() {
+            return false;
+        }
+
+        let duration = self.current.load().as_ref().and_then(|x| x.duration);
+        let position = duration.map_or(position, |x| position.min(x));
+
+        let seeked = self.sink.try_seek(position).is_ok();
+        if seeked {
+            self.clock.seek(position);
+            ui::redraw();
+        }
+
+        seeked
+    }
+
+    /// Sets the playback speed of both the [Sink] & the [`Clock`].
+    #[cfg(feature = "mpris")]
+    pub fn set_speed(&self, speed: f32) {
+        self.sink.set_speed(speed);
+        self.clock.set_speed(speed);
+    }
+
+    /// Gradually lowers the volume to zero over `duration`, which avoids
+    /// the small click some audio backends make when playback is cut off.
+    ///
+    /// This doesn't do anything if nothing is actually playing.
+    pub async fn fade_out(&self, duration: Duration) {
+        if duration.is_zero() || self.sink.is_paused() || !self.current_exists() {
+            return;
+        }
+
+        let volume = self.sink.volume();
+        let steps = (duration.as_millis() / FADE_STEP.as_millis()).max(1) as u32;
+
+        for step in (0..steps).rev() {
+            self.sink.set_volume(volume * step as f32 / steps as f32);
+            sleep(FADE_STEP).await;
+        }
     }
 
     /// Sets the volume of the sink, and also clamps the value to avoid negative/over 100% values.
     pub fn set_volume(&self, volume: f32) {
         self.sink.set_volume(volume.clamp(0.0, 1.0));
+        ui::redraw();
     }
 
     /// Initializes the entire player, including audio devices & sink.
     ///
     /// This also will load the track list & persistent volume.
     pub async fn new(args: &Args) -> eyre::Result<Self> {
+        Self::build(args, |queues| {
+            audio::Output::new(queues, audio::Settings::new(args), args.pause_on_disconnect)
+                .map(Some)
+        })
+        .await
+    }
+
+    /// Does the actual work of [`Player::new`], where `open` is given the queues of
+    /// the sink & the [`Ambience`] and plays them, usually by opening an [`audio::Output`].
+    ///
+    /// Tests give an `open` which plays the queues itself, so that no audio device is needed.
+    async fn build(
+        args: &Args,
+        open: impl FnOnce([SourcesQueueOutput<f32>; 2]) -> eyre::Result<Option<audio::Output>>,
+    ) -> eyre::Result<Self> {
         // Load the volume file.
         let volume = PersistentVolume::load().await?;
 
-        // Load the track list.
-        let list = List::load(&args.tracks).await?;
+        // Load the bookmarks & play counts.
+        let bookmarks = Bookmarks::load().await?;
+        let plays = Plays::load().await?;
+        let skips = Skips::load().await?;
 
-        // We should only shut up alsa forcefully if we really have to.
-        let (_stream, handle) = if cfg!(target_os = "linux") && !args.alternate && !args.debug {
-            Self::silent_get_output_stream()?
+        // Load the track list, which can also be made up of the bookmarks.
+        let list = if args.tracks.as_deref() == Some(bookmarks::NAME) {
+            bookmarks.list().await?
         } else {
-            OutputStream::try_default()?
+            List::load(&args.tracks).await?
         };
 
-        let sink = Sink::try_new(&handle)?;
+        if list.is_empty() {
+            return Err(eyre!(
+                "The track list \"{}\" doesn't have any tracks",
+                list.name
+            ));
+        }
+
+        let deck = match args.shuffle {
+            Shuffle::Deck => Some(Deck::load(&list.name, list.len()).await?),
+            Shuffle::Weighted => None,
+        };
+
+        // Evicting on startup makes sure that a newly lowered limit applies straight away,
+        // and it's done in the background so that a big cache doesn't hold up the first track.
+        let cache = Cache::new(args.cache, args.cache_size)?;
+        task::spawn(cache.clone().collect());
+
+        // This has to be done before the audio stream is opened, since it isn't `Send`.
+        let metered = !args.assume_unmetered && metered::detect().await;
+
+        let (sink, queue) = Sink::new_idle();
+        let (ambience, background) = Ambience::new(args.ambience.clone());
+        let output = open([queue, background])?;
         if args.paused {
             sink.pause();
         }
 
-        let client = Client::builder()
-            .user_agent(concat!(
-                env!("CARGO_PKG_NAME"),
-                "/",
-                env!("CARGO_PKG_VERSION")
-            ))
-            .timeout(TIMEOUT)
-            .build()?;
+        let fetcher = Fetcher::new(TIMEOUT, cache, args.max_bandwidth)?;
+
+        // On a metered connection, only download what's needed & one at a time,
+        // unless the buffer size was asked for specifically.
+        let (buffer_size, workers) = if metered {
+            (metered::BUFFER_SIZE, 1)
+        } else {
+            (BUFFER_SIZE, args.workers.into())
+        };
+        let buffer_size = args.buffer_size.map_or(buffer_size, usize::from);
 
         let player = Self {
             tracks: RwLock::new(VecDeque::with_capacity(5)),
             current: ArcSwapOption::new(None),
-            client,
+            clock: Clock::new(),
+            fetcher,
+            workers,
+            buffer_size,
+            stream: args.stream,
+            paused: watch::Sender::new(args.paused.then(Instant::now)),
+            idle_after: args
+                .idle_after
+                .map(|x| Duration::from_secs(x.saturating_mul(60))),
+            retry: Policy::new(args.retry_delay, args.max_retries),
+            retrying: ArcSwapOption::new(None),
+            failure: ArcSwapOption::new(None),
             sink,
             volume,
             list,
-            _handle: handle,
-            _stream,
+            bookmarks,
+            history: History::new(),
+            back: AtomicUsize::new(0),
+            plays,
+            log: Log::new(),
+            skips,
+            deck,
+            visualizer: args.visualizer.then(|| Arc::new(Visualizer::new())),
+            #[cfg(feature = "equalizer")]
+            equalizer: Arc::new(equalizer::Equalizer::new(equalizer::Settings::default())),
+            lofi: Arc::new(Lofi::new()),
+            ambience,
+            art: args.art || cfg!(feature = "mpris"),
+            output,
+            config: watch::Sender::new(Arc::default()),
+            snapshots: watch::Sender::new(Snapshot::default()),
         };
 
         Ok(player)
     }
 
+    /// Lets the user know that a track failed because of `error`, and that it's being retried.
+    ///
+    /// Download errors only have the URL of the track, so its name is worked
+    /// out from that, while decode errors already say which track it was.
+    pub fn fail(&self, error: &eyre::Report) {
+        let reason = error.downcast_ref::<reqwest::Error>().map_or_else(
+            || error.to_string(),
+            |error| {
+                let name = error.url().map_or_else(
+                    || String::from("a track"),
+                    |url| self.list.display_name(url.path()),
+                );
+
+                format!("failed to fetch {name}")
+            },
+        );
+
+        self.alert(format!("{reason}, retrying..."));
+    }
+
+    /// Shows `message` in the window for a few seconds, for things which went wrong but aren't fatal.
+    pub fn alert(&self, message: String) {
+        self.failure
+            .store(Some(Arc::new(retry::Failure::new(message))));
+        ui::redraw();
+    }
+
     /// This will play the next track, as well as refilling the buffer in the background.
     ///
     /// This will also set `current` to the newly loaded song.
     pub async fn next(&self) -> eyre::Result<tracks::Decoded> {
+        // The previous track has already been finished by now, so it's cleared even if
+        // the buffer isn't empty, otherwise a failed decode would get it logged again on the retry.
+        self.current.store(None);
+
         let track = if let Some(track) = self.tracks.write().await.pop_front() {
             track
         } else {
@@ -230,16 +716,25 @@ impl Player {
             // This is relevant particularly at the first song.
 
             // Serves as an indicator that the queue is "loading".
-            // We're doing it here so that we don't get the "loading" display
+            // We're only redrawing here so that we don't get the "loading" display
             // for only a frame in the other case that the buffer is not empty.
-            self.current.store(None);
+            ui::redraw();
 
-            self.list.random(&self.client).await?
+            self.list
+                .random(&self.fetcher, &self.plays, self.deck.as_ref(), self.stream)
+                .await?
         };
 
-        let decoded = track.decode()?;
+        // Decoding has to happen on a blocking thread, since a track which
+        // is still streaming will block until enough of it has downloaded.
+        let art = self.art;
+        let name = track.display_name();
+        let decoded = task::spawn_blocking(move || track.decode(art))
+            .await?
+            .wrap_err_with(|| format!("failed to decode {name}"))?;
 
         // Set the current track.
+        self.plays.increment(&decoded.info.full_path);
         self.set_current(decoded.info.clone());
 
         Ok(decoded)
@@ -253,11 +748,15 @@ impl Player {
     /// signals while it's loading.
     ///
     /// This also sends the `NewSong` signal to `tx` apon successful completion.
+    /// The track that was playing is written to the [`Log`] first, as having stopped because of `end`.
     async fn handle_next(
         player: Arc<Self>,
         itx: Sender<()>,
         tx: Sender<Messages>,
+        end: End,
     ) -> eyre::Result<()> {
+        player.finish(end).await;
+
         // Stop the sink.
         player.sink.stop();
 
@@ -265,8 +764,20 @@ impl Player {
 
         match track {
             Ok(track) => {
+                player.retrying.store(None);
+
+                #[cfg(feature = "equalizer")]
+                let data = player.equalizer.apply(track.data);
+                #[cfg(not(feature = "equalizer"))]
+                let data = track.data;
+                let data = player.lofi.apply(data);
+
                 // Start playing the new track.
-                player.sink.append(track.data);
+                match &player.visualizer {
+                    Some(visualizer) => player.sink.append(visualizer.tap(data)),
+                    None => player.sink.append(data),
+                }
+                player.clock.reset(player.sink.is_paused());
 
                 // Notify the background downloader that there's an empty spot
                 // in the buffer.
@@ -276,10 +787,34 @@ impl Player {
                 tx.send(Messages::NewSong).await?;
             }
             Err(error) => {
-                if !error.downcast::<reqwest::Error>()?.is_timeout() {
-                    sleep(TIMEOUT).await;
+                player.skips.record(Reason::Error);
+
+                let timeout = error
+                    .downcast_ref::<reqwest::Error>()
+                    .is_some_and(reqwest::Error::is_timeout);
+                let attempt = player.retrying.load().as_ref().map_or(1, |x| x.attempt + 1);
+
+                if player.retry.exhausted(attempt) {
+                    tx.send(Messages::GiveUp).await?;
+                    return Ok(());
                 }
 
+                player.fail(&error);
+
+                // Timeouts have already waited long enough, so they're retried straight away.
+                let delay = if timeout {
+                    Duration::ZERO
+                } else {
+                    player.retry.delay(attempt)
+                };
+
+                player.retrying.store(Some(Arc::new(retry::Status {
+                    attempt,
+                    max: player.retry.max,
+                    at: Instant::now() + delay,
+                })));
+
+                sleep(delay).await;
                 tx.send(Messages::TryAgain).await?;
             }
         };
@@ -287,12 +822,113 @@ impl Player {
         Ok(())
     }
 
+    /// Applies `message` along with any other volume changes which are
+    /// already waiting in `rx`, all at once.
+    ///
+    /// If a different message is found, it's put in `pending` so it can be handled next.
+    fn coalesce(
+        &self,
+        rx: &mut Receiver<Messages>,
+        message: Messages,
+        pending: &mut Option<Messages>,
+    ) {
+        let mut volume = self.sink.volume();
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                Messages::ChangeVolume(change) => volume += change,
+                Messages::SetVolume(x) => volume = x,
+                other => {
+                    *pending = Some(other);
+                    break;
+                }
+            }
+
+            next = rx.try_recv().ok();
+        }
+
+        self.set_volume(volume);
+    }
+
+    /// Downloads the track at `index` in the [History] again,
+    /// and puts it at the front of the queue so that it's played next.
+    ///
+    /// Returns whether there was a track at `index` to replay.
+    async fn replay(player: &Self, index: usize) -> eyre::Result<bool> {
+        let Some(info) = player.history.get(index) else {
+            return Ok(false);
+        };
+
+        let track = player
+            .list
+            .track(&info.full_path, &info.name, &player.fetcher)
+            .await?;
+
+        player.tracks.write().await.push_front(track);
+        ui::redraw();
+
+        Ok(true)
+    }
+
+    /// Handles [`Messages::Replay`] & [`Messages::Previous`] in the background.
+    ///
+    /// Going back works like replaying the track before the current one,
+    /// except that the current one is ended straight away to play it.
+    async fn handle_replay(
+        player: Arc<Self>,
+        message: Messages,
+        tx: Sender<Messages>,
+    ) -> eyre::Result<()> {
+        let index = match message {
+            Messages::Replay(index) => index,
+            // The current track is at the top of the history, and every track that's gone
+            // back to is moved up there too, so the next one back is one further down each time.
+            // There's nothing to end while a track is loading, so it's ignored then.
+            _ if player.current_exists() => player.back.load(Ordering::Relaxed) + 1,
+            _ => return Ok(()),
+        };
+
+        if Self::replay(&player, index).await? && message == Messages::Previous {
+            player.back.fetch_add(1, Ordering::Relaxed);
+            tx.send(Messages::Back).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Handles [`Messages::Seek`] & [`Messages::SeekTo`] in the background, and then
+    /// sends [`Messages::Seeked`] to `tx` if the seek actually happened.
+    ///
+    /// Seeking is done on a blocking thread, since it has to wait for the audio
+    /// thread to pick it up, and maybe for a streamed track to download.
+    async fn handle_seek(
+        player: Arc<Self>,
+        message: Messages,
+        tx: Sender<Messages>,
+    ) -> eyre::Result<()> {
+        let seeked = task::spawn_blocking(move || match message {
+            Messages::Seek(fraction) => player.seek(fraction),
+            #[cfg(feature = "mpris")]
+            Messages::SeekTo(position) => player.seek_to(position),
+            _ => false,
+        })
+        .await?;
+
+        if seeked {
+            tx.send(Messages::Seeked).await?;
+        }
+
+        Ok(())
+    }
+
     /// This is the main "audio server".
     ///
     /// `rx` & `tx` are used to communicate with it, for example when to
     /// skip tracks or pause.
     ///
     /// This will also initialize a [Downloader] as well as an MPRIS server if enabled.
+    // Every message gets its own arm, so this grows along with them.
+    #[allow(clippy::too_many_lines)]
     pub async fn play(
         player: Arc<Self>,
         tx: Sender<Messages>,
@@ -312,12 +948,20 @@ impl Player {
             })?;
 
         // `itx` is used to notify the `Downloader` when it needs to download new tracks.
-        let downloader = Downloader::new(Arc::clone(&player));
-        let (itx, downloader) = downloader.start();
+        let (itx, downloader) = Downloader::new(Arc::clone(&player)).start();
 
         // Start buffering tracks immediately.
         Downloader::notify(&itx).await?;
 
+        // `stx` is used to notify the `Saver` when the bookmarks or volume change.
+        let (stx, saver) = Saver::new(Arc::clone(&player)).start();
+
+        // Moves playback over to another device if the current one goes away.
+        let recovery = task::spawn(audio::recover(Arc::clone(&player), tx.clone()));
+
+        // Lets the hooks, scrobbler & everything else know when something changes.
+        let publisher = task::spawn(snapshot::publish(Arc::clone(&player)));
+
         // Set the initial sink volume to the one specified.
         player.set_volume(player.volume.float());
 
@@ -328,79 +972,118 @@ impl Player {
         // loaded  and it'll be `false` if a track is still currently loading.
         let mut new = false;
 
-        loop {
+        // A message which was recieved while coalescing volume changes,
+        // and which still has to be handled on the next iteration.
+        let mut pending = None;
+
+        let result = loop {
             let clone = Arc::clone(&player);
 
-            let msg = select! {
-                biased;
-
-                Some(x) = rx.recv() => x,
-                // This future will finish only at the end of the current track.
-                // The condition is a kind-of hack which gets around the quirks
-                // of `sleep_until_end`.
-                //
-                // That's because `sleep_until_end` will return instantly if the sink
-                // is uninitialized. That's why we put a check to make sure that the last
-                // signal we got was `NewSong`, since we shouldn't start waiting for the
-                // song to be over until it has actually started.
-                //
-                // It's also important to note that the condition is only checked at the
-                // beginning of the loop, not throughout.
-                Ok(()) = task::spawn_blocking(move || clone.sink.sleep_until_end()),
-                        if new => Messages::Next,
+            let msg = if let Some(msg) = pending.take() {
+                msg
+            } else {
+                select! {
+                    biased;
+
+                    Some(x) = rx.recv() => x,
+                    // This future will finish only at the end of the current track.
+                    // The condition is a kind-of hack which gets around the quirks
+                    // of `sleep_until_end`.
+                    //
+                    // That's because `sleep_until_end` will return instantly if the sink
+                    // is uninitialized. That's why we put a check to make sure that the last
+                    // signal we got was `NewSong`, since we shouldn't start waiting for the
+                    // song to be over until it has actually started.
+                    //
+                    // It's also important to note that the condition is only checked at the
+                    // beginning of the loop, not throughout.
+                    Ok(()) = task::spawn_blocking(move || clone.sink.sleep_until_end()),
+                            if new => Messages::End,
+                }
             };
 
             match msg {
-                Messages::Next | Messages::Init | Messages::TryAgain => {
+                Messages::Next
+                | Messages::End
+                | Messages::Back
+                | Messages::Init
+                | Messages::TryAgain => {
                     // We manually skipped, so we shouldn't actually wait for the song
                     // to be over until we recieve the `NewSong` signal.
                     new = false;
 
                     // This basically just prevents `Next` while a song is still currently loading.
-                    if msg == Messages::Next && !player.current_exists() {
+                    if msg == Messages::Next && !player.skip() {
                         continue;
                     }
 
                     // Handle the rest of the signal in the background,
                     // as to not block the main audio server thread.
+                    let end = match msg {
+                        Messages::Next => End::Skipped,
+                        Messages::Back => End::Previous,
+                        _ => End::Finished,
+                    };
+
+                    // Going back any further only makes sense while the user is still going back.
+                    if msg != Messages::Back {
+                        player.back.store(0, Ordering::Relaxed);
+                    }
+
                     task::spawn(Self::handle_next(
                         Arc::clone(&player),
                         itx.clone(),
                         tx.clone(),
+                        end,
                     ));
                 }
-                Messages::Play => {
-                    player.sink.play();
-
-                    #[cfg(feature = "mpris")]
-                    mpris.playback(PlaybackStatus::Playing).await?;
-                }
-                Messages::Pause => {
-                    player.sink.pause();
-
-                    #[cfg(feature = "mpris")]
-                    mpris.playback(PlaybackStatus::Paused).await?;
-                }
-                Messages::PlayPause => {
-                    if player.sink.is_paused() {
-                        player.sink.play();
-                    } else {
-                        player.sink.pause();
-                    }
+                Messages::Play | Messages::Pause | Messages::PlayPause => {
+                    player.set_paused(match msg {
+                        Messages::Play => false,
+                        Messages::Pause => true,
+                        _ => !player.sink.is_paused(),
+                    });
 
                     #[cfg(feature = "mpris")]
                     mpris
                         .playback(mpris.player().playback_status().await?)
                         .await?;
                 }
-                Messages::ChangeVolume(change) => {
-                    player.set_volume(player.sink.volume() + change);
+                change @ (Messages::ChangeVolume(_) | Messages::SetVolume(_)) => {
+                    // Holding down a volume key or dragging the audio bar can flood the channel,
+                    // so waiting volume changes are applied at once, with a single MPRIS update.
+                    player.coalesce(&mut rx, change, &mut pending);
+                    Saver::notify(&stx);
 
                     #[cfg(feature = "mpris")]
                     mpris
                         .changed(vec![Property::Volume(player.sink.volume().into())])
                         .await?;
                 }
+                Messages::Seek(_) => {
+                    task::spawn(Self::handle_seek(Arc::clone(&player), msg, tx.clone()));
+                }
+                #[cfg(feature = "mpris")]
+                Messages::SeekTo(_) => {
+                    task::spawn(Self::handle_seek(Arc::clone(&player), msg, tx.clone()));
+                }
+                Messages::Seeked => {
+                    #[cfg(feature = "mpris")]
+                    mpris.seeked(player.elapsed()).await?;
+                }
+                Messages::Replay(_) | Messages::Previous => {
+                    task::spawn(Self::handle_replay(Arc::clone(&player), msg, tx.clone()));
+                }
+                Messages::Bookmark => {
+                    if player.bookmark().await {
+                        Saver::notify(&stx);
+                    }
+                }
+                Messages::ReloadConfig => player.reload().await,
+                #[cfg(feature = "equalizer")]
+                Messages::Equalize(change) => player.alert(player.equalizer.change(change)),
+                Messages::ToggleLofi => player.alert(player.lofi.toggle()),
+                Messages::Ambience(change) => player.alert(player.ambience.change(change)),
                 // This basically just continues, but more importantly, it'll re-evaluate
                 // the select macro at the beginning of the loop.
                 // See the top section to find out why this matters.
@@ -419,12 +1102,362 @@ impl Player {
 
                     continue;
                 }
-                Messages::Quit => break,
+                Messages::GiveUp => {
+                    break Err(eyre!(
+                        "Couldn't download a track after {} retries",
+                        player.retry.max.unwrap_or_default()
+                    ))
+                }
+                Messages::Quit => break Ok(()),
             }
-        }
+        };
 
         downloader.abort();
+        saver.abort();
+        recovery.abort();
+        publisher.abort();
 
-        Ok(())
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        future::Future,
+        panic::{self, AssertUnwindSafe},
+        sync::{mpsc as std_mpsc, Arc},
+        thread,
+        time::Duration,
+    };
+
+    use bytes::Bytes;
+    use clap::Parser;
+    use rodio::queue::SourcesQueueOutput;
+    use tokio::{
+        fs,
+        runtime::Runtime,
+        sync::mpsc::{self, Sender},
+        task::{self, JoinHandle},
+        time::{sleep, Instant},
+    };
+
+    use super::{Messages, Player};
+    use crate::{
+        play,
+        tracks::{
+            cache::Cache,
+            log::{End, Log},
+            skips::Reason,
+        },
+        Args,
+    };
+
+    /// The base of every test list. Nothing is listening here, so the only
+    /// tracks which can be "downloaded" are the ones put in the [`Cache`].
+    const BASE: &str = "http://127.0.0.1:9/";
+
+    /// How long to wait for the player to get somewhere before the test fails.
+    const PATIENCE: Duration = Duration::from_secs(5);
+
+    /// Waits until `condition` is true, which can `.await`, failing the test if it takes too long.
+    macro_rules! until {
+        ($condition:expr) => {
+            let start = Instant::now();
+            while !$condition {
+                assert!(
+                    start.elapsed() < PATIENCE,
+                    concat!("gave up waiting for ", stringify!($condition))
+                );
+                sleep(Duration::from_millis(10)).await;
+            }
+        };
+    }
+
+    /// Gets an MP3 of `frames` frames of silence, which are each about 26ms long.
+    ///
+    /// Each frame is just a header for 128 kbps mono at 44.1 kHz, followed by zeros.
+    fn silence(frames: usize) -> Bytes {
+        let mut frame = [0; 417];
+        frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0xC0]);
+        frame.repeat(frames).into()
+    }
+
+    /// Plays `queues` in real time like an audio device would, except that the samples
+    /// are just thrown away, until `stop` is disconnected.
+    ///
+    /// This has to be done, since the sink waits for a track which was stopped to
+    /// actually be taken out of the queue before anything else can be added to it.
+    fn play(mut queues: [SourcesQueueOutput<f32>; 2], stop: &std_mpsc::Receiver<()>) {
+        while !matches!(stop.try_recv(), Err(std_mpsc::TryRecvError::Disconnected)) {
+            // The test tracks are mono at 44.1 kHz, so this is about 10ms of them.
+            for queue in &mut queues {
+                queue.by_ref().take(441).for_each(drop);
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Runs `test` on a new runtime.
+    ///
+    /// The runtime isn't waited on when it shuts down, since a thread
+    /// is still blocked waiting for the end of whatever track was playing.
+    fn run(test: impl Future<Output = ()>) {
+        let runtime = Runtime::new().unwrap();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| runtime.block_on(test)));
+        runtime.shutdown_background();
+
+        if let Err(panic) = result {
+            panic::resume_unwind(panic);
+        }
+    }
+
+    /// A [`Player`] running in the background, without an audio device.
+    struct Harness {
+        /// The player itself.
+        player: Arc<Player>,
+
+        /// Sends messages to the player, like the UI would.
+        tx: Sender<Messages>,
+
+        /// The player's main loop, from [`Player::play`].
+        task: JoinHandle<eyre::Result<()>>,
+
+        /// Stops the thread which plays the queues when it's dropped, see [`play`].
+        _device: std_mpsc::Sender<()>,
+    }
+
+    impl Harness {
+        /// Starts playing a list called `name`, where `tracks` are the names of the
+        /// tracks along with what the fetcher gives back for them, and `args` are extra arguments.
+        async fn start(name: &str, tracks: &[(&str, Bytes)], args: &[&str]) -> Self {
+            let dir = play::data_dir().unwrap();
+            fs::create_dir_all(&dir).await.unwrap();
+
+            let cache = Cache::new(true, None).unwrap();
+            let mut list = String::from(BASE);
+            for (track, data) in tracks {
+                cache.put(&format!("{BASE}{track}"), data).await.unwrap();
+                list.push('\n');
+                list.push_str(track);
+            }
+
+            let path = dir.join(format!("{name}.txt"));
+            fs::write(&path, list).await.unwrap();
+
+            let path = path.to_string_lossy();
+            let args = Args::parse_from(
+                [
+                    "lowfi",
+                    "--tracks",
+                    &path,
+                    "--assume-unmetered",
+                    "--buffer-size",
+                    "1",
+                ]
+                .iter()
+                .chain(args),
+            );
+
+            let (device, stop) = std_mpsc::channel();
+            let player = Player::build(&args, |queues| {
+                thread::spawn(move || play(queues, &stop));
+                Ok(None)
+            })
+            .await
+            .unwrap();
+
+            let player = Arc::new(player);
+            let (tx, rx) = mpsc::channel(8);
+            let task = task::spawn(Player::play(Arc::clone(&player), tx.clone(), rx));
+            tx.send(Messages::Init).await.unwrap();
+
+            Self {
+                player,
+                tx,
+                task,
+                _device: device,
+            }
+        }
+
+        /// Sends `message` to the player.
+        async fn send(&self, message: Messages) {
+            self.tx.send(message).await.unwrap();
+        }
+
+        /// Gets the full path of the track that's playing, after waiting for there to be one.
+        async fn current(&self) -> String {
+            until!(self.player.current_exists());
+            self.player.current.load_full().unwrap().full_path.clone()
+        }
+
+        /// Quits, and then gets what the player's main loop finished with.
+        async fn quit(self) -> eyre::Result<()> {
+            self.send(Messages::Quit).await;
+            self.task.await.unwrap()
+        }
+    }
+
+    #[test]
+    fn skip() {
+        run(async {
+            let tracks = [("skip-a.mp3", silence(400)), ("skip-b.mp3", silence(400))];
+            let harness = Harness::start("skip", &tracks, &[]).await;
+            let player = Arc::clone(&harness.player);
+
+            let first = harness.current().await;
+            assert_eq!(player.history.played(), 1);
+            assert_eq!(player.sink.len(), 1);
+
+            harness.send(Messages::Next).await;
+            until!(player.history.played() == 2);
+
+            assert_eq!(player.skips.get(Reason::Manual), 1);
+            assert!(player.history.skipped(&first));
+
+            harness.quit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn previous() {
+        run(async {
+            let tracks = [
+                ("previous-a.mp3", silence(400)),
+                ("previous-b.mp3", silence(400)),
+                ("previous-c.mp3", silence(400)),
+            ];
+
+            // Decks never repeat a track until they're through all of them.
+            let harness = Harness::start("previous", &tracks, &["--shuffle", "deck"]).await;
+            let player = Arc::clone(&harness.player);
+
+            harness.current().await;
+            for count in 2..=3 {
+                harness.send(Messages::Next).await;
+                until!(player.history.played() == count);
+            }
+
+            let paths: Vec<String> = player
+                .history
+                .entries()
+                .iter()
+                .map(|x| x.full_path.clone())
+                .collect();
+
+            // Going back twice should go two tracks back, rather than back & forth.
+            for track in &paths[1..] {
+                harness.send(Messages::Previous).await;
+                until!(harness.current().await == *track);
+            }
+
+            harness.quit().await.unwrap();
+
+            let ends: Vec<End> = Log::load()
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|x| x.list == "previous")
+                .map(|x| x.end)
+                .collect();
+
+            assert_eq!(
+                ends,
+                [End::Skipped, End::Skipped, End::Previous, End::Previous]
+            );
+        });
+    }
+
+    #[test]
+    fn pause() {
+        run(async {
+            let harness = Harness::start("pause", &[("pause.mp3", silence(400))], &[]).await;
+            let player = Arc::clone(&harness.player);
+            harness.current().await;
+            assert!(!player.sink.is_paused());
+
+            harness.send(Messages::Pause).await;
+            until!(player.sink.is_paused());
+            assert!(player.paused.borrow().is_some());
+
+            harness.send(Messages::PlayPause).await;
+            until!(!player.sink.is_paused());
+            assert!(player.paused.borrow().is_none());
+
+            harness.send(Messages::PlayPause).await;
+            until!(player.sink.is_paused());
+
+            harness.send(Messages::Play).await;
+            until!(!player.sink.is_paused());
+
+            harness.quit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn bookmark() {
+        run(async {
+            let harness = Harness::start("bookmark", &[("bookmark.mp3", silence(400))], &[]).await;
+            let player = Arc::clone(&harness.player);
+            let current = harness.current().await;
+            assert!(!player.bookmarks.contains(&current).await);
+
+            harness.send(Messages::Bookmark).await;
+            until!(player.bookmarks.contains(&current).await);
+
+            harness.send(Messages::Bookmark).await;
+            until!(!player.bookmarks.contains(&current).await);
+
+            harness.quit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn retry() {
+        run(async {
+            let tracks = [("retry.mp3", Bytes::from_static(b"not an mp3"))];
+            let args = ["--retry-delay", "1", "--max-retries", "3"];
+            let harness = Harness::start("retry", &tracks, &args).await;
+            let player = Arc::clone(&harness.player);
+
+            until!(player.retrying.load().is_some());
+            assert!(player.failure.load().is_some());
+            assert!(!player.current_exists());
+
+            // The track can be decoded by the time it's tried again.
+            Cache::new(true, None)
+                .unwrap()
+                .put(&format!("{BASE}retry.mp3"), &silence(400))
+                .await
+                .unwrap();
+
+            // The broken track might've already been downloaded into the buffer too,
+            // in which case that fails once more, so only the end result is checked.
+            harness.current().await;
+            assert!(player.retrying.load().is_none());
+            assert!(player.skips.get(Reason::Error) >= 1);
+
+            harness.quit().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn give_up() {
+        run(async {
+            let tracks = [("give-up.mp3", Bytes::from_static(b"not an mp3"))];
+            let args = ["--retry-delay", "0", "--max-retries", "2"];
+            let harness = Harness::start("give-up", &tracks, &args).await;
+            let player = Arc::clone(&harness.player);
+
+            // Nothing is sent, since the player should stop by itself.
+            let error = harness.task.await.unwrap().unwrap_err();
+            assert_eq!(
+                error.to_string(),
+                "Couldn't download a track after 2 retries"
+            );
+            assert_eq!(player.skips.get(Reason::Error), 3);
+            assert!(!player.current_exists());
+        });
     }
 }
@@ -0,0 +1,246 @@
+//! Contains the [`Ambience`], which loops rain, a fireplace, or any other sound
+//! underneath the music, with its own volume. It's set with `--ambience`.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+
+use eyre::{bail, WrapErr};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rodio::{queue::SourcesQueueOutput, source::SeekError, Decoder, Sink, Source};
+use serde::Deserialize;
+
+use super::biquad::Biquad;
+
+/// The sample rate of the built in sounds.
+const RATE: u32 = 44100;
+
+/// How loud the ambience is if the volume isn't set, from 0 to 1.
+const VOLUME: f32 = 0.5;
+
+/// The ambience's settings from the `[ambience]` section of `config.toml`.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Either `rain`, `fire`, or the path to an audio file to loop.
+    pub sound: Option<String>,
+
+    /// How loud the ambience is, from 0 to 1.
+    pub volume: Option<f32>,
+}
+
+/// A change to the [`Ambience`], which is sent by the keybindings.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Change {
+    /// Mutes the ambience, or brings it back.
+    Toggle,
+
+    /// Changes the volume of the ambience, by this much.
+    Volume(f32),
+}
+
+/// One of the built in sounds, which are made up on the fly rather than shipped with lowfi.
+#[derive(Clone, Copy)]
+enum Kind {
+    /// Muffled noise, which sounds like rain on a window.
+    Rain,
+
+    /// A low rumble with pops & crackles.
+    Fire,
+}
+
+/// A built in sound, which goes on forever.
+struct Generated {
+    /// Which sound it is.
+    kind: Kind,
+
+    /// Where the noise comes from.
+    rng: StdRng,
+
+    /// The filter which shapes the noise.
+    filter: Biquad,
+
+    /// The last two inputs & outputs of the filter, for each channel.
+    history: [[f32; 4]; 2],
+
+    /// Which channel the next sample belongs to.
+    channel: usize,
+
+    /// How loud the current pop is, for the fire.
+    pop: f32,
+}
+
+impl Generated {
+    /// Creates the sound for `kind`.
+    fn new(kind: Kind) -> Self {
+        // Rain is noise with the hiss taken off, while a fire is mostly rumble.
+        let cutoff = match kind {
+            Kind::Rain => 3000.0,
+            Kind::Fire => 300.0,
+        };
+
+        Self {
+            kind,
+            rng: StdRng::from_entropy(),
+            filter: Biquad::low_pass(cutoff, 0.707, RATE as f32),
+            history: [[0.0; 4]; 2],
+            channel: 0,
+            pop: 0.0,
+        }
+    }
+}
+
+impl Iterator for Generated {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let channel = self.channel;
+        self.channel = (self.channel + 1) % 2;
+
+        // Each channel gets its own noise, so that the sound is all around instead of in the middle.
+        let noise = self.rng.gen_range(-1.0..1.0);
+        let noise = self.filter.process(noise, &mut self.history[channel]);
+
+        Some(match self.kind {
+            Kind::Rain => noise * 0.5,
+            Kind::Fire => {
+                // Pops are the same on both channels, and there's a few of them every second.
+                if channel == 0 {
+                    self.pop *= 0.99;
+                    if self.rng.gen::<f32>() < 4.0 / RATE as f32 {
+                        self.pop = self.rng.gen_range(-0.5..0.5);
+                    }
+                }
+
+                self.pop.mul_add(self.rng.gen::<f32>(), noise)
+            }
+        })
+    }
+}
+
+impl Source for Generated {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, _: Duration) -> Result<(), SeekError> {
+        Ok(())
+    }
+}
+
+/// Plays a looping sound on its own [`Sink`], separately from the music.
+pub struct Ambience {
+    /// The sink that the sound is played on, which has its own volume.
+    sink: Sink,
+
+    /// The sound from `--ambience`, which takes priority over the one in `config.toml`.
+    flag: Option<String>,
+
+    /// The sound that's playing, if there is one.
+    sound: Mutex<Option<String>>,
+}
+
+impl Ambience {
+    /// Creates a new, silent [`Ambience`], along with the queue that has to be played
+    /// on the output stream for it to be heard.
+    ///
+    /// `flag` is the sound from `--ambience`, if it was set.
+    pub fn new(flag: Option<String>) -> (Self, SourcesQueueOutput<f32>) {
+        let (sink, queue) = Sink::new_idle();
+        sink.set_volume(VOLUME);
+
+        let ambience = Self {
+            sink,
+            flag,
+            sound: Mutex::new(None),
+        };
+
+        (ambience, queue)
+    }
+
+    /// Decodes `sound`, which is either the name of a built in sound or a path.
+    fn source(sound: &str) -> eyre::Result<Box<dyn Source<Item = f32> + Send>> {
+        Ok(match sound {
+            "rain" => Box::new(Generated::new(Kind::Rain)),
+            "fire" => Box::new(Generated::new(Kind::Fire)),
+            path => {
+                let file = File::open(path)
+                    .wrap_err_with(|| format!("couldn't open the ambience at {path}"))?;
+                let decoder = Decoder::new(BufReader::new(file))
+                    .wrap_err_with(|| format!("couldn't decode the ambience at {path}"))?;
+
+                Box::new(decoder.convert_samples::<f32>().repeat_infinite())
+            }
+        })
+    }
+
+    /// Applies `settings`, like when `config.toml` changes,
+    /// starting the sound over only if it's a different one.
+    ///
+    /// The volume is only set if it's different from the `old` settings,
+    /// so that turning the ambience up or down with `<` & `>` is kept.
+    pub fn set(&self, old: &Settings, settings: &Settings) -> eyre::Result<()> {
+        if settings.volume.map(f32::to_bits) != old.volume.map(f32::to_bits) {
+            let volume = settings.volume.unwrap_or(VOLUME);
+            if !(0.0..=1.0).contains(&volume) {
+                bail!("the ambience volume has to be from 0 to 1");
+            }
+
+            self.sink.set_volume(volume);
+        }
+
+        let sound = self.flag.clone().or_else(|| settings.sound.clone());
+        let mut current = self.sound.lock().unwrap_or_else(PoisonError::into_inner);
+        if *current == sound {
+            return Ok(());
+        }
+
+        self.sink.stop();
+        if let Some(sound) = &sound {
+            self.sink.append(Self::source(sound)?);
+        }
+
+        *current = sound;
+        drop(current);
+
+        Ok(())
+    }
+
+    /// Applies `change`, returning a short message about what it did to show in the window.
+    pub fn change(&self, change: Change) -> String {
+        if self.sink.empty() {
+            return String::from("there's no ambience, use --ambience rain to add some");
+        }
+
+        match change {
+            Change::Toggle if self.sink.is_paused() => {
+                self.sink.play();
+                String::from("ambience on")
+            }
+            Change::Toggle => {
+                self.sink.pause();
+                String::from("ambience off")
+            }
+            Change::Volume(change) => {
+                let volume = (self.sink.volume() + change).clamp(0.0, 1.0);
+                self.sink.set_volume(volume);
+                format!("ambience at {:.0}%", volume * 100.0)
+            }
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! Contains the HTTP API, which lets home automation & web dashboards control lowfi.
+//!
+//! It's deliberately tiny, so rather than pulling in a whole web framework, it only
+//! understands just enough HTTP to map each endpoint onto a [`remote`] request:
+//!
+//! * `GET /status` - What's playing, as JSON.
+//! * `GET /bookmarks` - The full paths of the bookmarked tracks, as JSON.
+//! * `POST /bookmarks` - Bookmarks the current track, or removes the bookmark.
+//! * `POST /next`, `/previous`, `/play`, `/pause` & `/play-pause` - Controls playback.
+//! * `POST /volume` - Changes the volume, with a body like `50` or `+10`.
+//! * `GET /events` - A WebSocket which sends an [`Event`] whenever something changes.
+//!
+//! Requests with an `Origin` header are turned away, since that means they came from
+//! a web page, which shouldn't be able to control lowfi just because it was opened.
+//!
+//! [`remote`]: super::remote
+
+use std::{io::ErrorKind, sync::Arc, time::Duration};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use eyre::bail;
+use serde::Serialize;
+use serde_json::json;
+use tokio::{
+    io::{self, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    select,
+    sync::mpsc::Sender,
+    task,
+    time::timeout,
+};
+
+use super::{remote, snapshot::Snapshot, Messages, Player};
+
+/// The biggest request body that's read, in bytes, which is plenty for a volume.
+const MAX_BODY: usize = 64;
+
+/// The longest request line or header that's read, in bytes.
+const MAX_LINE: u64 = 8 * 1024;
+
+/// The most headers that a request can have.
+const MAX_HEADERS: usize = 64;
+
+/// How long a client has to send the whole request, before it's dropped.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The endpoints which can be sent a `POST`, which are named after their [`remote`] requests.
+const ACTIONS: [&str; 6] = ["next", "previous", "play", "pause", "play-pause", "volume"];
+
+/// Appended to the key that a WebSocket client sends, to work out the key that's sent back.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Something which changed in the player, which is sent to WebSocket clients as JSON.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'snapshot> {
+    /// Everything at once, which is sent as soon as the WebSocket opens.
+    Status(&'snapshot Snapshot),
+
+    /// A new track started, or a track finished loading.
+    Track(&'snapshot Snapshot),
+
+    /// Playback was paused or unpaused.
+    Paused {
+        /// Whether playback is paused now.
+        paused: bool,
+    },
+
+    /// The volume changed.
+    Volume {
+        /// The new volume, from 0 to 1.
+        volume: f32,
+    },
+
+    /// The current track played for another second.
+    Progress {
+        /// How long the current track has played for, in seconds.
+        elapsed: u64,
+    },
+}
+
+impl<'snapshot> Event<'snapshot> {
+    /// Works out the events which happened between the `last` snapshot and the `next` one.
+    fn between(last: &Snapshot, next: &'snapshot Snapshot) -> Vec<Self> {
+        let mut events = Vec::new();
+        if last.track != next.track || last.played != next.played {
+            events.push(Self::Track(next));
+        }
+
+        if last.paused != next.paused {
+            events.push(Self::Paused {
+                paused: next.paused,
+            });
+        }
+
+        if (last.volume - next.volume).abs() > f32::EPSILON {
+            events.push(Self::Volume {
+                volume: next.volume,
+            });
+        }
+
+        if last.elapsed.as_secs() != next.elapsed.as_secs() {
+            events.push(Self::Progress {
+                elapsed: next.elapsed.as_secs(),
+            });
+        }
+
+        events
+    }
+}
+
+/// Wraps `text` in a WebSocket text frame, which isn't masked since it's sent by the server.
+// WebSocket frames always have big endian lengths.
+#[allow(clippy::big_endian_bytes)]
+fn frame(text: &str) -> Vec<u8> {
+    let mut frame = vec![0x81];
+    let len = text.len();
+    if let Ok(len @ 0..=125) = u8::try_from(len) {
+        frame.push(len);
+    } else if let Ok(len) = u16::try_from(len) {
+        frame.push(126);
+        frame.extend(len.to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend((len as u64).to_be_bytes());
+    }
+
+    frame.extend(text.as_bytes());
+    frame
+}
+
+/// Reads frames from the client until it closes the WebSocket, skipping over anything else.
+///
+/// This keeps track of where each frame ends, so that a payload which happens
+/// to look like a close frame isn't mistaken for one.
+async fn closed(reader: &mut BufReader<OwnedReadHalf>) -> eyre::Result<()> {
+    loop {
+        let mut header = [0; 2];
+        match reader.read_exact(&mut header).await {
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        };
+
+        // The opcode for closing is 8, which is in the lower half of the first byte.
+        if header[0] & 0x0f == 8 {
+            return Ok(());
+        }
+
+        // Lengths of 126 & 127 mean that the real length comes after, in 2 or 8 bytes.
+        let length = match header[1] & 0x7f {
+            126 => u64::from(reader.read_u16().await?),
+            127 => reader.read_u64().await?,
+            length => u64::from(length),
+        };
+
+        // Frames from clients are masked, which puts a 4 byte key before the payload.
+        let masked = header[1] & 0x80 != 0;
+        let length = length.saturating_add(if masked { 4 } else { 0 });
+        io::copy(&mut (&mut *reader).take(length), &mut io::sink()).await?;
+    }
+}
+
+/// Finishes opening a WebSocket with the `key` that the client sent,
+/// and then sends events until the client closes it.
+///
+/// Anything the client sends is ignored, other than it closing the WebSocket.
+async fn events(
+    player: &Player,
+    mut reader: BufReader<OwnedReadHalf>,
+    mut writer: OwnedWriteHalf,
+    key: &str,
+) -> eyre::Result<()> {
+    let accept = STANDARD.encode(
+        sha1_smol::Sha1::from(format!("{key}{WEBSOCKET_GUID}"))
+            .digest()
+            .bytes(),
+    );
+    let handshake = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+    );
+    writer.write_all(handshake.as_bytes()).await?;
+
+    let mut snapshots = player.snapshots.subscribe();
+    let mut last = player.snapshot().await;
+    writer
+        .write_all(&frame(&serde_json::to_string(&Event::Status(&last))?))
+        .await?;
+
+    let closed = closed(&mut reader);
+    tokio::pin!(closed);
+    loop {
+        select! {
+            Ok(()) = snapshots.changed() => {
+                let next = snapshots.borrow_and_update().clone();
+                for event in Event::between(&last, &next) {
+                    writer.write_all(&frame(&serde_json::to_string(&event)?)).await?;
+                }
+
+                last = next;
+            }
+            result = &mut closed => {
+                result?;
+                writer.write_all(&[0x88, 0]).await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Formats an HTTP response with a `status` like `200 OK`, and a JSON `body`.
+fn response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Works out the [`remote`] request for a `method` on an endpoint at `path`,
+/// or the status to respond with if there isn't one.
+fn route(method: &str, path: &str, body: &str) -> Result<String, &'static str> {
+    let name = path.trim_start_matches('/');
+
+    match (method, name) {
+        ("GET", "status") => Ok("now-playing".to_owned()),
+        ("GET", "bookmarks") => Ok("bookmarks".to_owned()),
+        ("POST", "bookmarks") => Ok("bookmark".to_owned()),
+        ("POST", "volume") => Ok(format!("volume {}", body.trim())),
+        ("POST", name) if ACTIONS.contains(&name) => Ok(name.to_owned()),
+        ("GET", "events") => Err("426 Upgrade Required"),
+        (_, "status" | "bookmarks" | "events") => Err("405 Method Not Allowed"),
+        (_, name) if ACTIONS.contains(&name) => Err("405 Method Not Allowed"),
+        _ => Err("404 Not Found"),
+    }
+}
+
+/// Reads a single line from `reader`, which can't be longer than [`MAX_LINE`].
+async fn line(reader: &mut BufReader<OwnedReadHalf>) -> eyre::Result<String> {
+    let mut line = String::new();
+    (&mut *reader).take(MAX_LINE).read_line(&mut line).await?;
+    if !line.ends_with('\n') {
+        bail!("line is too long, or was cut off");
+    }
+
+    Ok(line)
+}
+
+/// The parts of a request that the API cares about.
+struct Request {
+    /// The method, like `GET`.
+    method: String,
+
+    /// The path, without the query string since that isn't used for anything.
+    path: String,
+
+    /// The body, which is cut off after [`MAX_BODY`] bytes.
+    body: String,
+
+    /// The key that a WebSocket client sends, if it sent one.
+    key: Option<String>,
+
+    /// Whether there was an `Origin` header, which browsers add to requests from web pages.
+    origin: bool,
+}
+
+impl Request {
+    /// Reads a request from `reader`, up to the end of the body.
+    async fn read(reader: &mut BufReader<OwnedReadHalf>) -> eyre::Result<Self> {
+        let start = line(reader).await?;
+        let mut parts = start.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_owned();
+        let target = parts.next().unwrap_or_default();
+        let path = target.split('?').next().unwrap_or_default().to_owned();
+
+        // The only headers that matter are the length of the body, the WebSocket key & the origin.
+        let mut length = 0;
+        let mut key = None;
+        let mut origin = false;
+        let mut ended = false;
+        for _ in 0..MAX_HEADERS {
+            let header = line(reader).await?;
+            let header = header.trim();
+            if header.is_empty() {
+                ended = true;
+                break;
+            }
+
+            let Some((name, value)) = header.split_once(':') else {
+                continue;
+            };
+
+            match name.to_ascii_lowercase().as_str() {
+                "content-length" => length = value.trim().parse().unwrap_or_default(),
+                "sec-websocket-key" => key = Some(value.trim().to_owned()),
+                "origin" => origin = true,
+                _ => (),
+            }
+        }
+
+        if !ended {
+            bail!("request has too many headers");
+        }
+
+        let mut body = vec![0; usize::min(length, MAX_BODY)];
+        reader.read_exact(&mut body).await?;
+
+        Ok(Self {
+            method,
+            path,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            key,
+            origin,
+        })
+    }
+}
+
+/// Reads a request from `stream`, and writes the response back.
+///
+/// Clients which take longer than [`TIMEOUT`] to send the request, or send
+/// one that's too big, are just disconnected.
+async fn handle(
+    player: Arc<Player>,
+    sender: Sender<Messages>,
+    stream: TcpStream,
+) -> eyre::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+    let request = timeout(TIMEOUT, Request::read(&mut reader)).await??;
+
+    if request.origin {
+        let status = "403 Forbidden";
+        let response = response(status, &json!({ "error": status }).to_string());
+        writer.write_all(response.as_bytes()).await?;
+
+        return Ok(());
+    }
+
+    if let (Some(key), "GET", "/events") =
+        (&request.key, request.method.as_str(), request.path.as_str())
+    {
+        return events(&player, reader, writer, key).await;
+    }
+
+    let response = match route(&request.method, &request.path, &request.body) {
+        Ok(request) => match remote::respond(&player, &sender, &request).await {
+            Ok(answer) if answer == "ok" => response("200 OK", r#"{"ok":true}"#),
+            Ok(answer) => response("200 OK", &answer),
+            Err(error) => {
+                let error = json!({ "error": error.to_string() });
+                response("400 Bad Request", &error.to_string())
+            }
+        },
+        Err(status) => response(status, &json!({ "error": status }).to_string()),
+    };
+
+    writer.write_all(response.as_bytes()).await?;
+
+    Ok(())
+}
+
+/// The HTTP API, which has already started listening.
+pub struct Server {
+    /// The listener that connections are accepted from.
+    listener: TcpListener,
+}
+
+impl Server {
+    /// Starts listening on `port` at the `host`, which is done before serving
+    /// so that a port which is already taken stops lowfi straight away.
+    pub async fn bind(host: &str, port: u16) -> eyre::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind((host, port)).await?,
+        })
+    }
+
+    /// Handles each connection in the background, until the task is aborted.
+    pub async fn serve(self, player: Arc<Player>, sender: Sender<Messages>) -> eyre::Result<()> {
+        loop {
+            let (stream, _) = self.listener.accept().await?;
+            task::spawn(handle(Arc::clone(&player), sender.clone(), stream));
+        }
+    }
+}
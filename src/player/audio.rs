@@ -0,0 +1,494 @@
+//! Contains the functions for probing & initializing the audio output,
+//! as well as the [`Output`], which recovers when the device goes away.
+
+use std::{
+    ffi::CString,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, MutexGuard, PoisonError,
+    },
+    time::Duration,
+};
+
+use eyre::{bail, eyre, WrapErr};
+use rodio::{
+    cpal::{
+        self,
+        traits::{DeviceTrait, HostTrait, StreamTrait},
+        BufferSize, FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig,
+        SupportedBufferSize,
+    },
+    dynamic_mixer::{self, DynamicMixer, DynamicMixerController},
+    queue::SourcesQueueOutput,
+    source::SeekError,
+    OutputStream, OutputStreamHandle, Source, StreamError,
+};
+use tokio::{
+    sync::mpsc::Sender,
+    time::{interval_at, Instant, MissedTickBehavior},
+};
+
+use super::{Messages, Player};
+use crate::Args;
+
+/// How long the output can go without asking for any samples before the device
+/// is considered to be gone, like when headphones are unplugged.
+const STALL: Duration = Duration::from_secs(1);
+
+/// The advice shown when there isn't any usable output device.
+const HELP: &str = "Make sure that your speakers or headphones are connected, and that sound works in other programs.
+
+On Linux, lowfi uses ALSA, so you'll need `alsa-lib` on Arch or `libasound2` on Ubuntu.
+If you're using PulseAudio or PipeWire, also install `pulseaudio-alsa` or `pipewire-alsa`.
+
+Run lowfi with --debug to see ALSA's own logs.";
+
+/// The file descriptor of stderr.
+///
+/// This is used instead of [`libc::STDERR_FILENO`] since that isn't defined on Windows.
+const STDERR: libc::c_int = 2;
+
+/// The last error from the output stream, if it hasn't been shown yet.
+///
+/// Errors happen on cpal's own thread, and printing them would mess up the window,
+/// so they're kept here until [`recover`] shows them as an alert instead.
+type Failure = Arc<Mutex<Option<String>>>;
+
+/// This runs `function` while shutting up alsa with [libc].
+fn silenced<T>(function: impl FnOnce() -> T) -> eyre::Result<T> {
+    // This is a bit of an ugly hack that basically just uses `libc` to redirect alsa's
+    // output to `/dev/null` so that it wont be shoved down our throats.
+    let null = CString::new("/dev/null")?;
+
+    // The original stderr is duplicated first, so that it can be restored afterwards.
+    // SAFETY: Simple enough to be impossible to fail. Hopefully.
+    let original = unsafe { libc::dup(STDERR) };
+
+    // SAFETY: See the previous call to `dup`.
+    let null = unsafe { libc::open(null.as_ptr(), libc::O_WRONLY) };
+
+    if original < 0i32 || null < 0i32 {
+        return Ok(function());
+    }
+
+    // SAFETY: Both file descriptors were checked to be valid above.
+    unsafe {
+        libc::dup2(null, STDERR);
+    }
+
+    // SAFETY: `null` has been duplicated onto stderr, so it isn't needed anymore.
+    unsafe {
+        libc::close(null);
+    }
+
+    // Run the function while stderr is still redirected to /dev/null.
+    // This isn't using `?` so that stderr is always restored, even on errors.
+    let result = function();
+
+    // Restore the original stderr, so that other output isn't silenced.
+    // This is done with the original file descriptor rather than `/dev/tty`
+    // so that errors are still visible when stderr isn't a terminal.
+    //
+    // SAFETY: `original` was checked to be valid above.
+    unsafe {
+        libc::dup2(original, STDERR);
+    }
+
+    // SAFETY: See the previous call to `dup2`.
+    unsafe {
+        libc::close(original);
+    }
+
+    Ok(result)
+}
+
+/// Checks that there's a usable default output device, returning a
+/// friendly & actionable error if there isn't.
+///
+/// This should be done before touching the terminal, so that the error is readable.
+fn check() -> eyre::Result<()> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| eyre!("No audio output device could be found.\n\n{HELP}"))?;
+
+    device.default_output_config().map_err(|error| {
+        eyre!("The default audio output device can't be used: {error}\n\n{HELP}")
+    })?;
+
+    Ok(())
+}
+
+/// Whether alsa's logs should be hidden, which is only done if we really have to.
+pub const fn silent(args: &Args) -> bool {
+    cfg!(target_os = "linux") && !args.alternate && !args.debug
+}
+
+/// How the output stream should be opened, from `--audio-buffer` & `--sample-rate`.
+#[derive(Clone, Copy)]
+pub struct Settings {
+    /// Whether alsa's logs should be hidden, see [`silent`].
+    silent: bool,
+
+    /// How many frames the device should be given at a time, or its default if [`None`].
+    buffer: Option<u32>,
+
+    /// The sample rate to play at in Hz, or the device's default if [`None`].
+    rate: Option<u32>,
+}
+
+impl Settings {
+    /// Gets the [`Settings`] from `args`.
+    pub const fn new(args: &Args) -> Self {
+        Self {
+            silent: silent(args),
+            buffer: args.audio_buffer,
+            rate: args.sample_rate,
+        }
+    }
+
+    /// Whether the device's defaults are used for everything.
+    const fn default(self) -> bool {
+        self.buffer.is_none() && self.rate.is_none()
+    }
+}
+
+/// Probes the audio devices, see [`check`].
+///
+/// `silent` controls whether alsa's logs should be hidden.
+pub fn probe(silent: bool) -> eyre::Result<()> {
+    if silent {
+        silenced(check)?
+    } else {
+        check()
+    }
+}
+
+/// An open output stream, which stops playing as soon as it's dropped.
+pub enum Stream {
+    /// A stream opened by rodio, with all of the device's defaults.
+    Default {
+        /// The stream itself, which only has to be kept around.
+        _stream: OutputStream,
+
+        /// Where sources are sent to be played.
+        handle: OutputStreamHandle,
+    },
+
+    /// A stream opened with cpal directly, since rodio can't set the buffer size.
+    Tuned {
+        /// The stream itself, which only has to be kept around.
+        _stream: cpal::Stream,
+
+        /// Where sources are sent to be played.
+        mixer: Arc<DynamicMixerController<f32>>,
+    },
+}
+
+impl Stream {
+    /// Starts playing `source` on the stream, alongside anything else that's playing.
+    fn play(&self, source: impl Source<Item = f32> + Send + 'static) -> eyre::Result<()> {
+        match self {
+            Self::Default { handle, .. } => handle.play_raw(source)?,
+            Self::Tuned { mixer, .. } => mixer.add(source),
+        }
+
+        Ok(())
+    }
+}
+
+/// Builds a cpal stream which plays whatever's in `mixer`, with samples of type `T`.
+///
+/// Any errors while it's playing are put in `failure`.
+fn build<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &StreamConfig,
+    mut mixer: DynamicMixer<f32>,
+    failure: Failure,
+) -> eyre::Result<cpal::Stream> {
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            for sample in data {
+                *sample = T::from_sample(mixer.next().unwrap_or(0.0));
+            }
+        },
+        move |error| {
+            let mut failure = failure.lock().unwrap_or_else(PoisonError::into_inner);
+            *failure = Some(format!("an error occurred on output stream: {error}"));
+        },
+        None,
+    )?;
+
+    Ok(stream)
+}
+
+/// Opens a stream on the default device with the buffer size & sample rate in `settings`.
+///
+/// The buffer size is kept within whatever the device says it can do,
+/// but the sample rate has to be supported exactly.
+fn tuned(settings: Settings, failure: &Failure) -> eyre::Result<Stream> {
+    let device = cpal::default_host()
+        .default_output_device()
+        .ok_or_else(|| eyre!("No audio output device could be found.\n\n{HELP}"))?;
+    let default = device.default_output_config()?;
+
+    let supported = match settings.rate {
+        Some(rate) => device
+            .supported_output_configs()?
+            .filter(|x| x.channels() == default.channels())
+            .filter(|x| x.sample_format() == default.sample_format())
+            .find(|x| (x.min_sample_rate().0..=x.max_sample_rate().0).contains(&rate))
+            .ok_or_else(|| eyre!("The audio output device can't play at {rate} Hz"))?
+            .with_sample_rate(SampleRate(rate)),
+        None => default,
+    };
+
+    let mut config = supported.config();
+    if let Some(frames) = settings.buffer {
+        config.buffer_size = BufferSize::Fixed(match supported.buffer_size() {
+            SupportedBufferSize::Range { min, max } => frames.clamp(*min, *max),
+            SupportedBufferSize::Unknown => frames,
+        });
+    }
+
+    let (controller, mixer) = dynamic_mixer::mixer(config.channels, config.sample_rate.0);
+    let stream = match supported.sample_format() {
+        SampleFormat::F32 => build::<f32>(&device, &config, mixer, Arc::clone(failure)),
+        SampleFormat::I16 => build::<i16>(&device, &config, mixer, Arc::clone(failure)),
+        SampleFormat::U16 => build::<u16>(&device, &config, mixer, Arc::clone(failure)),
+        SampleFormat::I32 => build::<i32>(&device, &config, mixer, Arc::clone(failure)),
+        format => bail!("The audio output device uses {format} samples, which aren't supported"),
+    }?;
+    stream.play()?;
+
+    Ok(Stream::Tuned {
+        _stream: stream,
+        mixer: controller,
+    })
+}
+
+/// Gets the output stream, which uses rodio's defaults unless `--audio-buffer`
+/// or `--sample-rate` were specified.
+///
+/// Errors from a tuned stream while it's playing are put in `failure`.
+pub fn stream(settings: Settings, failure: &Failure) -> eyre::Result<Stream> {
+    if !settings.default() {
+        let stream = if settings.silent {
+            silenced(|| tuned(settings, failure))?
+        } else {
+            tuned(settings, failure)
+        };
+
+        return stream.wrap_err("Couldn't open the audio output stream");
+    }
+
+    let result = if settings.silent {
+        silenced(OutputStream::try_default)?
+    } else {
+        OutputStream::try_default()
+    };
+
+    let (stream, handle) = result.map_err(|error| match error {
+        StreamError::NoDevice => eyre!("No audio output device could be found.\n\n{HELP}"),
+        other => eyre!("Couldn't open the audio output stream: {other}"),
+    })?;
+
+    Ok(Stream::Default {
+        _stream: stream,
+        handle,
+    })
+}
+
+/// A [`Source`] which plays a [`rodio::Sink`]'s queue on an output stream, and counts
+/// every sample that's asked for, so that [`recover`] can tell when the device is gone.
+///
+/// The queue is shared, so that it can be moved over to a new stream without
+/// having to make a new [`rodio::Sink`].
+struct Relay {
+    /// The queue of the [`rodio::Sink`].
+    queue: Arc<Mutex<SourcesQueueOutput<f32>>>,
+
+    /// How many samples have been played so far.
+    pulled: Arc<AtomicU64>,
+}
+
+impl Relay {
+    /// Locks the queue, which is only ever held by one stream at a time.
+    fn queue(&self) -> MutexGuard<'_, SourcesQueueOutput<f32>> {
+        self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Iterator for Relay {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.pulled.fetch_add(1, Ordering::Relaxed);
+        self.queue().next()
+    }
+}
+
+impl Source for Relay {
+    fn current_frame_len(&self) -> Option<usize> {
+        self.queue().current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.queue().channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.queue().sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.queue().try_seek(pos)
+    }
+}
+
+/// The audio output, which keeps the stream alive and can open a new one on the
+/// default device if the old one goes away, while carrying on with the same queue.
+pub struct Output {
+    /// The stream & its handle, which only have to be kept alive.
+    ///
+    /// This is [`None`] while a new stream is being opened.
+    stream: Mutex<Option<Stream>>,
+
+    /// The queues of the [`rodio::Sink`]s, which are played on whichever stream is open.
+    queues: Vec<Arc<Mutex<SourcesQueueOutput<f32>>>>,
+
+    /// How many samples have been played so far, see [`Relay`].
+    pulled: Arc<AtomicU64>,
+
+    /// How each stream is opened.
+    settings: Settings,
+
+    /// The last error from the stream, see [`Failure`].
+    failure: Failure,
+
+    /// Whether to pause when the device goes away, from `--pause-on-disconnect`.
+    pause: bool,
+}
+
+impl Output {
+    /// Opens a stream on the default device, and starts playing all of the `queues` on it.
+    ///
+    /// `settings` controls how the stream is opened, and `pause`
+    /// is whether to pause when the device goes away.
+    pub fn new(
+        queues: impl IntoIterator<Item = SourcesQueueOutput<f32>>,
+        settings: Settings,
+        pause: bool,
+    ) -> eyre::Result<Self> {
+        let output = Self {
+            stream: Mutex::new(None),
+            queues: queues
+                .into_iter()
+                .map(|x| Arc::new(Mutex::new(x)))
+                .collect(),
+            pulled: Arc::new(AtomicU64::new(0)),
+            settings,
+            failure: Arc::default(),
+            pause,
+        };
+
+        output.reconnect()?;
+        Ok(output)
+    }
+
+    /// Opens a new stream on the default device, and moves the queues over to it.
+    ///
+    /// The old stream is closed first, so that they never both play the queue at once.
+    fn reconnect(&self) -> eyre::Result<()> {
+        let lock = || self.stream.lock().unwrap_or_else(PoisonError::into_inner);
+        drop(lock().take());
+
+        let stream = stream(self.settings, &self.failure)?;
+        for queue in &self.queues {
+            stream.play(Relay {
+                queue: Arc::clone(queue),
+                pulled: Arc::clone(&self.pulled),
+            })?;
+        }
+
+        *lock() = Some(stream);
+        Ok(())
+    }
+
+    /// Gets how many samples have been played so far.
+    fn pulled(&self) -> u64 {
+        self.pulled.load(Ordering::Relaxed)
+    }
+
+    /// Takes the last error from the stream, if there's been one since this was last called.
+    fn failure(&self) -> Option<String> {
+        self.failure
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+}
+
+/// Watches for the output device going away, and moves playback over to the default
+/// device when it does, until the task is aborted. Errors from the stream are shown here too.
+///
+/// The stream asks for samples even while paused or loading, so if it hasn't asked
+/// for any in a while, then it isn't coming back. If there's no device at all,
+/// this keeps trying every [`STALL`] until there is one again.
+///
+/// There's nothing to watch without an [`Output`], so this returns straight away then.
+// This runs for as long as lowfi does, and is aborted when it quits.
+#[allow(clippy::infinite_loop)]
+pub async fn recover(player: Arc<Player>, tx: Sender<Messages>) {
+    // Ticks are never bunched up, like after waking up from sleep,
+    // since the stream wouldn't have had time to ask for anything in between.
+    let mut ticks = interval_at(Instant::now() + STALL, STALL);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let Some(mut last) = player.output.as_ref().map(Output::pulled) else {
+        return;
+    };
+    let mut missing = false;
+
+    loop {
+        ticks.tick().await;
+
+        // The output is borrowed again each time, since it can't be held across an `.await`.
+        let Some(output) = &player.output else {
+            return;
+        };
+
+        if let Some(error) = output.failure() {
+            player.alert(error);
+        }
+
+        let pulled = output.pulled();
+        if pulled != last {
+            if missing {
+                player.alert(String::from("switched to the default audio device"));
+                missing = false;
+            }
+
+            last = pulled;
+            continue;
+        }
+
+        let pause = !missing && output.pause;
+        missing = true;
+        player.alert(String::from(
+            "lost the audio device, waiting for another one...",
+        ));
+
+        // If this fails, then it's just tried again next time.
+        let _ = output.reconnect();
+        last = output.pulled();
+
+        if pause {
+            let _ = tx.send(Messages::Pause).await;
+        }
+    }
+}
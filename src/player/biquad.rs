@@ -0,0 +1,126 @@
+//! Contains the [`Biquad`] filter, which the equalizer & the lofi filter are made out of.
+
+use std::f32::consts::PI;
+
+/// The coefficients of a single biquad filter, already divided by `a0`.
+///
+/// These come from Robert Bristow-Johnson's "Audio EQ Cookbook".
+#[derive(Clone, Copy)]
+pub struct Biquad {
+    /// The feedforward coefficients, `b0` to `b2`.
+    feedforward: [f32; 3],
+
+    /// The feedback coefficients, `a1` & `a2`.
+    feedback: [f32; 2],
+}
+
+/// Works out the sine & cosine of `frequency` at the sample `rate`, in radians per sample.
+///
+/// Frequencies are kept under the highest one that the rate can hold,
+/// since the filter would be unstable otherwise.
+fn angle(frequency: f32, rate: f32) -> (f32, f32) {
+    let frequency = frequency.min(rate * 0.45);
+    (2.0 * PI * frequency / rate).sin_cos()
+}
+
+impl Biquad {
+    /// A filter which doesn't change anything.
+    pub const FLAT: Self = Self {
+        feedforward: [1.0, 0.0, 0.0],
+        feedback: [0.0, 0.0],
+    };
+
+    /// Creates a filter from the raw coefficients, dividing them all by `a0`.
+    fn new([b0, b1, b2]: [f32; 3], [a0, a1, a2]: [f32; 3]) -> Self {
+        Self {
+            feedforward: [b0 / a0, b1 / a0, b2 / a0],
+            feedback: [a1 / a0, a2 / a0],
+        }
+    }
+
+    /// A filter which boosts or cuts everything below or above `frequency` by `gain` in decibels,
+    /// depending on whether it's a `high` shelf.
+    #[cfg(feature = "equalizer")]
+    fn shelf(frequency: f32, gain: f32, rate: f32, high: bool) -> Self {
+        let amplitude = 10f32.powf(gain / 40.0);
+        let (sine, cosine) = angle(frequency, rate);
+        let shelf = sine * amplitude.sqrt() * 2f32.sqrt();
+
+        // The high shelf is the low shelf with the sign of every `cos` flipped.
+        let (cos, sign) = if high { (-cosine, -1.0) } else { (cosine, 1.0) };
+
+        let (plus, minus) = (amplitude + 1.0, amplitude - 1.0);
+        Self::new(
+            [
+                amplitude * ((-minus).mul_add(cos, plus) + shelf),
+                sign * 2.0 * amplitude * plus.mul_add(-cos, minus),
+                amplitude * ((-minus).mul_add(cos, plus) - shelf),
+            ],
+            [
+                minus.mul_add(cos, plus) + shelf,
+                sign * -2.0 * plus.mul_add(cos, minus),
+                minus.mul_add(cos, plus) - shelf,
+            ],
+        )
+    }
+
+    /// A filter which boosts or cuts everything below `frequency` by `gain` in decibels.
+    #[cfg(feature = "equalizer")]
+    pub fn low_shelf(frequency: f32, gain: f32, rate: f32) -> Self {
+        Self::shelf(frequency, gain, rate, false)
+    }
+
+    /// A filter which boosts or cuts everything above `frequency` by `gain` in decibels.
+    #[cfg(feature = "equalizer")]
+    pub fn high_shelf(frequency: f32, gain: f32, rate: f32) -> Self {
+        Self::shelf(frequency, gain, rate, true)
+    }
+
+    /// A filter which boosts or cuts around `frequency` by `gain` in decibels,
+    /// where a higher `width` (the Q) means a narrower band.
+    #[cfg(feature = "equalizer")]
+    pub fn peak(frequency: f32, gain: f32, width: f32, rate: f32) -> Self {
+        let amplitude = 10f32.powf(gain / 40.0);
+        let (sine, cosine) = angle(frequency, rate);
+        let alpha = sine / (2.0 * width);
+
+        Self::new(
+            [
+                alpha.mul_add(amplitude, 1.0),
+                -2.0 * cosine,
+                (-alpha).mul_add(amplitude, 1.0),
+            ],
+            [
+                1.0 + alpha / amplitude,
+                -2.0 * cosine,
+                1.0 - alpha / amplitude,
+            ],
+        )
+    }
+
+    /// A filter which lets everything below `frequency` through, and muffles everything above it.
+    pub fn low_pass(frequency: f32, width: f32, rate: f32) -> Self {
+        let (sine, cosine) = angle(frequency, rate);
+        let alpha = sine / (2.0 * width);
+        let low = (1.0 - cosine) / 2.0;
+
+        Self::new(
+            [low, 1.0 - cosine, low],
+            [1.0 + alpha, -2.0 * cosine, 1.0 - alpha],
+        )
+    }
+
+    /// Filters a single `sample`, where `history` is the last two inputs & outputs.
+    pub fn process(&self, sample: f32, history: &mut [f32; 4]) -> f32 {
+        let [x1, x2, y1, y2] = *history;
+        let [b0, b1, b2] = self.feedforward;
+        let [a1, a2] = self.feedback;
+        let output = b0.mul_add(
+            sample,
+            b1.mul_add(x1, b2.mul_add(x2, (-a1).mul_add(y1, -a2 * y2))),
+        );
+
+        *history = [sample, x1, output, y1];
+        output
+    }
+}
@@ -0,0 +1,110 @@
+//! Contains the [`Bookmarks`] struct, which manages the user's
+//! favorite tracks and persists them in the data directory.
+
+use std::path::PathBuf;
+
+use eyre::eyre;
+use tokio::{fs, sync::RwLock};
+
+use crate::{play, tracks::list::List};
+
+/// The name used to refer to the bookmarks, both as a file
+/// in the data directory & as a list name for `--tracks`.
+pub const NAME: &str = "bookmarks";
+
+/// The bookmarks, which are loaded at startup and saved on shutdown.
+///
+/// `bookmarks.txt` is just a list of full track URLs, seperated by newlines.
+/// Like other lists, it can also have comments & disabled tracks, which are kept when saving.
+pub struct Bookmarks {
+    /// The full paths of all of the bookmarked tracks, as well as any comments.
+    entries: RwLock<Vec<String>>,
+}
+
+impl Bookmarks {
+    /// Retrieves the path to `bookmarks.txt` in the data directory.
+    async fn path() -> eyre::Result<PathBuf> {
+        let data = play::data_dir()?;
+
+        if !data.exists() {
+            fs::create_dir_all(&data).await?;
+        }
+
+        Ok(data.join(format!("{NAME}.txt")))
+    }
+
+    /// Loads the [`Bookmarks`] from [`dirs::data_dir()`].
+    pub async fn load() -> eyre::Result<Self> {
+        let path = Self::path().await?;
+
+        let entries = if path.exists() {
+            fs::read_to_string(path)
+                .await?
+                .lines()
+                .map(str::trim)
+                .filter(|x| !x.is_empty())
+                .map(ToOwned::to_owned)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            entries: RwLock::new(entries),
+        })
+    }
+
+    /// Saves the bookmarks to `bookmarks.txt`.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path().await?;
+        let mut contents = self.entries.read().await.join("\n");
+        contents.push('\n');
+
+        play::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    /// Bookmarks a track if it isn't bookmarked, and removes it if it is.
+    ///
+    /// Returns whether the track is bookmarked after toggling.
+    pub async fn toggle(&self, path: &str) -> bool {
+        let mut entries = self.entries.write().await;
+
+        if let Some(index) = entries.iter().position(|x| x == path) {
+            entries.remove(index);
+            false
+        } else {
+            entries.push(path.to_owned());
+            true
+        }
+    }
+
+    /// Checks whether a track is bookmarked.
+    pub async fn contains(&self, path: &str) -> bool {
+        self.entries.read().await.iter().any(|x| x == path)
+    }
+
+    /// Gets the full paths of the bookmarked tracks, leaving out any comments.
+    pub async fn paths(&self) -> Vec<String> {
+        self.entries
+            .read()
+            .await
+            .iter()
+            .filter(|x| !List::is_comment(x))
+            .cloned()
+            .collect()
+    }
+
+    /// Builds a playable [List] out of the bookmarked tracks.
+    pub async fn list(&self) -> eyre::Result<List> {
+        let entries = self.entries.read().await.clone();
+        if entries.iter().all(|x| List::is_comment(x)) {
+            return Err(eyre!(
+                "You don't have any bookmarks yet, press 'b' while a track is playing to add one"
+            ));
+        }
+
+        Ok(List::from_paths(NAME, &entries))
+    }
+}
@@ -0,0 +1,104 @@
+//! Contains the [`Clock`] struct, which keeps track of how long
+//! the current track has actually been playing for.
+
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The internal state of the [`Clock`].
+struct State {
+    /// How much of the track has played up until `resumed`.
+    elapsed: Duration,
+
+    /// When playback was last resumed, or [`None`] if it's paused.
+    resumed: Option<Instant>,
+
+    /// The playback speed, where 1.0 is normal speed.
+    speed: f32,
+}
+
+impl State {
+    /// Gets the total amount of the track that has played.
+    fn elapsed(&self) -> Duration {
+        self.resumed.map_or(self.elapsed, |resumed| {
+            self.elapsed + resumed.elapsed().mul_f32(self.speed)
+        })
+    }
+
+    /// Folds the time since `resumed` into `elapsed`,
+    /// which is needed before changing anything about the playback.
+    fn fold(&mut self) {
+        self.elapsed = self.elapsed();
+
+        if self.resumed.is_some() {
+            self.resumed = Some(Instant::now());
+        }
+    }
+}
+
+/// A monotonic playback clock, which accounts for pauses & speed changes.
+///
+/// This is owned by the [`super::Player`], which updates it whenever
+/// playback changes, so that every consumer agrees on the position of the track.
+pub struct Clock {
+    /// The actual state, behind a [`Mutex`] since it's shared between threads.
+    state: Mutex<State>,
+}
+
+impl Clock {
+    /// Creates a new [`Clock`], which starts out paused at zero.
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(State {
+                elapsed: Duration::ZERO,
+                resumed: None,
+                speed: 1.0,
+            }),
+        }
+    }
+
+    /// Resets the clock to zero, which should be done when a new track starts.
+    pub fn reset(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        state.elapsed = Duration::ZERO;
+        state.resumed = if paused { None } else { Some(Instant::now()) };
+    }
+
+    /// Pauses or resumes the clock.
+    pub fn set_paused(&self, paused: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        state.fold();
+        state.resumed = match (paused, state.resumed) {
+            (true, _) => None,
+            (false, None) => Some(Instant::now()),
+            (false, resumed) => resumed,
+        };
+    }
+
+    /// Moves the clock to `position`, which should be done after seeking.
+    pub fn seek(&self, position: Duration) {
+        let mut state = self.state.lock().unwrap();
+
+        state.fold();
+        state.elapsed = position;
+    }
+
+    /// Changes the speed that the clock moves at.
+    ///
+    /// The speed can only be changed through MPRIS for now.
+    #[cfg(feature = "mpris")]
+    pub fn set_speed(&self, speed: f32) {
+        let mut state = self.state.lock().unwrap();
+
+        state.fold();
+        state.speed = speed;
+    }
+
+    /// Gets how long the current track has actually played for.
+    pub fn elapsed(&self) -> Duration {
+        self.state.lock().unwrap().elapsed()
+    }
+}
@@ -1,14 +1,17 @@
 //! Contains the [`Downloader`] struct.
 
-use std::sync::Arc;
+use std::{iter, sync::Arc};
 
+use futures::future::join_all;
 use tokio::{
     sync::mpsc::{self, Receiver, Sender},
     task::{self, JoinHandle},
     time::sleep,
 };
 
-use super::{Player, BUFFER_SIZE, TIMEOUT};
+use crate::tracks::skips::Reason;
+
+use super::{ui, Player};
 
 /// This struct is responsible for downloading tracks in the background.
 ///
@@ -42,6 +45,29 @@ impl Downloader {
         Self { player, rx, tx }
     }
 
+    /// Downloads a single track and pushes it to the buffer.
+    ///
+    /// Returns whether the download was successful.
+    async fn download(player: &Player) -> bool {
+        let track = player
+            .list
+            .random(&player.fetcher, &player.plays, player.deck.as_ref(), false)
+            .await;
+
+        match track {
+            Ok(track) => {
+                player.tracks.write().await.push_back(track);
+                ui::redraw();
+                true
+            }
+            Err(error) => {
+                player.skips.record(Reason::Error);
+                player.fail(&error.into());
+                false
+            }
+        }
+    }
+
     /// Actually starts & consumes the [Downloader].
     pub fn start(mut self) -> (Sender<()>, JoinHandle<()>) {
         (
@@ -49,16 +75,40 @@ impl Downloader {
             task::spawn(async move {
                 // Loop through each update notification.
                 while self.rx.recv().await == Some(()) {
+                    // How many times in a row every download has failed.
+                    let mut failures = 0;
+
                     //  For each update notification, we'll push tracks until the buffer is completely full.
-                    while self.player.tracks.read().await.len() < BUFFER_SIZE {
-                        match self.player.list.random(&self.player.client).await {
-                            Ok(track) => self.player.tracks.write().await.push_back(track),
-                            Err(error) => {
-                                if !error.is_timeout() {
-                                    sleep(TIMEOUT).await;
-                                }
-                            }
+                    loop {
+                        let missing = self
+                            .player
+                            .buffer_size
+                            .saturating_sub(self.player.buffered().await);
+                        if missing == 0 {
+                            break;
                         }
+
+                        // If lowfi has been paused for a while, then it's probably not going to be
+                        // unpaused any time soon, so there's no point in using up bandwidth.
+                        self.player.wait_until_active().await;
+
+                        // Download as many tracks at once as there are workers, but never
+                        // more than there's room for. Each track is pushed as soon as it's done.
+                        let workers = missing.min(self.player.workers);
+                        let downloads = iter::repeat_with(|| Self::download(&self.player));
+                        if join_all(downloads.take(workers)).await.contains(&true) {
+                            failures = 0;
+                            continue;
+                        }
+
+                        // If nothing could be downloaded, then back off before trying again,
+                        // or wait until the next notification if there have been too many retries.
+                        failures += 1;
+                        if self.player.retry.exhausted(failures) {
+                            break;
+                        }
+
+                        sleep(self.player.retry.delay(failures)).await;
                     }
                 }
             }),
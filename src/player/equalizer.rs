@@ -0,0 +1,276 @@
+//! Contains the [`Equalizer`], a simple three band EQ which is applied to every track,
+//! and which can be set up in `config.toml` or adjusted with the `e`, `[` & `]` keys.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Sample, Source};
+use serde::Deserialize;
+
+use super::biquad::Biquad;
+
+/// The most that any band can be boosted or cut by, in decibels.
+const LIMIT: f32 = 12.0;
+
+/// The frequency of each band, in Hz, from the bass up to the treble.
+const FREQUENCIES: [f32; 3] = [120.0, 1000.0, 8000.0];
+
+/// The Q of the middle band, which is how narrow it is.
+const WIDTH: f32 = 0.7;
+
+/// The gain of each band from the `[equalizer]` section of `config.toml`, in decibels.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// The gain of the low shelf, which is what people usually mean by bass boost.
+    pub bass: f32,
+
+    /// The gain of the middle band.
+    pub mid: f32,
+
+    /// The gain of the high shelf.
+    pub treble: f32,
+}
+
+impl Settings {
+    /// Gets the gain of every band, which are kept within [`LIMIT`].
+    fn gains(self) -> [f32; 3] {
+        [self.bass, self.mid, self.treble].map(|x| x.clamp(-LIMIT, LIMIT))
+    }
+}
+
+/// A change to the [`Equalizer`], which is sent by the keybindings.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Change {
+    /// Turns the equalizer off, or back on.
+    Toggle,
+
+    /// Raises or lowers the bass by this many decibels.
+    Bass(f32),
+}
+
+/// The settings of the [`Equalizer`], which are shared with every track that it's applied to.
+///
+/// Like the [`super::visualizer::Visualizer`], these are atomics so that the audio
+/// thread never has to wait on a lock.
+pub struct Equalizer {
+    /// Whether the tracks are left alone, which is toggled with `e`.
+    bypassed: AtomicBool,
+
+    /// The bits of the [`f32`] gain of each band, in decibels.
+    gains: [AtomicU32; 3],
+
+    /// Goes up each time anything changes, so the filters know to work out new coefficients.
+    version: AtomicU32,
+}
+
+impl Equalizer {
+    /// Creates a new [`Equalizer`] with every band at the gain in `settings`.
+    pub fn new(settings: Settings) -> Self {
+        let gains = settings.gains().map(|x| AtomicU32::new(x.to_bits()));
+
+        Self {
+            bypassed: AtomicBool::new(false),
+            gains,
+            version: AtomicU32::new(0),
+        }
+    }
+
+    /// Sets each band to the gain in `settings`, like when `config.toml` changes.
+    ///
+    /// Only the bands which are different from the `old` settings are set, so that
+    /// a band which was changed with a keybinding isn't reset when another one is edited.
+    pub fn set(&self, old: Settings, settings: Settings) {
+        let bands = self.gains.iter().zip(old.gains()).zip(settings.gains());
+        for ((band, old), gain) in bands {
+            if gain.to_bits() != old.to_bits() {
+                band.store(gain.to_bits(), Ordering::Relaxed);
+            }
+        }
+
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the gain of every band, in decibels.
+    fn gains(&self) -> [f32; 3] {
+        let [bass, mid, treble] = &self.gains;
+        [bass, mid, treble].map(|x| f32::from_bits(x.load(Ordering::Relaxed)))
+    }
+
+    /// Applies `change`, returning a short message about what it did to show in the window.
+    pub fn change(&self, change: Change) -> String {
+        match change {
+            Change::Toggle => {
+                let bypassed = !self.bypassed.fetch_xor(true, Ordering::Relaxed);
+                self.version.fetch_add(1, Ordering::Relaxed);
+
+                String::from(if bypassed {
+                    "equalizer off"
+                } else {
+                    "equalizer on"
+                })
+            }
+            Change::Bass(change) => {
+                let bass = (self.gains()[0] + change).clamp(-LIMIT, LIMIT);
+                self.gains[0].store(bass.to_bits(), Ordering::Relaxed);
+                self.bypassed.store(false, Ordering::Relaxed);
+                self.version.fetch_add(1, Ordering::Relaxed);
+
+                format!("bass {bass:+.0} dB")
+            }
+        }
+    }
+
+    /// Wraps `source` so that it's equalized as it's played.
+    pub fn apply<S: Source>(self: &Arc<Self>, source: S) -> Filter<S>
+    where
+        S::Item: Sample,
+    {
+        Filter {
+            source,
+            equalizer: Arc::clone(self),
+            version: None,
+            rate: 0,
+            bypassed: false,
+            bands: [Biquad::FLAT; 3],
+            history: Vec::new(),
+            channel: 0,
+        }
+    }
+}
+
+/// Works out the filter for `band`, with `gain` in decibels, at the sample `rate`.
+///
+/// The first band is a low shelf, the last is a high shelf, and the middle is a peak.
+fn filter(band: usize, gain: f32, rate: f32) -> Biquad {
+    let frequency = FREQUENCIES[band];
+    match band {
+        0 => Biquad::low_shelf(frequency, gain, rate),
+        1 => Biquad::peak(frequency, gain, WIDTH, rate),
+        _ => Biquad::high_shelf(frequency, gain, rate),
+    }
+}
+
+/// A [`Source`] which runs another one through an [`Equalizer`].
+pub struct Filter<S> {
+    /// The source that's actually being played.
+    source: S,
+
+    /// Where the gains come from.
+    equalizer: Arc<Equalizer>,
+
+    /// The version of the [`Equalizer`] that `bands` were worked out for,
+    /// which is [`None`] before the first sample.
+    version: Option<u32>,
+
+    /// The sample rate that `bands` were worked out for.
+    rate: u32,
+
+    /// Whether the [`Equalizer`] was bypassed when `bands` were worked out.
+    bypassed: bool,
+
+    /// The filter for each band.
+    bands: [Biquad; 3],
+
+    /// The last two inputs & outputs of each band, for each channel.
+    history: Vec<[[f32; 4]; 3]>,
+
+    /// Which channel the next sample belongs to.
+    channel: u16,
+}
+
+impl<S: Source> Filter<S>
+where
+    S::Item: Sample,
+{
+    /// Works out the filters again if the [`Equalizer`] or the sample rate changed.
+    fn update(&mut self) {
+        let version = self.equalizer.version.load(Ordering::Relaxed);
+        let rate = self.source.sample_rate();
+        if self.version == Some(version) && self.rate == rate {
+            return;
+        }
+
+        let gains = self.equalizer.gains();
+        for (band, filter) in self.bands.iter_mut().enumerate() {
+            *filter = self::filter(band, gains[band], rate as f32);
+        }
+
+        self.version = Some(version);
+        self.rate = rate;
+        self.bypassed = self.equalizer.bypassed.load(Ordering::Relaxed);
+        self.history = vec![[[0.0; 4]; 3]; usize::from(self.source.channels().max(1))];
+    }
+}
+
+impl<S: Source> Iterator for Filter<S>
+where
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?.to_f32();
+        self.update();
+
+        let channel = usize::from(self.channel);
+        self.channel = (self.channel + 1) % self.source.channels().max(1);
+
+        if self.bypassed {
+            return Some(sample);
+        }
+
+        let Some(history) = self.history.get_mut(channel) else {
+            return Some(sample);
+        };
+
+        let output = self
+            .bands
+            .iter()
+            .zip(history.iter_mut())
+            .fold(sample, |sample, (band, history)| {
+                band.process(sample, history)
+            });
+
+        // Boosting can push loud tracks past full scale, which would wrap around otherwise.
+        Some(output.clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Filter<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // Seeking always lands at the start of a frame, and the old history would click.
+        self.channel = 0;
+        self.version = None;
+
+        self.source.try_seek(pos)
+    }
+}
@@ -0,0 +1,78 @@
+//! Contains the [`History`] struct, which keeps track of
+//! which tracks have been played during this session.
+
+use std::{
+    collections::{BTreeSet, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use crate::tracks::Info;
+
+/// The most tracks that will be kept in the [`History`].
+const MAX_LEN: usize = 32;
+
+/// The tracks which have been played recently, newest first.
+///
+/// This includes the current track, and isn't saved across sessions.
+pub struct History {
+    /// The actual tracks.
+    entries: Mutex<VecDeque<Arc<Info>>>,
+
+    /// How many tracks have been played in total, which unlike
+    /// `entries` isn't limited to the most recent ones.
+    played: AtomicUsize,
+
+    /// The paths of the tracks which the user skipped before they finished.
+    skipped: Mutex<BTreeSet<String>>,
+}
+
+impl History {
+    /// Creates a new, empty [`History`].
+    pub const fn new() -> Self {
+        Self {
+            entries: Mutex::new(VecDeque::new()),
+            played: AtomicUsize::new(0),
+            skipped: Mutex::new(BTreeSet::new()),
+        }
+    }
+
+    /// Adds a track which just started playing.
+    ///
+    /// If the track has been played before, then it's moved to the top rather than repeated.
+    pub fn push(&self, info: Arc<Info>) {
+        self.played.fetch_add(1, Ordering::Relaxed);
+        let mut entries = self.entries.lock().unwrap();
+
+        entries.retain(|x| x.full_path != info.full_path);
+        entries.push_front(info);
+        entries.truncate(MAX_LEN);
+    }
+
+    /// Remembers that the track at `path` was skipped.
+    pub fn skip(&self, path: &str) {
+        self.skipped.lock().unwrap().insert(path.to_owned());
+    }
+
+    /// Whether the track at `path` has been skipped during this session.
+    pub fn skipped(&self, path: &str) -> bool {
+        self.skipped.lock().unwrap().contains(path)
+    }
+
+    /// Gets the track at `index`, where 0 is the most recent one.
+    pub fn get(&self, index: usize) -> Option<Arc<Info>> {
+        self.entries.lock().unwrap().get(index).cloned()
+    }
+
+    /// Gets how many tracks have been played during this session, including repeats.
+    pub fn played(&self) -> usize {
+        self.played.load(Ordering::Relaxed)
+    }
+
+    /// Gets all of the tracks, newest first.
+    pub fn entries(&self) -> Vec<Arc<Info>> {
+        self.entries.lock().unwrap().iter().cloned().collect()
+    }
+}
@@ -0,0 +1,127 @@
+//! Contains the [`Hooks`], which are shell commands from `config.toml` that are run
+//! when something happens in the player, like a new track starting.
+//!
+//! Each hook gets the state of the player in its environment, as `LOWFI_TRACK`,
+//! `LOWFI_ARTIST`, `LOWFI_ALBUM`, `LOWFI_DURATION`, `LOWFI_LIST`, `LOWFI_PAUSED` & `LOWFI_VOLUME`,
+//! as well as `LOWFI_EVENT`, which is the name of the hook without the `on_`.
+
+use std::{process::Stdio, sync::Arc};
+
+use serde::Deserialize;
+use tokio::process::Command;
+
+use super::{snapshot::Snapshot, Player};
+
+/// The hooks from the `[hooks]` section of `config.toml`, which are all optional.
+#[derive(Clone, Default, Deserialize)]
+#[serde(default)]
+// The fields are named after the keys in `config.toml`.
+#[allow(clippy::struct_field_names)]
+pub struct Hooks {
+    /// Run whenever a new track starts, including when a track is replayed.
+    pub on_track_change: Option<String>,
+
+    /// Run whenever playback is paused or unpaused, which can be told apart with `LOWFI_PAUSED`.
+    pub on_pause: Option<String>,
+
+    /// Run when lowfi quits, which waits for it to finish first.
+    pub on_quit: Option<String>,
+}
+
+/// Makes a [`Command`] which runs `hook` in the shell, with `snapshot` in its environment.
+///
+/// The hook's output is thrown away, since it would otherwise end up all over the window.
+fn command(hook: &str, event: &str, snapshot: &Snapshot) -> Command {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+
+    let duration = snapshot
+        .duration
+        .map(|x| x.as_secs().to_string())
+        .unwrap_or_default();
+
+    let mut command = Command::new(shell);
+    command
+        .args([flag, hook])
+        .env("LOWFI_EVENT", event)
+        .env("LOWFI_TRACK", snapshot.track.as_deref().unwrap_or_default())
+        .env(
+            "LOWFI_ARTIST",
+            snapshot.artist.as_deref().unwrap_or_default(),
+        )
+        .env("LOWFI_ALBUM", snapshot.album.as_deref().unwrap_or_default())
+        .env("LOWFI_DURATION", duration)
+        .env("LOWFI_LIST", &snapshot.list)
+        .env("LOWFI_PAUSED", snapshot.paused.to_string())
+        .env("LOWFI_VOLUME", format!("{:.0}", snapshot.volume * 100.0))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    command
+}
+
+impl Hooks {
+    /// Whether there are any hooks to run while lowfi is playing, which is
+    /// used to avoid checking on the player when there's nothing to do.
+    pub const fn any(&self) -> bool {
+        self.on_track_change.is_some() || self.on_pause.is_some()
+    }
+
+    /// Starts `hook` in the background if it's set, without waiting for it.
+    ///
+    /// Any errors are shown in the window, rather than stopping lowfi.
+    fn spawn(player: &Player, snapshot: &Snapshot, hook: Option<&str>, event: &str) {
+        let Some(hook) = hook else {
+            return;
+        };
+
+        if let Err(error) = command(hook, event, snapshot).spawn() {
+            player.alert(format!("couldn't run the {event} hook: {error}"));
+        }
+    }
+
+    /// Checks on the player, and runs `on_track_change` & `on_pause` when they happen.
+    pub async fn run(self, player: Arc<Player>) {
+        let mut snapshots = player.snapshots.subscribe();
+
+        // Replaying a track makes a new `Info`, so it counts as a track change.
+        let mut playing = None;
+        let mut paused = player.sink.is_paused();
+
+        while snapshots.changed().await.is_ok() {
+            let snapshot = snapshots.borrow_and_update().clone();
+
+            let current = player.current.load_full();
+            if let Some(current) = &current {
+                if !playing.as_ref().is_some_and(|x| Arc::ptr_eq(x, current)) {
+                    let hook = self.on_track_change.as_deref();
+                    Self::spawn(&player, &snapshot, hook, "track_change");
+                }
+            }
+
+            if current.is_some() {
+                playing = current;
+            }
+
+            if snapshot.paused != paused {
+                paused = snapshot.paused;
+                Self::spawn(&player, &snapshot, self.on_pause.as_deref(), "pause");
+            }
+        }
+    }
+
+    /// Runs `on_quit` if it's set, and waits for it to finish.
+    pub async fn quit(&self, player: &Player) -> eyre::Result<()> {
+        if let Some(hook) = &self.on_quit {
+            command(hook, "quit", &player.snapshot().await)
+                .status()
+                .await?;
+        }
+
+        Ok(())
+    }
+}
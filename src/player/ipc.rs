@@ -0,0 +1,147 @@
+//! Contains the socket that lets other programs control lowfi while it's running,
+//! as well as the `ctl` & `now-playing` commands, which send one request over it.
+//!
+//! Each connection sends a single line with a [`remote`] request, like `volume +10`, and gets a
+//! single line back, which is either `ok`, `error: ` followed by what went wrong, or an answer.
+//!
+//! [`remote`]: super::remote
+
+use std::{
+    os::unix::fs::MetadataExt,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use eyre::{bail, eyre};
+use tokio::{
+    fs,
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::mpsc::Sender,
+    task,
+};
+
+use super::{
+    remote,
+    snapshot::Snapshot,
+    ui::{format::Locale, title::Template},
+    Messages, Player,
+};
+use crate::{play, CtlCommands};
+
+/// The inode of the socket that this instance is listening on, or 0 if it isn't listening yet.
+///
+/// This is used to tell if the socket has since been replaced by another instance.
+static INODE: AtomicU64 = AtomicU64::new(0);
+
+/// Gets the path to the socket, which is in the runtime directory if there is one.
+///
+/// There's only one socket, so if several instances are running, the newest one is controlled.
+fn path() -> eyre::Result<PathBuf> {
+    let dir = dirs::runtime_dir().map_or_else(play::data_dir, Ok)?;
+    Ok(dir.join("lowfi.sock"))
+}
+
+/// Reads a request from `stream`, and writes the response back.
+async fn handle(
+    player: Arc<Player>,
+    sender: Sender<Messages>,
+    stream: UnixStream,
+) -> eyre::Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut request = String::new();
+    BufReader::new(reader).read_line(&mut request).await?;
+
+    let response = remote::respond(&player, &sender, request.trim())
+        .await
+        .unwrap_or_else(|error| format!("error: {error}"));
+
+    writer.write_all(format!("{response}\n").as_bytes()).await?;
+
+    Ok(())
+}
+
+/// Listens on the socket, handling each connection in the background.
+pub async fn serve(player: Arc<Player>, sender: Sender<Messages>) -> eyre::Result<()> {
+    let path = path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    // A socket left behind by an instance that crashed would stop this one from listening.
+    if path.exists() {
+        fs::remove_file(&path).await?;
+    }
+
+    let listener = UnixListener::bind(&path)?;
+    INODE.store(fs::metadata(&path).await?.ino(), Ordering::Relaxed);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        task::spawn(handle(Arc::clone(&player), sender.clone(), stream));
+    }
+}
+
+/// Removes the socket when quitting, unless a newer instance has since replaced it.
+pub async fn remove() -> eyre::Result<()> {
+    let path = path()?;
+    let inode = INODE.load(Ordering::Relaxed);
+    if inode != 0 && fs::metadata(&path).await.is_ok_and(|x| x.ino() == inode) {
+        fs::remove_file(&path).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends a single `request` to the running instance of lowfi, and returns the answer.
+async fn request(request: &str) -> eyre::Result<String> {
+    let stream = UnixStream::connect(path()?)
+        .await
+        .map_err(|_error| eyre!("Couldn't connect to lowfi, is it running?"))?;
+    let (reader, mut writer) = stream.into_split();
+    writer.write_all(format!("{request}\n").as_bytes()).await?;
+
+    let mut response = String::new();
+    BufReader::new(reader).read_line(&mut response).await?;
+
+    let response = response.trim();
+    if let Some(error) = response.strip_prefix("error: ") {
+        bail!("{error}");
+    }
+
+    Ok(response.to_owned())
+}
+
+/// Prints what the running instance of lowfi is playing, filled into a [`Template`] like `{artist} - {title}`.
+pub async fn now_playing(format: &str, locale: Option<&str>) -> eyre::Result<()> {
+    let template = Template::parse(format)?;
+    let snapshot: Snapshot = serde_json::from_str(&request("now-playing").await?)?;
+    println!("{}", template.render(&snapshot, Locale::detect(locale)));
+
+    Ok(())
+}
+
+/// Sends `command` to the running instance of lowfi, and prints the answer if there is one.
+pub async fn ctl(command: CtlCommands) -> eyre::Result<()> {
+    let request = match command {
+        CtlCommands::Next => "next".to_owned(),
+        CtlCommands::Previous => "previous".to_owned(),
+        CtlCommands::Play => "play".to_owned(),
+        CtlCommands::Pause => "pause".to_owned(),
+        CtlCommands::PlayPause => "play-pause".to_owned(),
+        CtlCommands::Bookmark => "bookmark".to_owned(),
+        CtlCommands::Quit => "quit".to_owned(),
+        CtlCommands::Volume { change } => format!("volume {change}"),
+        CtlCommands::NowPlaying => "now-playing".to_owned(),
+    };
+
+    let response = self::request(&request).await?;
+    if response != "ok" {
+        println!("{response}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,279 @@
+//! Contains the lofi filter, which muffles whatever's playing and adds some vinyl crackle
+//! over it, so that any track list sounds like proper background lofi. It's toggled with `f`.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rodio::{source::SeekError, Sample, Source};
+use serde::Deserialize;
+
+use super::biquad::Biquad;
+
+/// How sharp the cutoff of the low pass is, which is flat for this Q.
+const WIDTH: f32 = 0.707;
+
+/// About how many pops of crackle there are every second.
+const POPS: f32 = 6.0;
+
+/// How loud the loudest pop can be.
+const POP: f32 = 0.08;
+
+/// How much of each pop is left after every sample, which makes them short clicks.
+const DECAY: f32 = 0.9;
+
+/// How loud the constant hiss under the pops is.
+const HISS: f32 = 0.002;
+
+/// The lofi filter's settings from the `[lofi]` section of `config.toml`.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Whether the filter is on when lowfi starts.
+    pub enabled: bool,
+
+    /// The frequency which everything above is muffled, in Hz.
+    pub cutoff: f32,
+
+    /// Whether to add vinyl crackle, or only muffle the tracks.
+    pub crackle: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cutoff: 2500.0,
+            crackle: true,
+        }
+    }
+}
+
+/// The settings of the filter, which are shared with every track that it's applied to.
+///
+/// Like the [`super::visualizer::Visualizer`], these are atomics so that the audio
+/// thread never has to wait on a lock.
+pub struct Lofi {
+    /// Whether the filter is on, which is toggled with `f`.
+    enabled: AtomicBool,
+
+    /// The bits of the [`f32`] cutoff, in Hz.
+    cutoff: AtomicU32,
+
+    /// Whether to add vinyl crackle.
+    crackle: AtomicBool,
+
+    /// Goes up each time anything changes, so the tracks know to update.
+    version: AtomicU32,
+}
+
+impl Lofi {
+    /// Creates a new [`Lofi`] filter, with the default [`Settings`].
+    pub fn new() -> Self {
+        let settings = Settings::default();
+
+        Self {
+            enabled: AtomicBool::new(settings.enabled),
+            cutoff: AtomicU32::new(settings.cutoff.to_bits()),
+            crackle: AtomicBool::new(settings.crackle),
+            version: AtomicU32::new(0),
+        }
+    }
+
+    /// Applies `settings`, like when `config.toml` changes.
+    ///
+    /// Only the settings which are different from the `old` ones are applied,
+    /// so that toggling the filter with `f` isn't undone by editing something else.
+    pub fn set(&self, old: Settings, settings: Settings) {
+        if settings.enabled != old.enabled {
+            self.enabled.store(settings.enabled, Ordering::Relaxed);
+        }
+
+        if settings.cutoff.to_bits() != old.cutoff.to_bits() {
+            // Anything under 20Hz would just be silence.
+            let cutoff = settings.cutoff.max(20.0);
+            self.cutoff.store(cutoff.to_bits(), Ordering::Relaxed);
+        }
+
+        if settings.crackle != old.crackle {
+            self.crackle.store(settings.crackle, Ordering::Relaxed);
+        }
+
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Turns the filter on or off, returning a short message about it to show in the window.
+    pub fn toggle(&self) -> String {
+        let enabled = !self.enabled.fetch_xor(true, Ordering::Relaxed);
+        self.version.fetch_add(1, Ordering::Relaxed);
+
+        String::from(if enabled {
+            "lofi filter on"
+        } else {
+            "lofi filter off"
+        })
+    }
+
+    /// Wraps `source` so that it goes through the filter as it's played.
+    pub fn apply<S: Source>(self: &Arc<Self>, source: S) -> Vinyl<S>
+    where
+        S::Item: Sample,
+    {
+        Vinyl {
+            source,
+            lofi: Arc::clone(self),
+            version: None,
+            rate: 0,
+            enabled: false,
+            crackle: false,
+            low_pass: Biquad::FLAT,
+            history: Vec::new(),
+            channel: 0,
+            pop: 0.0,
+            noise: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+}
+
+/// A [`Source`] which runs another one through the [`Lofi`] filter.
+pub struct Vinyl<S> {
+    /// The source that's actually being played.
+    source: S,
+
+    /// Where the settings come from.
+    lofi: Arc<Lofi>,
+
+    /// The version of the [`Lofi`] filter that the settings below are from,
+    /// which is [`None`] before the first sample.
+    version: Option<u32>,
+
+    /// The sample rate that `low_pass` was worked out for.
+    rate: u32,
+
+    /// Whether the filter is on.
+    enabled: bool,
+
+    /// Whether to add vinyl crackle.
+    crackle: bool,
+
+    /// The filter which muffles the track.
+    low_pass: Biquad,
+
+    /// The last two inputs & outputs of the low pass, for each channel.
+    history: Vec<[f32; 4]>,
+
+    /// Which channel the next sample belongs to.
+    channel: u16,
+
+    /// How loud the current pop is, which is the same on every channel.
+    pop: f32,
+
+    /// The crackle of the current frame, which is the same on every channel.
+    noise: f32,
+
+    /// Decides when pops happen & how loud they are.
+    rng: StdRng,
+}
+
+impl<S: Source> Vinyl<S>
+where
+    S::Item: Sample,
+{
+    /// Works out the low pass again if the [`Lofi`] filter or the sample rate changed.
+    fn update(&mut self) {
+        let version = self.lofi.version.load(Ordering::Relaxed);
+        let rate = self.source.sample_rate();
+        if self.version == Some(version) && self.rate == rate {
+            return;
+        }
+
+        let cutoff = f32::from_bits(self.lofi.cutoff.load(Ordering::Relaxed));
+        self.low_pass = Biquad::low_pass(cutoff, WIDTH, rate as f32);
+        self.enabled = self.lofi.enabled.load(Ordering::Relaxed);
+        self.crackle = self.lofi.crackle.load(Ordering::Relaxed);
+
+        self.version = Some(version);
+        self.rate = rate;
+        self.history = vec![[0.0; 4]; usize::from(self.source.channels().max(1))];
+    }
+
+    /// Works out the crackle for the next frame, which is a pop every now & then
+    /// that dies away quickly, on top of a quiet hiss.
+    fn crackle(&mut self) -> f32 {
+        self.pop *= DECAY;
+        if self.rng.gen::<f32>() < POPS / self.rate.max(1) as f32 {
+            self.pop = self.rng.gen_range(-POP..POP);
+        }
+
+        self.pop + self.rng.gen_range(-HISS..HISS)
+    }
+}
+
+impl<S: Source> Iterator for Vinyl<S>
+where
+    S::Item: Sample,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?.to_f32();
+        self.update();
+
+        let channel = usize::from(self.channel);
+        self.channel = (self.channel + 1) % self.source.channels().max(1);
+
+        if !self.enabled {
+            return Some(sample);
+        }
+
+        if channel == 0 {
+            self.noise = if self.crackle { self.crackle() } else { 0.0 };
+        }
+
+        let Some(history) = self.history.get_mut(channel) else {
+            return Some(sample);
+        };
+
+        let output = self.low_pass.process(sample, history) + self.noise;
+        Some(output.clamp(-1.0, 1.0))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Vinyl<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // Seeking always lands at the start of a frame, and the old history would click.
+        self.channel = 0;
+        self.version = None;
+
+        self.source.try_seek(pos)
+    }
+}
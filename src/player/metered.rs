@@ -0,0 +1,60 @@
+//! Detects whether the internet connection is metered, so that
+//! lowfi can avoid downloading more than it has to.
+//!
+//! For now, this only works on Linux with `NetworkManager`, and
+//! on every other platform the connection is assumed to be unmetered.
+
+use tokio::task;
+
+/// How many tracks to download in advance on a metered connection,
+/// which is just enough for skipping to still be instant.
+pub const BUFFER_SIZE: usize = 1;
+
+/// Asks `NetworkManager` over D-Bus whether the current connection is metered.
+///
+/// This uses `busctl` rather than talking to D-Bus directly, so that
+/// lowfi doesn't need to depend on a D-Bus library just for this.
+#[cfg(target_os = "linux")]
+fn query() -> Option<bool> {
+    use std::process::{Command, Stdio};
+
+    let output = Command::new("busctl")
+        .args([
+            "--system",
+            "get-property",
+            "org.freedesktop.NetworkManager",
+            "/org/freedesktop/NetworkManager",
+            "org.freedesktop.NetworkManager",
+            "Metered",
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    // The output looks like `u 1`, where the number is one of `NetworkManager`'s
+    // `NMMetered` values, of which 1 is "yes" and 3 is "guessed yes".
+    let output = String::from_utf8(output.stdout).ok()?;
+    let value: u32 = output.trim().strip_prefix("u ")?.parse().ok()?;
+
+    Some(matches!(value, 1 | 3))
+}
+
+/// There's no way to tell on other platforms, yet.
+#[cfg(not(target_os = "linux"))]
+const fn query() -> Option<bool> {
+    None
+}
+
+/// Checks whether the connection is metered, which is
+/// assumed not to be the case if it can't be detected.
+pub async fn detect() -> bool {
+    task::spawn_blocking(query)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
@@ -1,18 +1,68 @@
 //! Contains the code for the MPRIS server & other helper functions.
 
-use std::{process, sync::Arc};
+use std::{env, path::PathBuf, process, sync::Arc, time::Duration};
 
+use eyre::eyre;
 use mpris_server::{
     zbus::{self, fdo, Result},
     LoopStatus, Metadata, PlaybackRate, PlaybackStatus, PlayerInterface, Property, RootInterface,
-    Time, TrackId, Volume,
+    Signal, Time, TrackId, Volume,
 };
-use tokio::sync::mpsc::Sender;
+use reqwest::Url;
+use tokio::{fs, sync::mpsc::Sender};
 
 use super::Messages;
+use crate::tracks::tags::Art;
 
 const ERROR: fdo::Error = fdo::Error::Failed(String::new());
 
+/// The object path that MPRIS players, and therefore also [`Extras`], are served at.
+const PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Gets the ID of the track which was the `played`th one to be played this session.
+///
+/// lowfi doesn't have a track list, so this is only really used to make sure
+/// that `SetPosition` is about the track that's actually playing.
+fn track_id_of(played: usize) -> TrackId {
+    TrackId::try_from(format!("/dev/talwat/lowfi/track/{played}").as_str())
+        .unwrap_or(TrackId::NO_TRACK)
+}
+
+/// Converts an MPRIS [`Time`] into a [`Duration`], treating negative times as zero.
+fn duration(time: Time) -> Duration {
+    Duration::from_micros(u64::try_from(time.as_micros()).unwrap_or_default())
+}
+
+/// Gets the folder that album art is written to, so that clients can read it from a `file://` URL.
+///
+/// Each instance of lowfi has its own, so that they don't delete each other's art.
+fn art_dir() -> PathBuf {
+    env::temp_dir().join(format!("lowfi.{}", process::id()))
+}
+
+/// Writes `art` to a file for the `played`th track, and gets the URL of that file.
+///
+/// Each track gets its own file, since clients tend to cache the art by its URL,
+/// and the art of earlier tracks is removed since only the current one is needed.
+async fn art_url(art: &Art, played: usize) -> eyre::Result<String> {
+    let dir = art_dir();
+    let extension = art.media_type.strip_prefix("image/").unwrap_or("img");
+    let path = dir.join(format!("{played}.{extension}"));
+
+    if !fs::try_exists(&path).await? {
+        if fs::try_exists(&dir).await? {
+            fs::remove_dir_all(&dir).await?;
+        }
+
+        fs::create_dir_all(&dir).await?;
+        fs::write(&path, &art.data).await?;
+    }
+
+    Url::from_file_path(&path)
+        .map(String::from)
+        .map_err(|()| eyre!("{} isn't an absolute path", path.display()))
+}
+
 /// The actual MPRIS player.
 pub struct Player {
     /// A reference to the [`super::Player`] itself.
@@ -85,7 +135,10 @@ impl PlayerInterface for Player {
     }
 
     async fn previous(&self) -> fdo::Result<()> {
-        Err(ERROR)
+        self.sender
+            .send(Messages::Previous)
+            .await
+            .map_err(|_error| ERROR)
     }
 
     async fn pause(&self) -> fdo::Result<()> {
@@ -113,12 +166,40 @@ impl PlayerInterface for Player {
             .map_err(|_error| ERROR)
     }
 
-    async fn seek(&self, _offset: Time) -> fdo::Result<()> {
-        Err(ERROR)
+    async fn seek(&self, offset: Time) -> fdo::Result<()> {
+        let snapshot = self.player.snapshot().await;
+        let distance = Duration::from_micros(offset.as_micros().unsigned_abs());
+        let position = if offset.as_micros() < 0 {
+            snapshot.elapsed.saturating_sub(distance)
+        } else {
+            snapshot.elapsed + distance
+        };
+
+        // The spec says that seeking past the end should skip to the next track.
+        let message = if snapshot.duration.is_some_and(|x| position > x) {
+            Messages::Next
+        } else {
+            Messages::SeekTo(position)
+        };
+
+        self.sender.send(message).await.map_err(|_error| ERROR)
     }
 
-    async fn set_position(&self, _track_id: TrackId, _position: Time) -> fdo::Result<()> {
-        Err(ERROR)
+    async fn set_position(&self, track_id: TrackId, position: Time) -> fdo::Result<()> {
+        // The spec says that positions for other tracks, negative positions
+        // & positions past the end of the track should be ignored.
+        let snapshot = self.player.snapshot().await;
+        if track_id != track_id_of(snapshot.played)
+            || position.as_micros() < 0
+            || snapshot.duration.is_some_and(|x| duration(position) > x)
+        {
+            return Ok(());
+        }
+
+        self.sender
+            .send(Messages::SeekTo(duration(position)))
+            .await
+            .map_err(|_error| ERROR)
     }
 
     async fn open_uri(&self, _uri: String) -> fdo::Result<()> {
@@ -126,9 +207,11 @@ impl PlayerInterface for Player {
     }
 
     async fn playback_status(&self) -> fdo::Result<PlaybackStatus> {
-        Ok(if !self.player.current_exists() {
+        let snapshot = self.player.snapshot().await;
+
+        Ok(if snapshot.track.is_none() {
             PlaybackStatus::Stopped
-        } else if self.player.sink.is_paused() {
+        } else if snapshot.paused {
             PlaybackStatus::Paused
         } else {
             PlaybackStatus::Playing
@@ -148,7 +231,7 @@ impl PlayerInterface for Player {
     }
 
     async fn set_rate(&self, rate: PlaybackRate) -> Result<()> {
-        self.player.sink.set_speed(rate as f32);
+        self.player.set_speed(rate as f32);
         Ok(())
     }
 
@@ -161,28 +244,34 @@ impl PlayerInterface for Player {
     }
 
     async fn metadata(&self) -> fdo::Result<Metadata> {
-        let metadata = self
-            .player
-            .current
-            .load()
-            .as_ref()
-            .map_or_else(Metadata::new, |track| {
-                let mut metadata = Metadata::builder().title(track.name.clone()).build();
-
-                metadata.set_length(
-                    track
-                        .duration
-                        .map(|x| Time::from_micros(x.as_micros() as i64)),
-                );
-
-                metadata
-            });
+        let snapshot = self.player.snapshot().await;
+        let Some(name) = snapshot.track else {
+            return Ok(Metadata::new());
+        };
+
+        let mut metadata = Metadata::builder()
+            .trackid(track_id_of(snapshot.played))
+            .title(name)
+            .build();
+        metadata.set_length(
+            snapshot
+                .duration
+                .map(|x| Time::from_micros(x.as_micros() as i64)),
+        );
+        metadata.set_artist(snapshot.artist.map(|x| [x]));
+        metadata.set_album(snapshot.album);
+
+        // Art that can't be written just isn't shown, rather than failing the whole request.
+        let current = self.player.current.load_full();
+        if let Some(art) = current.and_then(|x| x.art.clone()) {
+            metadata.set_art_url(art_url(&art, snapshot.played).await.ok());
+        }
 
         Ok(metadata)
     }
 
     async fn volume(&self) -> fdo::Result<Volume> {
-        Ok(self.player.sink.volume().into())
+        Ok(self.player.snapshot().await.volume.into())
     }
 
     async fn set_volume(&self, volume: Volume) -> Result<()> {
@@ -192,9 +281,8 @@ impl PlayerInterface for Player {
     }
 
     async fn position(&self) -> fdo::Result<Time> {
-        Ok(Time::from_micros(
-            self.player.sink.get_pos().as_micros() as i64
-        ))
+        let elapsed = self.player.snapshot().await.elapsed;
+        Ok(Time::from_micros(elapsed.as_micros() as i64))
     }
 
     async fn minimum_rate(&self) -> fdo::Result<PlaybackRate> {
@@ -210,7 +298,7 @@ impl PlayerInterface for Player {
     }
 
     async fn can_go_previous(&self) -> fdo::Result<bool> {
-        Ok(false)
+        Ok(true)
     }
 
     async fn can_play(&self) -> fdo::Result<bool> {
@@ -222,7 +310,7 @@ impl PlayerInterface for Player {
     }
 
     async fn can_seek(&self) -> fdo::Result<bool> {
-        Ok(false)
+        Ok(true)
     }
 
     async fn can_control(&self) -> fdo::Result<bool> {
@@ -230,6 +318,32 @@ impl PlayerInterface for Player {
     }
 }
 
+/// lowfi's own D-Bus interface, `dev.talwat.lowfi.Extras`, which is served next to
+/// the MPRIS ones and has the things that MPRIS doesn't have a way of expressing.
+pub struct Extras {
+    /// A reference to the [`super::Player`] itself.
+    player: Arc<super::Player>,
+
+    /// The audio server sender, which is used for bookmarking.
+    sender: Sender<Messages>,
+}
+
+#[zbus::interface(name = "dev.talwat.lowfi.Extras")]
+impl Extras {
+    /// Bookmarks the current track, or removes the bookmark if it's already bookmarked.
+    async fn bookmark(&self) -> fdo::Result<()> {
+        self.sender
+            .send(Messages::Bookmark)
+            .await
+            .map_err(|_error| ERROR)
+    }
+
+    /// Gets the names of the tracks which are downloaded & waiting to be played, in order.
+    async fn queue_info(&self) -> Vec<String> {
+        self.player.upcoming().await
+    }
+}
+
 /// A struct which contains the MPRIS [Server], and has some helper functions
 /// to make it easier to work with.
 pub struct Server {
@@ -255,6 +369,15 @@ impl Server {
             .await
     }
 
+    /// Shorthand to emit a `Seeked` signal, which lets clients know that the position jumped.
+    pub async fn seeked(&self, position: Duration) -> zbus::Result<()> {
+        self.inner
+            .emit(Signal::Seeked {
+                position: Time::from_micros(position.as_micros() as i64),
+            })
+            .await
+    }
+
     /// Shorthand to get the inner mpris player object.
     pub fn player(&self) -> &Player {
         self.inner.imp()
@@ -264,8 +387,21 @@ impl Server {
     pub async fn new(player: Arc<super::Player>, sender: Sender<Messages>) -> eyre::Result<Self> {
         let suffix = format!("lowfi.{}.instance{}", player.list.name, process::id());
 
+        let extras = Extras {
+            player: Arc::clone(&player),
+            sender: sender.clone(),
+        };
+
         let server = mpris_server::Server::new(&suffix, Player { player, sender }).await?;
+        server.connection().object_server().at(PATH, extras).await?;
 
         Ok(Self { inner: server })
     }
 }
+
+impl Drop for Server {
+    /// Cleans up the album art, since nothing else will once lowfi has quit.
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(art_dir());
+    }
+}
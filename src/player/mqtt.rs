@@ -0,0 +1,196 @@
+//! Contains the [`Publisher`], which publishes what's playing to an MQTT broker,
+//! so that home automation like Home Assistant can show it & react to it.
+//!
+//! Everything is published under the topic from `config.toml`, which is `lowfi` by default:
+//!
+//! * `lowfi/state` - A [`Snapshot`] as JSON, which is retained & published whenever
+//!   the track, playback, or volume changes.
+//! * `lowfi/availability` - Either `online` or `offline`, which the broker publishes
+//!   by itself if lowfi quits or loses its connection.
+
+use std::{process, sync::Arc, time::Duration};
+
+use rumqttc::{AsyncClient, Event, EventLoop, LastWill, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+use tokio::{join, time::sleep};
+
+use super::{snapshot::Snapshot, Player};
+
+/// How long to wait before reconnecting after losing the connection to the broker.
+const RECONNECT: Duration = Duration::from_secs(10);
+
+/// How many messages can be waiting to be sent to the broker at once.
+const CAPACITY: usize = 8;
+
+/// The port that MQTT brokers usually listen on, without TLS.
+const fn port() -> u16 {
+    1883
+}
+
+/// The topic that everything is published under, if there isn't one in `config.toml`.
+fn topic() -> String {
+    "lowfi".to_owned()
+}
+
+/// The `[mqtt]` section of `config.toml`.
+#[derive(Clone, Deserialize)]
+pub struct Settings {
+    /// The address of the broker, like `homeassistant.local`.
+    pub host: String,
+
+    /// The port of the broker.
+    #[serde(default = "port")]
+    pub port: u16,
+
+    /// The topic that everything is published under.
+    #[serde(default = "topic")]
+    pub topic: String,
+
+    /// The username to log in to the broker with, if it needs one.
+    pub username: Option<String>,
+
+    /// The password to log in to the broker with, if it needs one.
+    pub password: Option<String>,
+}
+
+/// The parts of a [`Snapshot`] which are worth publishing a new state for.
+///
+/// The elapsed time is left out, since it changes all the time.
+#[derive(PartialEq)]
+struct Key {
+    /// The display name of the current track.
+    track: Option<String>,
+
+    /// How many tracks have been played, which changes when the same track is replayed.
+    played: usize,
+
+    /// Whether playback is paused.
+    paused: bool,
+
+    /// The volume, as a percentage.
+    volume: u8,
+}
+
+impl From<&Snapshot> for Key {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            track: snapshot.track.clone(),
+            played: snapshot.played,
+            paused: snapshot.paused,
+            volume: (snapshot.volume * 100.0).round() as u8,
+        }
+    }
+}
+
+/// Publishes the state of the player to an MQTT broker.
+pub struct Publisher {
+    /// Used to queue up messages for the broker.
+    client: AsyncClient,
+
+    /// What actually talks to the broker, which has to be polled for anything to be sent.
+    events: EventLoop,
+
+    /// The topic that everything is published under.
+    topic: String,
+}
+
+impl Publisher {
+    /// Sets up the connection to the broker from `settings`, which is only
+    /// actually made once the [`Publisher`] starts running.
+    pub fn new(settings: Settings) -> Self {
+        let availability = format!("{}/availability", settings.topic);
+
+        let mut options = MqttOptions::new(
+            format!("lowfi-{}", process::id()),
+            settings.host,
+            settings.port,
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+        options.set_last_will(LastWill::new(
+            availability,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let (Some(username), Some(password)) = (settings.username, settings.password) {
+            options.set_credentials(username, password);
+        }
+
+        let (client, events) = AsyncClient::new(options, CAPACITY);
+
+        Self {
+            client,
+            events,
+            topic: settings.topic,
+        }
+    }
+
+    /// Keeps the connection to the broker going, reconnecting whenever it's lost.
+    ///
+    /// `online` is published each time it connects, since the broker
+    /// might've published the last will in the meantime.
+    // This runs for as long as lowfi does, and is aborted when it quits.
+    #[allow(clippy::infinite_loop)]
+    async fn connect(player: &Player, client: &AsyncClient, mut events: EventLoop, topic: &str) {
+        let availability = format!("{topic}/availability");
+
+        loop {
+            match events.poll().await {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    // This can't wait, since the messages are only sent by polling.
+                    let online =
+                        client.try_publish(&availability, QoS::AtLeastOnce, true, "online");
+                    if let Err(error) = online {
+                        player.alert(format!("couldn't publish to MQTT: {error}"));
+                    }
+                }
+                Ok(_) => (),
+                Err(error) => {
+                    player.alert(format!("lost connection to the MQTT broker: {error}"));
+                    sleep(RECONNECT).await;
+                }
+            }
+        }
+    }
+
+    /// Publishes the state of the player whenever something changes, until the task is aborted.
+    ///
+    /// Any errors are shown in the window, rather than stopping lowfi.
+    pub async fn run(self, player: Arc<Player>) {
+        let Self {
+            client,
+            events,
+            topic,
+        } = self;
+
+        let publish = async {
+            let state = format!("{topic}/state");
+            let mut snapshots = player.snapshots.subscribe();
+            let mut last = None;
+
+            while snapshots.changed().await.is_ok() {
+                let snapshot = snapshots.borrow_and_update().clone();
+                let key = Some(Key::from(&snapshot));
+                if key == last {
+                    continue;
+                }
+
+                last = key;
+
+                let result = match serde_json::to_vec(&snapshot) {
+                    Ok(payload) => client
+                        .publish(&state, QoS::AtLeastOnce, true, payload)
+                        .await
+                        .map_err(|x| x.to_string()),
+                    Err(error) => Err(error.to_string()),
+                };
+
+                if let Err(error) = result {
+                    player.alert(format!("couldn't publish to MQTT: {error}"));
+                }
+            }
+        };
+
+        join!(Self::connect(&player, &client, events, &topic), publish);
+    }
+}
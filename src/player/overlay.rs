@@ -0,0 +1,88 @@
+//! Contains the [`Overlay`] for `--now-playing-file`, which writes what's playing to a file
+//! whenever a new track starts, so that OBS text sources can show it on stream.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use eyre::bail;
+use serde_json::json;
+
+use super::Player;
+use crate::{play, tracks::Info};
+
+/// Writes the current track to a text file, with all of its metadata in a JSON file next to it.
+pub struct Overlay {
+    /// Where the `artist – title` line is written.
+    text: PathBuf,
+
+    /// Where the metadata is written, which is `text` with a `.json` extension.
+    json: PathBuf,
+}
+
+impl Overlay {
+    /// Makes sure that the JSON file won't overwrite the text file at `path`,
+    /// so that mistakes are caught before lowfi starts playing.
+    pub fn new(path: &Path) -> eyre::Result<Self> {
+        let json = path.with_extension("json");
+        if json == path {
+            bail!("--now-playing-file can't end with .json, since that's used for the metadata");
+        }
+
+        Ok(Self {
+            text: path.to_owned(),
+            json,
+        })
+    }
+
+    /// Writes both files for `info`, which is playing from the list called `list`.
+    async fn write(&self, info: &Info, list: &str) -> eyre::Result<()> {
+        let text = info.artist.as_ref().map_or_else(
+            || info.name.clone(),
+            |artist| format!("{artist} – {}", info.name),
+        );
+
+        let metadata = json!({
+            "title": info.name,
+            "artist": info.artist,
+            "album": info.album,
+            "source": info.source,
+            "codec": info.codec,
+            "duration": info.duration.map(|x| x.as_secs_f64()),
+            "path": info.full_path,
+            "list": list,
+        });
+
+        play::write(&self.text, text).await?;
+        play::write(&self.json, serde_json::to_string_pretty(&metadata)?).await?;
+
+        Ok(())
+    }
+
+    /// Writes the files each time a new track starts, until the task is aborted.
+    ///
+    /// Any errors are shown in the window, rather than stopping lowfi.
+    pub async fn run(self, player: Arc<Player>) {
+        let mut snapshots = player.snapshots.subscribe();
+
+        // Replaying a track makes a new `Info`, so it's written again.
+        let mut playing: Option<Arc<Info>> = None;
+
+        while snapshots.changed().await.is_ok() {
+            let Some(current) = player.current.load_full() else {
+                continue;
+            };
+
+            if playing.as_ref().is_some_and(|x| Arc::ptr_eq(x, &current)) {
+                continue;
+            }
+
+            if let Err(error) = self.write(&current, &player.list.name).await {
+                player.alert(format!("couldn't write the now playing file: {error}"));
+            }
+
+            playing = Some(current);
+        }
+    }
+}
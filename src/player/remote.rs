@@ -0,0 +1,54 @@
+//! Contains the requests that other programs can make to control lowfi while it's running,
+//! which are shared by `lowfi ctl` and the HTTP API.
+//!
+//! A request is a name, like `next`, followed by an argument if it takes one, like `volume +10`.
+
+use eyre::{bail, eyre};
+use tokio::sync::mpsc::Sender;
+
+use super::{Messages, Player};
+
+/// Gets the message for a `volume` request, where `change` is in percent.
+///
+/// The change is relative if it starts with `+` or `-`, like `+10`, and otherwise sets the volume.
+fn volume(change: &str) -> eyre::Result<Messages> {
+    let percent: f32 = change
+        .parse()
+        .ok()
+        .filter(|x: &f32| x.is_finite())
+        .ok_or_else(|| eyre!("{change:?} isn't a volume"))?;
+
+    Ok(if change.starts_with(['+', '-']) {
+        Messages::ChangeVolume(percent / 100.0)
+    } else {
+        Messages::SetVolume(percent / 100.0)
+    })
+}
+
+/// Works out the response to a single `request`, sending any messages to `sender`.
+///
+/// The response is `ok`, unless the request asks a question, like `now-playing`.
+pub async fn respond(
+    player: &Player,
+    sender: &Sender<Messages>,
+    request: &str,
+) -> eyre::Result<String> {
+    let (command, argument) = request.split_once(' ').unwrap_or((request, ""));
+    let message = match command {
+        "now-playing" => return Ok(serde_json::to_string(&player.snapshot().await)?),
+        "bookmarks" => return Ok(serde_json::to_string(&player.bookmarks.paths().await)?),
+        "next" => Messages::Next,
+        "previous" => Messages::Previous,
+        "play" => Messages::Play,
+        "pause" => Messages::Pause,
+        "play-pause" => Messages::PlayPause,
+        "bookmark" => Messages::Bookmark,
+        "quit" => Messages::Quit,
+        "volume" => volume(argument.trim())?,
+        _ => bail!("unknown request {command:?}"),
+    };
+
+    sender.send(message).await?;
+
+    Ok("ok".to_owned())
+}
@@ -0,0 +1,85 @@
+//! Contains the [`Policy`] for retrying failed downloads,
+//! as well as the [`Status`] of a retry & the last [`Failure`], which the UI can show.
+
+use std::time::{Duration, Instant};
+
+/// The longest that lowfi will ever wait in between retries.
+const MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a [`Failure`] is shown in the window for.
+const FAILURE_DURATION: Duration = Duration::from_secs(4);
+
+/// How failed downloads are retried, specified with `--retry-delay` & `--max-retries`.
+#[derive(Clone, Copy)]
+pub struct Policy {
+    /// How long to wait before the first retry, which doubles with each attempt.
+    delay: Duration,
+
+    /// The maximum amount of retries in a row, or [`None`] to retry forever.
+    pub max: Option<u32>,
+}
+
+impl Policy {
+    /// Creates a new [`Policy`], where `delay` is in seconds.
+    pub const fn new(delay: u64, max: Option<u32>) -> Self {
+        Self {
+            delay: Duration::from_secs(delay),
+            max,
+        }
+    }
+
+    /// Gets how long to wait before retry number `attempt`, starting from 1.
+    pub fn delay(self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.delay.saturating_mul(factor).min(MAX_DELAY)
+    }
+
+    /// Whether retry number `attempt` is one too many.
+    pub fn exhausted(self, attempt: u32) -> bool {
+        self.max.is_some_and(|max| attempt > max)
+    }
+}
+
+/// Information about the retry that's currently happening, for the UI.
+pub struct Status {
+    /// Which retry this is, starting from 1.
+    pub attempt: u32,
+
+    /// The maximum amount of retries, if there is one.
+    pub max: Option<u32>,
+
+    /// When the retry will actually start.
+    pub at: Instant,
+}
+
+impl Status {
+    /// How long it is until the retry starts, which is zero once it's started.
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Something which went wrong without stopping lowfi, like a track which couldn't
+/// be downloaded, that's shown in the window so it doesn't just fail silently.
+pub struct Failure {
+    /// What went wrong, like `failed to fetch Track, retrying...`.
+    pub message: String,
+
+    /// When it went wrong.
+    at: Instant,
+}
+
+impl Failure {
+    /// Creates a new [`Failure`] which happened just now.
+    pub fn new(message: String) -> Self {
+        Self {
+            message,
+            at: Instant::now(),
+        }
+    }
+
+    /// Whether the failure is recent enough to still be shown, which is for [`FAILURE_DURATION`].
+    pub fn shown(&self) -> bool {
+        self.at.elapsed() < FAILURE_DURATION
+    }
+}
@@ -0,0 +1,72 @@
+//! Contains the [`Saver`] struct.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::{
+    sync::mpsc::{self, Receiver, Sender},
+    task::{self, JoinHandle},
+    time::timeout,
+};
+
+use super::Player;
+use crate::play::PersistentVolume;
+
+/// How long to wait after a change before saving, so that a burst
+/// of changes, like holding down a volume key, is only saved once.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// This struct is responsible for saving the bookmarks & volume in the
+/// background whenever they change, so that they aren't lost if lowfi crashes.
+///
+/// Everything is still saved again when lowfi quits.
+pub struct Saver {
+    /// The player which has the bookmarks & volume to save.
+    player: Arc<Player>,
+
+    /// The internal reciever, which is used by the saver to know
+    /// when something has changed.
+    rx: Receiver<()>,
+
+    /// A copy of the internal sender, which can be useful for keeping
+    /// track of it.
+    tx: Sender<()>,
+}
+
+impl Saver {
+    /// Uses a sender recieved from [`Saver::start`] to let the
+    /// saver know that something has changed.
+    ///
+    /// This never waits, since if the channel is full then a save is already coming up.
+    pub fn notify(sender: &Sender<()>) {
+        let _ = sender.try_send(());
+    }
+
+    /// Initializes the [Saver].
+    pub fn new(player: Arc<Player>) -> Self {
+        let (tx, rx) = mpsc::channel(1);
+        Self { player, rx, tx }
+    }
+
+    /// Saves the volume & bookmarks.
+    async fn save(player: &Player) -> eyre::Result<()> {
+        PersistentVolume::save(player.sink.volume()).await?;
+        player.bookmarks.save().await
+    }
+
+    /// Actually starts & consumes the [Saver].
+    pub fn start(mut self) -> (Sender<()>, JoinHandle<()>) {
+        (
+            self.tx,
+            task::spawn(async move {
+                while self.rx.recv().await == Some(()) {
+                    // Keep waiting for as long as more changes are coming in.
+                    while timeout(DEBOUNCE, self.rx.recv()).await == Ok(Some(())) {}
+
+                    // A failed save isn't worth stopping playback over,
+                    // especially since it'll be tried again on quit.
+                    let _ = Self::save(&self.player).await;
+                }
+            }),
+        )
+    }
+}
@@ -0,0 +1,420 @@
+//! Contains the [`Scrobbler`], which lets Last.fm & ListenBrainz know what's being
+//! listened to, as well as the `scrobble` command which logs lowfi in to Last.fm.
+
+use std::{
+    collections::BTreeMap,
+    io::stdin,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{bail, eyre};
+use reqwest::{
+    header::{AUTHORIZATION, CONTENT_TYPE},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{fs, io::AsyncWriteExt, join, task};
+
+use super::{Player, TIMEOUT};
+use crate::{config, tracks::Info, ScrobbleCommands};
+
+/// Last.fm's API, which every Last.fm request is sent to.
+const LASTFM: &str = "https://ws.audioscrobbler.com/2.0/";
+
+/// Where listens are submitted to ListenBrainz.
+const LISTENBRAINZ: &str = "https://api.listenbrainz.org/1/submit-listens";
+
+/// The page where users let lowfi scrobble to their account.
+const AUTH: &str = "https://www.last.fm/api/auth/";
+
+/// Tracks shorter than this aren't scrobbled, which is one of Last.fm's rules.
+const MIN_DURATION: Duration = Duration::from_secs(30);
+
+/// Tracks are scrobbled once they've played for half of their duration,
+/// or for this long if that comes first, which is also one of Last.fm's rules.
+const MAX_WAIT: Duration = Duration::from_secs(4 * 60);
+
+/// Gets the current time as a UNIX timestamp, which is how Last.fm wants it.
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs())
+}
+
+/// Signs `params` with `secret`, which Last.fm does by hashing every
+/// parameter sorted by name, followed by the secret, with MD5.
+fn sign(params: &BTreeMap<&str, String>, secret: &str) -> String {
+    let mut text = String::new();
+    for (name, value) in params {
+        text.push_str(name);
+        text.push_str(value);
+    }
+    text.push_str(secret);
+
+    format!("{:x}", md5::compute(text))
+}
+
+/// Calls `method` with `params` using the API account with `api_key` & `secret`.
+async fn call(
+    client: &Client,
+    method: &str,
+    mut params: BTreeMap<&str, String>,
+    api_key: &str,
+    secret: &str,
+) -> eyre::Result<Value> {
+    params.insert("method", method.to_owned());
+    params.insert("api_key", api_key.to_owned());
+    let signature = sign(&params, secret);
+    params.insert("api_sig", signature);
+
+    // This is added after signing, since Last.fm leaves it out of the signature.
+    params.insert("format", "json".to_owned());
+
+    let text = client
+        .post(LASTFM)
+        .form(&params)
+        .send()
+        .await?
+        .text()
+        .await?;
+    let response: Value = serde_json::from_str(&text)?;
+    if response.get("error").is_some() {
+        let message = response["message"].as_str().unwrap_or("unknown error");
+        bail!("Last.fm couldn't {method}: {message}");
+    }
+
+    Ok(response)
+}
+
+/// The Last.fm API account & session that lowfi scrobbles with.
+///
+/// This is saved in `lastfm.toml` in the config directory once the user logs in.
+/// Last.fm API accounts are free, so everyone uses their own rather than sharing one.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    /// The API key of the user's API account.
+    api_key: String,
+
+    /// The shared secret of the user's API account, which requests are signed with.
+    secret: String,
+
+    /// The session key, which lets lowfi scrobble to the user's account.
+    key: String,
+
+    /// The name of the user's account.
+    user: String,
+}
+
+impl Session {
+    /// Retrieves the path to `lastfm.toml`.
+    async fn path() -> eyre::Result<PathBuf> {
+        Ok(config::dir().await?.join("lastfm.toml"))
+    }
+
+    /// Loads the [`Session`], or [`None`] if the user hasn't logged in.
+    async fn load() -> eyre::Result<Option<Self>> {
+        let path = Self::path().await?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let session = toml::from_str(&fs::read_to_string(path).await?)
+            .map_err(|error| eyre!("lastfm.toml is invalid: {error}"))?;
+
+        Ok(Some(session))
+    }
+
+    /// Saves the [`Session`] to `lastfm.toml`.
+    ///
+    /// Since it has the user's secret & session key in it, only the user can read it on unix.
+    async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path().await?;
+        let temporary = path.with_extension("part");
+
+        // The permissions are only set when creating the file, so a leftover one can't be reused.
+        if temporary.exists() {
+            fs::remove_file(&temporary).await?;
+        }
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&temporary).await?;
+        file.write_all(toml::to_string(self)?.as_bytes()).await?;
+        file.sync_all().await?;
+        drop(file);
+
+        fs::rename(temporary, path).await?;
+
+        Ok(())
+    }
+
+    /// Calls `method` with `params` as the user.
+    async fn call(
+        &self,
+        client: &Client,
+        method: &str,
+        mut params: BTreeMap<&str, String>,
+    ) -> eyre::Result<Value> {
+        params.insert("sk", self.key.clone());
+        call(client, method, params, &self.api_key, &self.secret).await
+    }
+}
+
+/// Lets Last.fm & ListenBrainz know what's playing, and submits each track once it's been listened to.
+///
+/// This is only started if the user has logged in with `lowfi scrobble login`,
+/// or has set `listenbrainz_token` in `config.toml`.
+pub struct Scrobbler {
+    /// The Last.fm session to scrobble with, if the user has logged in.
+    lastfm: Option<Session>,
+
+    /// The ListenBrainz user token to submit listens with, if the user has one.
+    listenbrainz: Option<String>,
+
+    /// The client that requests are sent with.
+    client: Client,
+}
+
+impl Scrobbler {
+    /// Loads the [`Scrobbler`], which submits to ListenBrainz with the `listenbrainz` token if there is one.
+    ///
+    /// This is [`None`] if there's neither a token nor a Last.fm session.
+    pub async fn load(listenbrainz: Option<String>) -> eyre::Result<Option<Self>> {
+        let lastfm = Session::load().await?;
+        if lastfm.is_none() && listenbrainz.is_none() {
+            return Ok(None);
+        }
+
+        Ok(Some(Self {
+            lastfm,
+            listenbrainz,
+            client: Client::builder().timeout(TIMEOUT).build()?,
+        }))
+    }
+
+    /// Gets the Last.fm parameters which describe a track by `artist`, from `info`.
+    fn params(artist: &str, info: &Info) -> BTreeMap<&'static str, String> {
+        let mut params =
+            BTreeMap::from([("artist", artist.to_owned()), ("track", info.name.clone())]);
+
+        if let Some(album) = &info.album {
+            params.insert("album", album.clone());
+        }
+
+        if let Some(duration) = info.duration {
+            params.insert("duration", duration.as_secs().to_string());
+        }
+
+        params
+    }
+
+    /// Gets the ListenBrainz listen for a track by `artist`, from `info`, which was played from the `list`.
+    fn listen(artist: &str, info: &Info, list: &str) -> Value {
+        let mut additional = json!({
+            "submission_client": "lowfi",
+            "submission_client_version": env!("CARGO_PKG_VERSION"),
+            "lowfi_list": list,
+        });
+
+        if let Some(duration) = info.duration {
+            additional["duration_ms"] = u64::try_from(duration.as_millis())
+                .unwrap_or(u64::MAX)
+                .into();
+        }
+
+        let mut metadata = json!({
+            "artist_name": artist,
+            "track_name": info.name,
+            "additional_info": additional,
+        });
+
+        if let Some(album) = &info.album {
+            metadata["release_name"] = album.as_str().into();
+        }
+
+        json!({ "track_metadata": metadata })
+    }
+
+    /// Calls the Last.fm `method` with `params`, if the user has logged in.
+    async fn lastfm(&self, method: &str, params: BTreeMap<&str, String>) -> eyre::Result<()> {
+        if let Some(session) = &self.lastfm {
+            session.call(&self.client, method, params).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Submits `listen` to ListenBrainz as `kind`, if the user has a token.
+    async fn listenbrainz(&self, kind: &str, listen: Value) -> eyre::Result<()> {
+        let Some(token) = &self.listenbrainz else {
+            return Ok(());
+        };
+
+        let body = json!({ "listen_type": kind, "payload": [listen] });
+        let response = self
+            .client
+            .post(LISTENBRAINZ)
+            .header(AUTHORIZATION, format!("Token {token}"))
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let text = response.text().await?;
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|x| x["error"].as_str().map(str::to_owned))
+                .unwrap_or(text);
+
+            bail!("ListenBrainz couldn't take the listen: {message}");
+        }
+
+        Ok(())
+    }
+
+    /// Lets both services know that a track by `artist` just started playing from the `list`.
+    async fn now_playing(&self, artist: &str, info: &Info, list: &str) -> eyre::Result<()> {
+        let (lastfm, listenbrainz) = join!(
+            self.lastfm("track.updateNowPlaying", Self::params(artist, info)),
+            self.listenbrainz("playing_now", Self::listen(artist, info, list)),
+        );
+
+        lastfm.and(listenbrainz)
+    }
+
+    /// Submits a track by `artist` from the `list` to both services,
+    /// which started playing at the `started` timestamp.
+    async fn scrobble(
+        &self,
+        artist: &str,
+        info: &Info,
+        list: &str,
+        started: u64,
+    ) -> eyre::Result<()> {
+        let mut params = Self::params(artist, info);
+        params.insert("timestamp", started.to_string());
+
+        let mut listen = Self::listen(artist, info, list);
+        listen["listened_at"] = started.into();
+
+        let (lastfm, listenbrainz) = join!(
+            self.lastfm("track.scrobble", params),
+            self.listenbrainz("single", listen),
+        );
+
+        lastfm.and(listenbrainz)
+    }
+
+    /// Watches what `player` is playing, until the task is aborted.
+    ///
+    /// Tracks without an artist in their tags can't be scrobbled, so they're left out.
+    /// Any errors are shown in the window, rather than stopping lowfi.
+    pub async fn run(self, player: Arc<Player>) {
+        let mut snapshots = player.snapshots.subscribe();
+        let list = &player.list.name;
+
+        // The track that's playing, when it started & whether it's been scrobbled yet.
+        let mut playing: Option<(Arc<Info>, u64, bool)> = None;
+
+        while snapshots.changed().await.is_ok() {
+            let Some(current) = player.current.load_full() else {
+                continue;
+            };
+
+            let Some(artist) = current.artist.as_deref() else {
+                continue;
+            };
+
+            // Replaying a track makes a new `Info`, so it counts as a new listen.
+            if !playing
+                .as_ref()
+                .is_some_and(|x| Arc::ptr_eq(&x.0, &current))
+            {
+                playing = Some((Arc::clone(&current), now(), false));
+
+                if let Err(error) = self.now_playing(artist, &current, list).await {
+                    player.alert(error.to_string());
+                }
+            }
+
+            let Some((_, started, scrobbled)) = &mut playing else {
+                continue;
+            };
+
+            let Some(duration) = current.duration.filter(|x| *x >= MIN_DURATION) else {
+                continue;
+            };
+
+            if !*scrobbled && player.elapsed() >= (duration / 2).min(MAX_WAIT) {
+                *scrobbled = true;
+
+                if let Err(error) = self.scrobble(artist, &current, list, *started).await {
+                    player.alert(error.to_string());
+                }
+            }
+        }
+    }
+}
+
+/// Logs in to Last.fm with the API account that has `api_key` & `secret`.
+///
+/// The user has to let lowfi scrobble to their account in their browser,
+/// after which the session is saved so that they only have to do this once.
+async fn login(api_key: String, secret: String) -> eyre::Result<()> {
+    let client = Client::builder().timeout(TIMEOUT).build()?;
+
+    let response = call(&client, "auth.getToken", BTreeMap::new(), &api_key, &secret).await?;
+    let token = response["token"]
+        .as_str()
+        .ok_or_else(|| eyre!("Last.fm didn't send a token"))?
+        .to_owned();
+
+    println!("open this link to let lowfi scrobble to your account, and then press enter:");
+    println!("{AUTH}?api_key={api_key}&token={token}");
+    task::spawn_blocking(|| stdin().read_line(&mut String::new())).await??;
+
+    let params = BTreeMap::from([("token", token)]);
+    let response = call(&client, "auth.getSession", params, &api_key, &secret).await?;
+    let (Some(key), Some(user)) = (
+        response["session"]["key"].as_str(),
+        response["session"]["name"].as_str(),
+    ) else {
+        bail!("Last.fm didn't send a session");
+    };
+
+    let session = Session {
+        api_key,
+        secret,
+        key: key.to_owned(),
+        user: user.to_owned(),
+    };
+    session.save().await?;
+
+    println!("scrobbling to {}", session.user);
+
+    Ok(())
+}
+
+/// Runs one of the `scrobble` subcommands.
+pub async fn command(command: ScrobbleCommands) -> eyre::Result<()> {
+    match command {
+        ScrobbleCommands::Login { api_key, secret } => login(api_key, secret).await?,
+        ScrobbleCommands::Logout => {
+            let path = Session::path().await?;
+            if path.exists() {
+                fs::remove_file(path).await?;
+            }
+
+            println!("logged out of Last.fm");
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Contains the [`Snapshot`], which is the state of the player at a single moment.
+//!
+//! Anything outside of the player itself which wants to show what's playing,
+//! like MPRIS or the title line, should take one with [`Player::snapshot`]
+//! instead of reading the [`rodio::Sink`] & the current track separately.
+//!
+//! Anything which reacts to changes, like the hooks or the scrobbler, should
+//! wait on [`Player::snapshots`] instead of checking on the player by itself.
+//!
+//! [`Player::snapshot`]: super::Player::snapshot
+//! [`Player::snapshots`]: super::Player::snapshots
+
+use std::{sync::Arc, time::Duration};
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use tokio::time::interval;
+
+use super::Player;
+
+/// How often a new [`Snapshot`] is taken, to tell when something changes.
+const POLL: Duration = Duration::from_millis(250);
+
+/// Serializes a [`Duration`] as a number of seconds, which is easier to work with than
+/// the seconds & nanoseconds that [`Duration`] would be serialized as otherwise.
+fn seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+/// Like [`seconds`], but for a [`Duration`] which might be unknown.
+// Serde's `serialize_with` always passes a reference to the field.
+#[allow(clippy::ref_option)]
+fn optional_seconds<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => seconds(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The opposite of [`seconds`], for reading a [`Snapshot`] back, like `lowfi now-playing` does.
+fn from_seconds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+    Duration::try_from_secs_f64(f64::deserialize(deserializer)?).map_err(D::Error::custom)
+}
+
+/// Like [`from_seconds`], but for a [`Duration`] which might be unknown.
+fn from_optional_seconds<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(|x| Duration::try_from_secs_f64(x).map_err(D::Error::custom))
+        .transpose()
+}
+
+/// The state of the player at a single moment, which can be serialized as is.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// The display name of the current track, or [`None`] while it's loading.
+    pub track: Option<String>,
+
+    /// The artist of the current track, if its list or tags have one.
+    pub artist: Option<String>,
+
+    /// The album of the current track, if its list or tags have one.
+    pub album: Option<String>,
+
+    /// Where the current track came from, if its list says.
+    #[serde(default)]
+    pub source: Option<String>,
+
+    /// The codec of the current track, like `MP3`, if it's known.
+    #[serde(default)]
+    pub codec: Option<String>,
+
+    /// How long the current track has played for.
+    #[serde(serialize_with = "seconds", deserialize_with = "from_seconds")]
+    pub elapsed: Duration,
+
+    /// How long the current track is, if that's known.
+    #[serde(
+        serialize_with = "optional_seconds",
+        deserialize_with = "from_optional_seconds"
+    )]
+    pub duration: Option<Duration>,
+
+    /// The volume, from 0 to 1.
+    pub volume: f32,
+
+    /// Whether playback is paused.
+    pub paused: bool,
+
+    /// The name of the track list.
+    pub list: String,
+
+    /// How many tracks are downloaded and waiting to be played.
+    pub queued: usize,
+
+    /// How many tracks have been played during this session.
+    pub played: usize,
+}
+
+/// Takes a new [`Snapshot`] every [`POLL`], and sends it to `player.snapshots`
+/// if it's different from the last one, until the task is aborted.
+///
+/// While playback is paused, nothing changes, so nothing is sent either.
+// This runs for as long as lowfi does, and is aborted when it quits.
+#[allow(clippy::infinite_loop)]
+pub async fn publish(player: Arc<Player>) {
+    let mut ticks = interval(POLL);
+
+    loop {
+        ticks.tick().await;
+
+        let next = player.snapshot().await;
+        player.snapshots.send_if_modified(|snapshot| {
+            let changed = *snapshot != next;
+            if changed {
+                *snapshot = next;
+            }
+
+            changed
+        });
+    }
+}
@@ -4,41 +4,74 @@ use std::{
     fmt::Write,
     io::{stdout, Stdout},
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering},
         Arc,
     },
     time::Duration,
 };
 
-use crate::Args;
+use crate::{config::Config, tracks::Info, Args};
 
 use crossterm::{
-    cursor::{Hide, MoveTo, MoveToColumn, MoveUp, Show},
-    event::{KeyboardEnhancementFlags, PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags},
+    cursor::{MoveTo, MoveToColumn, MoveUp},
     style::{Print, Stylize},
-    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    terminal::{self, Clear, ClearType},
 };
 
+use clap::ValueEnum;
 use lazy_static::lazy_static;
-use tokio::{sync::mpsc::Sender, task, time::sleep};
+use serde::Deserialize;
+use tokio::{
+    select,
+    sync::{mpsc::Sender, watch, Notify},
+    task,
+    time::sleep,
+};
 
-use super::{Messages, Player};
+use super::{visualizer::Visualizer, Messages, Player};
+use environment::{Environment, CAPABILITIES};
+use format::Locale;
+use theme::{Preset, Theme};
+use title::Template;
 
+mod art;
 mod components;
+pub mod environment;
+pub mod format;
 mod input;
+pub mod status;
+pub mod theme;
+pub mod title;
 
 /// Self explanitory.
 const FPS: usize = 12;
 
+/// The narrowest that the window's contents can be, which is also the width with `--width 0`.
+const MIN_WIDTH: usize = 21;
+
+/// How long the borders are highlighted for when a new track starts, with [`Cue::Flash`].
+/// This is in frames.
+const FLASH_DURATION: usize = 4;
+
 /// How long the audio bar will be visible for when audio is adjusted.
 /// This is in frames.
 const AUDIO_BAR_DURATION: usize = 10;
 
+/// How many frames a scrolling track name stays on each character, with `--marquee`.
+const MARQUEE_DELAY: usize = 3;
+
+/// How much the bars of the visualizer can fall each frame, so that they don't flicker.
+const VISUALIZER_FALL: f32 = 0.12;
+
 /// How long to wait in between frames.
 /// This is fairly arbitrary, but an ideal value should be enough to feel
 /// snappy but not require too many resources.
 const FRAME_DELTA: f32 = 1.0 / FPS as f32;
 
+/// The longest the window goes without being redrawn while nothing is moving,
+/// which is just often enough to keep the progress bar ticking along.
+const IDLE_DELTA: Duration = Duration::from_secs(1);
+
 lazy_static! {
     /// The volume timer, which controls how long the volume display should
     /// show up and when it should disappear.
@@ -46,6 +79,241 @@ lazy_static! {
     /// When this is 0, it means that the audio bar shouldn't be displayed.
     /// To make it start counting, you need to set it to 1.
     static ref VOLUME_TIMER: AtomicUsize = AtomicUsize::new(0);
+
+    /// Whether the bottom control bar is hidden.
+    ///
+    /// This and [`BORDERLESS`] are kept here rather than passed to [`interface`],
+    /// so that they can be toggled by the input listener, and so that the
+    /// [`Config`] can be read back and saved when lowfi quits.
+    static ref MINIMALIST: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the window is drawn without borders.
+    static ref BORDERLESS: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the progress bar shows the time that's left, rather than the time that's passed.
+    static ref REMAINING: AtomicBool = AtomicBool::new(false);
+
+    /// Whether there's a title line at the top of the window, from `--title`,
+    /// which moves everything else down a row.
+    static ref TITLED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the artist line is shown under the track name,
+    /// which moves the progress bar & controls down a row.
+    static ref ARTIST: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the audio bar is being dragged with the mouse, which keeps it on screen.
+    static ref DRAGGING: AtomicBool = AtomicBool::new(false);
+
+    /// Whether a failure is being shown where the controls usually are, so they can't be clicked.
+    static ref FAILING: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the history panel is open.
+    static ref HISTORY: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the panel showing the upcoming tracks is open.
+    static ref UPCOMING: AtomicBool = AtomicBool::new(false);
+
+    /// The index of the selected track in the history panel.
+    ///
+    /// This can go past the end of the history, in which
+    /// case it's brought back the next time it's drawn.
+    static ref SELECTED: AtomicUsize = AtomicUsize::new(0);
+
+    /// The row of the terminal that the top of the window is on,
+    /// which is needed to work out what the mouse is clicking on.
+    static ref ORIGIN: AtomicU16 = AtomicU16::new(0);
+
+    /// The width of the window's contents, which changes along
+    /// with the size of the terminal if `--auto-width` is set.
+    static ref WIDTH: AtomicUsize = AtomicUsize::new(0);
+
+    /// Whether the terminal was resized since the last frame, in which case
+    /// the whole screen has to be cleared, since the terminal will have
+    /// rearranged whatever was drawn before.
+    static ref RESIZED: AtomicBool = AtomicBool::new(false);
+
+    /// Whether the terminal is too small for the window, in which case a
+    /// placeholder is drawn instead, so there's nothing to click on.
+    static ref CRAMPED: AtomicBool = AtomicBool::new(false);
+
+    /// Woken up whenever something changes that the window shows, see [`redraw`].
+    static ref REDRAW: Notify = Notify::new();
+}
+
+/// Lets the interface know that something it shows has changed, so it should be redrawn.
+///
+/// While nothing is animating, the window is only redrawn when this is called,
+/// or once every [`IDLE_DELTA`] to keep the progress bar up to date.
+pub fn redraw() {
+    REDRAW.notify_one();
+}
+
+/// A cue which lets the user know that a new track has started,
+/// even if the terminal isn't visible.
+#[derive(Clone, Copy, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cue {
+    /// Rings the terminal bell, which many terminals show as a notification.
+    Bell,
+
+    /// Briefly highlights the window's borders.
+    Flash,
+}
+
+/// Gets the width of the window's contents which fills up `columns`, for `--auto-width`.
+///
+/// This leaves room for the borders even if the window is borderless,
+/// so that toggling them doesn't make the window wrap around.
+fn fit(columns: u16) -> usize {
+    usize::from(columns).saturating_sub(4).max(MIN_WIDTH)
+}
+
+/// The preferences from `config.toml` which the interface is drawn with,
+/// which can change while lowfi is running if the file is edited.
+#[derive(Clone)]
+struct Preferences {
+    /// The colors & styles to draw with.
+    theme: Theme,
+
+    /// What to do when a new track starts, if anything.
+    cue: Option<Cue>,
+
+    /// Whether to scroll track names which don't fit.
+    marquee: bool,
+
+    /// The template of the title line at the top of the window, if there is one.
+    title: Option<Arc<Template>>,
+}
+
+impl Preferences {
+    /// Works out the preferences from `config`, with the flags in `args` taking priority.
+    ///
+    /// The preferences which can also be toggled with keys, like [`MINIMALIST`],
+    /// are kept in atomics rather than returned, so they're set here too.
+    fn apply(args: &Args, config: &Config) -> eyre::Result<Self> {
+        MINIMALIST.store(args.minimalist || config.minimalist, Ordering::Relaxed);
+        BORDERLESS.store(args.borderless || config.borderless, Ordering::Relaxed);
+        REMAINING.store(config.remaining, Ordering::Relaxed);
+
+        Self::title(args, config).map(|title| Self::new(args, config, title))
+    }
+
+    /// Works out the preferences from `config` again after it changed from `old`.
+    ///
+    /// Only the preferences which are different in `config` are applied, so that
+    /// anything toggled with a key, like [`MINIMALIST`], is kept unless it was changed
+    /// in the file too. The title is only parsed again if it's different as well.
+    fn update(&self, args: &Args, old: &Config, config: &Config) -> eyre::Result<Self> {
+        if config.minimalist != old.minimalist {
+            MINIMALIST.store(args.minimalist || config.minimalist, Ordering::Relaxed);
+        }
+
+        if config.borderless != old.borderless {
+            BORDERLESS.store(args.borderless || config.borderless, Ordering::Relaxed);
+        }
+
+        if config.remaining != old.remaining {
+            REMAINING.store(config.remaining, Ordering::Relaxed);
+        }
+
+        let title = if config.title == old.title {
+            self.title.clone()
+        } else {
+            Self::title(args, config)?
+        };
+
+        Ok(Self::new(args, config, title))
+    }
+
+    /// Parses the title template, from either `--title` or `config`.
+    fn title(args: &Args, config: &Config) -> eyre::Result<Option<Arc<Template>>> {
+        let title = args.title.as_deref().or(config.title.as_deref());
+        let title = title.map(Template::parse).transpose()?.map(Arc::new);
+        TITLED.store(title.is_some(), Ordering::Relaxed);
+
+        Ok(title)
+    }
+
+    /// Puts together the preferences which can't be toggled with keys, along with the `title`.
+    fn new(args: &Args, config: &Config, title: Option<Arc<Template>>) -> Self {
+        Self {
+            theme: CAPABILITIES.theme(args.theme.map_or(config.theme, Preset::theme)),
+            cue: args.cue.or(config.cue),
+            marquee: args.marquee || config.marquee,
+            title,
+        }
+    }
+}
+
+/// Applies any changes to `config.toml` while lowfi is running, by sending new preferences.
+///
+/// The file itself is loaded by the player, see [`Messages::ReloadConfig`], which
+/// this follows along with. If the new title can't be parsed, then the old preferences
+/// are kept and the error is shown in the window, so a typo doesn't make lowfi quit.
+async fn reload(player: Arc<Player>, args: Args, sender: watch::Sender<Preferences>) {
+    let mut configs = player.config.subscribe();
+    let mut config = Arc::clone(&configs.borrow_and_update());
+
+    while configs.changed().await.is_ok() {
+        let changed = Arc::clone(&configs.borrow_and_update());
+        let preferences = sender.borrow().update(&args, &config, &changed);
+        match preferences {
+            Ok(preferences) => {
+                sender.send_replace(preferences);
+            }
+            Err(error) => player.alert(error.to_string()),
+        }
+
+        config = changed;
+        redraw();
+    }
+}
+
+/// Gets `config` with the UI preferences replaced by the current ones.
+pub fn config(config: &Config) -> Config {
+    Config {
+        minimalist: MINIMALIST.load(Ordering::Relaxed),
+        borderless: BORDERLESS.load(Ordering::Relaxed),
+        remaining: REMAINING.load(Ordering::Relaxed),
+        ..config.clone()
+    }
+}
+
+/// The parts of the window's borders, which are prerendered
+/// since they don't change from frame to frame.
+struct Borders {
+    /// The top border, including the newline after it.
+    top: String,
+
+    /// The bottom border, which doesn't have a newline after it
+    /// to avoid extra space under the window.
+    bottom: String,
+
+    /// The left side of each line, including the padding.
+    left: String,
+
+    /// The right side of each line, including the padding.
+    right: String,
+}
+
+impl Borders {
+    /// Renders the borders for a window whose contents are `width` wide.
+    fn new(width: usize, theme: Theme) -> Self {
+        let glyphs = CAPABILITIES.glyphs();
+        let [top_left, top_right, bottom_left, bottom_right] = glyphs.corners;
+        let edge = glyphs.horizontal.to_string().repeat(width + 2);
+        let side = glyphs.vertical;
+
+        Self {
+            top: format!(
+                "{}\r\n",
+                theme.border(format!("{top_left}{edge}{top_right}"))
+            ),
+            bottom: theme.border(format!("{bottom_left}{edge}{bottom_right}")),
+            left: theme.border(format!("{side} ")),
+            right: theme.border(format!(" {side}")),
+        }
+    }
 }
 
 /// Represents an abstraction for drawing the actual lowfi window itself.
@@ -53,9 +321,18 @@ lazy_static! {
 /// The main purpose of this struct is just to add the fancy border,
 /// as well as clear the screen before drawing.
 pub struct Window {
-    /// The top & bottom borders, which are here since they can be
-    /// prerendered, as they don't change from window to window.
-    borders: [String; 2],
+    /// The borders, or [`None`] if the window is borderless.
+    borders: Option<Borders>,
+
+    /// The width of the window's contents.
+    width: usize,
+
+    /// How many more frames the borders should be highlighted for.
+    flash: usize,
+
+    /// The rendered window, which is reused from frame to frame
+    /// so that it doesn't have to be allocated over and over again.
+    buffer: String,
 
     /// The output, currently just an [`Stdout`].
     out: Stdout,
@@ -63,168 +340,481 @@ pub struct Window {
 
 impl Window {
     /// Initializes a new [Window].
-    pub fn new(width: usize) -> Self {
+    pub fn new(width: usize, borderless: bool, theme: Theme) -> Self {
         Self {
-            borders: [
-                format!("┌{}┐\r\n", "─".repeat(width + 2)),
-                // This one doesn't have a leading \r\n to avoid extra space under the window.
-                format!("└{}┘", "─".repeat(width + 2)),
-            ],
+            borders: (!borderless).then(|| Borders::new(width, theme)),
+            width,
+            flash: 0,
+            buffer: String::new(),
             out: stdout(),
         }
     }
 
-    /// Actually draws the window, with each element in `content` being on a new line.
-    pub fn draw(&mut self, content: Vec<String>) -> eyre::Result<()> {
-        let len = content.len() as u16;
+    /// Highlights the borders for the next few frames.
+    pub const fn flash(&mut self) {
+        self.flash = FLASH_DURATION;
+    }
 
-        let menu: String = content.into_iter().fold(String::new(), |mut output, x| {
-            write!(output, "│ {} │\r\n", x.reset()).unwrap();
+    /// Adds part of the borders to `buffer`, highlighted if `flashing` is set.
+    fn border(buffer: &mut String, text: &str, flashing: bool) {
+        if flashing {
+            write!(buffer, "{}", text.reverse()).unwrap();
+        } else {
+            buffer.push_str(text);
+        }
+    }
 
-            output
-        });
+    /// Whether the window would have to be rebuilt to be `width` wide & `borderless`.
+    const fn outdated(&self, width: usize, borderless: bool) -> bool {
+        self.width != width || self.borderless() != borderless
+    }
+
+    /// Gets the amount of columns the window takes up, including the borders.
+    const fn columns(&self) -> usize {
+        if self.borderless() {
+            self.width
+        } else {
+            self.width + 4
+        }
+    }
+
+    /// Whether the window is drawn without borders.
+    pub const fn borderless(&self) -> bool {
+        self.borders.is_none()
+    }
+
+    /// Renders the window into `buffer`, with each element in `content` being on a new line.
+    ///
+    /// The result doesn't end with a newline, to avoid extra space under the window.
+    fn render(&mut self, content: &[String]) {
+        let flashing = self.flash > 0;
+        let buffer = &mut self.buffer;
+        buffer.clear();
+
+        if let Some(borders) = &self.borders {
+            Self::border(buffer, &borders.top, flashing);
+        }
+
+        for (i, line) in content.iter().enumerate() {
+            if let Some(borders) = &self.borders {
+                Self::border(buffer, &borders.left, flashing);
+            }
+
+            write!(buffer, "{}", line.as_str().reset()).unwrap();
+
+            if let Some(borders) = &self.borders {
+                Self::border(buffer, &borders.right, flashing);
+            }
+
+            // Borderless windows end on their last line, rather than the bottom border.
+            if self.borders.is_some() || i + 1 < content.len() {
+                buffer.push_str("\r\n");
+            }
+        }
+
+        if let Some(borders) = &self.borders {
+            Self::border(buffer, &borders.bottom, flashing);
+        }
+    }
+
+    /// Gets the amount of rows the window takes up when it has `len` lines of content.
+    const fn height(&self, len: usize) -> u16 {
+        let len = len as u16;
+        let height = if self.borderless() { len } else { len + 2 };
+
+        // Windows always has an extra empty line under the window, see [`Window::draw`].
+        #[cfg(windows)]
+        let height = height + 1;
+
+        height
+    }
+
+    /// Actually draws the window, with each element in `content` being on a new line.
+    ///
+    /// The cursor is moved back up afterwards, so that the next frame is drawn over this one.
+    ///
+    /// If the terminal is too small for the window, then the lines would wrap
+    /// around and mangle it, so a placeholder is drawn instead until it's resized.
+    pub fn draw(&mut self, content: &[String]) -> eyre::Result<()> {
+        let height = self.height(content.len());
+        if let Ok((columns, rows)) = terminal::size() {
+            let cramped = usize::from(columns) < self.columns() || rows < height;
+            CRAMPED.store(cramped, Ordering::Relaxed);
+
+            if cramped {
+                return self.placeholder(usize::from(columns), height);
+            }
+        }
+
+        self.render(content);
+        self.flash = self.flash.saturating_sub(1);
 
         // We're doing this because Windows is stupid and can't stand
         // writing to the last line repeatedly. Again, it's stupid.
         #[cfg(windows)]
-        let (rendered, height) = (
-            format!("{}{}{}\r\n", self.borders[0], menu, self.borders[1]),
-            len + 2,
-        );
+        self.buffer.push_str("\r\n");
 
-        // Unix has no such ridiculous limitations, so we calculate
-        // the height of the window accurately.
-        #[cfg(not(windows))]
-        let (rendered, height) = (
-            format!("{}{}{}", self.borders[0], menu, self.borders[1]),
-            len + 1,
-        );
+        // Either way, the cursor ends up on the last row that the window takes up.
+        let height = height - 1;
+
+        // If the window didn't fit under the cursor, then the terminal will have
+        // scrolled it up, so the mouse has to know where it actually is now.
+        if let Ok((_, rows)) = terminal::size() {
+            ORIGIN.fetch_min(rows.saturating_sub(height + 1), Ordering::Relaxed);
+        }
 
         crossterm::execute!(
             self.out,
             Clear(ClearType::FromCursorDown),
             MoveToColumn(0),
-            Print(rendered),
+            Print(&self.buffer),
             MoveToColumn(0),
             MoveUp(height),
         )?;
 
         Ok(())
     }
+
+    /// Draws a single line saying how big the terminal has to be, in place of the window.
+    ///
+    /// `columns` is the width of the terminal, and `height` is how many rows the window needs.
+    fn placeholder(&mut self, columns: usize, height: u16) -> eyre::Result<()> {
+        // The last column is left empty, since some terminals wrap as soon as it's written to.
+        let message = components::cramped(self.columns(), height, columns.saturating_sub(1));
+
+        crossterm::execute!(
+            self.out,
+            Clear(ClearType::FromCursorDown),
+            MoveToColumn(0),
+            Print(message),
+            MoveToColumn(0),
+        )?;
+
+        Ok(())
+    }
+
+    /// Prints the window once, leaving the cursor underneath it.
+    ///
+    /// This is for showing a window outside of the actual player.
+    pub fn print(&mut self, content: &[String]) -> eyre::Result<()> {
+        self.render(content);
+        crossterm::execute!(self.out, Print(&self.buffer), Print("\r\n"))?;
+
+        Ok(())
+    }
 }
 
-/// The code for the terminal interface itself.
+/// Lets the user know that `current` just started playing, with the `cue` & the window title.
 ///
-/// * `minimalist` - All this does is hide the bottom control bar.
-/// * `width` - The width of player
-async fn interface(player: Arc<Player>, minimalist: bool, width: usize) -> eyre::Result<()> {
-    let mut window = Window::new(width);
+/// There's no cue if it's the `first` track, since lowfi was only just started.
+fn announce(
+    window: &mut Window,
+    current: &Info,
+    cue: Option<Cue>,
+    first: bool,
+) -> eyre::Result<()> {
+    if !first {
+        match cue {
+            Some(Cue::Bell) => crossterm::execute!(stdout(), Print('\x07'))?,
+            Some(Cue::Flash) => window.flash(),
+            None => (),
+        }
+    }
 
-    loop {
-        // Load `current` once so that it doesn't have to be loaded over and over
-        // again by different UI components.
-        let current = player.current.load();
-        let current = current.as_ref();
+    environment::title(&current.name)
+}
+
+/// Creates the row under the track name, which is usually the progress bar
+/// but is the audio bar for a little while after the volume is changed.
+fn middle(
+    player: &Player,
+    current: Option<&Arc<Info>>,
+    locale: Locale,
+    width: usize,
+    theme: Theme,
+) -> String {
+    let timer = VOLUME_TIMER.load(Ordering::Relaxed);
+    let middle = match timer {
+        0 => components::progress_bar(
+            player,
+            current,
+            REMAINING.load(Ordering::Relaxed),
+            width,
+            theme,
+        ),
+        _ => components::audio_bar(player.sink.volume(), locale, width, theme),
+    };
+
+    if DRAGGING.load(Ordering::Relaxed) {
+        // The audio bar stays up for as long as it's being dragged, even if the mouse stops moving.
+        VOLUME_TIMER.store(1, Ordering::Relaxed);
+    } else if timer > 0 && timer <= AUDIO_BAR_DURATION {
+        // We'll keep increasing the timer until it eventually hits `AUDIO_BAR_DURATION`.
+        VOLUME_TIMER.fetch_add(1, Ordering::Relaxed);
+    } else if timer > AUDIO_BAR_DURATION {
+        // If enough time has passed, we'll reset it back to 0.
+        VOLUME_TIMER.store(0, Ordering::Relaxed);
+    }
 
-        let action = components::action(&player, current, width);
+    middle
+}
 
-        let volume = player.sink.volume();
-        let percentage = format!("{}%", (volume * 100.0).round().abs());
+/// Creates the row under the progress bar, which is usually the controls, but is
+/// the last failure for a few seconds after a track couldn't be downloaded or decoded.
+///
+/// This is [`None`] in minimalist mode, unless there's a failure to show.
+fn bottom(player: &Player, width: usize, theme: Theme) -> Option<String> {
+    let failure = player.failure.load();
+    let failure = failure.as_ref().filter(|x| x.shown());
+    FAILING.store(failure.is_some(), Ordering::Relaxed);
+
+    failure.map_or_else(
+        || (!MINIMALIST.load(Ordering::Relaxed)).then(|| components::controls(width, theme)),
+        |failure| Some(components::failure(&failure.message, width, theme)),
+    )
+}
 
-        let timer = VOLUME_TIMER.load(Ordering::Relaxed);
-        let middle = match timer {
-            0 => components::progress_bar(&player, current, width - 16),
-            _ => components::audio_bar(volume, &percentage, width - 17),
-        };
+/// Whether anything in the window is moving, in which case it's redrawn every frame
+/// rather than only when something changes.
+///
+/// This is the loading spinner, a scrolling track name, flashing borders, the audio bar,
+/// and the visualizer, including while its bars are still falling after a pause.
+fn animating(
+    player: &Player,
+    current: Option<&Arc<Info>>,
+    marquee: bool,
+    window: &Window,
+    levels: &[f32],
+) -> bool {
+    // The action bar shares its row with the buffer indicator.
+    let width = WIDTH
+        .load(Ordering::Relaxed)
+        .saturating_sub(player.buffer_size() + 1);
+    let scrolling = marquee && current.is_some_and(|x| components::scrolls(x, width));
+    let visualizing = player.visualizer.is_some()
+        && ((current.is_some() && !player.sink.is_paused()) || levels.iter().any(|x| *x > 0.0));
+
+    current.is_none()
+        || scrolling
+        || visualizing
+        || window.flash > 0
+        || VOLUME_TIMER.load(Ordering::Relaxed) > 0
+}
 
-        if timer > 0 && timer <= AUDIO_BAR_DURATION {
-            // We'll keep increasing the timer until it eventually hits `AUDIO_BAR_DURATION`.
-            VOLUME_TIMER.fetch_add(1, Ordering::Relaxed);
-        } else if timer > AUDIO_BAR_DURATION {
-            // If enough time has passed, we'll reset it back to 0.
-            VOLUME_TIMER.store(0, Ordering::Relaxed);
+/// Waits until the next frame should be drawn, which is straight away
+/// if anything's `animating`, or otherwise once something changes.
+///
+/// This is also used by [`status`], which doesn't animate anything.
+async fn wait(animating: bool) {
+    if animating {
+        sleep(Duration::from_secs_f32(FRAME_DELTA)).await;
+    } else {
+        select! {
+            () = REDRAW.notified() => (),
+            () = sleep(IDLE_DELTA) => (),
         }
+    }
+}
 
-        let controls = components::controls(width);
+/// Creates the visualizer row, updating the heights of the bars in `levels`.
+fn visualize(
+    player: &Player,
+    visualizer: &Visualizer,
+    levels: &mut Vec<f32>,
+    width: usize,
+    theme: Theme,
+) -> String {
+    // There's nothing new to show while paused or loading, so the bars just fall away.
+    let bands = if player.current_exists() && !player.sink.is_paused() {
+        visualizer.bands(width)
+    } else {
+        vec![0.0; width]
+    };
+
+    levels.resize(width, 0.0);
+    for (level, band) in levels.iter_mut().zip(bands) {
+        *level = band.max(*level - VISUALIZER_FALL);
+    }
 
-        let menu = if minimalist {
-            vec![action, middle]
-        } else {
-            vec![action, middle, controls]
-        };
+    components::visualizer(levels, theme)
+}
 
-        window.draw(menu)?;
+/// Looks up what's known about a track to list it in a panel.
+async fn entry(player: &Player, name: String, path: &str) -> components::Entry {
+    components::Entry {
+        name,
+        bookmarked: player.bookmarks.contains(path).await,
+        skipped: player.history.skipped(path),
+    }
+}
 
-        sleep(Duration::from_secs_f32(FRAME_DELTA)).await;
+/// Creates the up next panel.
+async fn upcoming(player: &Player, width: usize, theme: Theme) -> Vec<String> {
+    let mut entries = Vec::new();
+    for track in player.tracks.read().await.iter() {
+        entries.push(entry(player, track.display_name(), &track.full_path).await);
     }
+
+    components::panel(
+        "up next",
+        "nothing has been downloaded yet",
+        &entries,
+        None,
+        width,
+        theme,
+    )
 }
 
-/// Represents the terminal environment, and is used to properly
-/// initialize and clean up the terminal.
-pub struct Environment {
-    /// Whether keyboard enhancements are enabled.
-    enhancement: bool,
+/// Creates the history panel, keeping the selection within the tracks that are actually there.
+async fn history(player: &Player, width: usize, theme: Theme) -> Vec<String> {
+    let mut entries = Vec::new();
+    for info in player.history.entries() {
+        entries.push(entry(player, info.name.clone(), &info.full_path).await);
+    }
 
-    /// Whether the terminal is in an alternate screen or not.
-    alternate: bool,
+    let selected = SELECTED
+        .load(Ordering::Relaxed)
+        .min(entries.len().saturating_sub(1));
+    SELECTED.store(selected, Ordering::Relaxed);
+
+    components::panel(
+        "history (enter to play next)",
+        "nothing has been played yet",
+        &entries,
+        Some(selected),
+        width,
+        theme,
+    )
 }
 
-impl Environment {
-    /// This prepares the terminal, returning an [Environment] helpful
-    /// for cleaning up afterwards.
-    pub fn ready(alternate: bool) -> eyre::Result<Self> {
-        let mut lock = stdout().lock();
+/// The code for the terminal interface itself.
+///
+/// * `locale` - How numbers & durations should be formatted.
+/// * `preferences` - The latest [`Preferences`], which change when `config.toml` does.
+/// * `art` - Whether to show the album art above the window.
+async fn interface(
+    player: Arc<Player>,
+    locale: Locale,
+    mut preferences: watch::Receiver<Preferences>,
+    art: bool,
+) -> eyre::Result<()> {
+    let mut window = Window::new(
+        WIDTH.load(Ordering::Relaxed),
+        BORDERLESS.load(Ordering::Relaxed),
+        preferences.borrow().theme,
+    );
+
+    // The amount of frames which have been drawn, used for animations.
+    let mut frame: usize = 0;
+
+    // The track that was playing last frame, used to tell when a new one starts.
+    let mut playing: Option<String> = None;
+
+    // The lines of the window, which are kept between frames to reuse the allocation.
+    let mut menu = Vec::new();
+
+    // The heights of the visualizer's bars, which fall gradually rather than all at once.
+    let mut levels = Vec::new();
+
+    // The frame that the current track started on, so that it starts scrolling from the beginning.
+    let mut started: usize = 0;
 
-        crossterm::execute!(lock, Hide)?;
+    loop {
+        // The borders can be toggled & the terminal resized at any time,
+        // in which case the window has to be rebuilt with the new dimensions.
+        // The same goes for when the theme changes in `config.toml`.
+        let width = WIDTH.load(Ordering::Relaxed);
+        let borderless = BORDERLESS.load(Ordering::Relaxed);
+        let changed = preferences.has_changed().unwrap_or(false);
+        let Preferences {
+            theme,
+            cue,
+            marquee,
+            title,
+        } = preferences.borrow_and_update().clone();
+        if changed || window.outdated(width, borderless) {
+            window = Window::new(width, borderless, theme);
+        }
 
-        if alternate {
-            crossterm::execute!(lock, EnterAlternateScreen, MoveTo(0, 0))?;
+        let resized = RESIZED.swap(false, Ordering::Relaxed);
+        if resized {
+            crossterm::execute!(stdout(), MoveTo(0, 0), Clear(ClearType::All))?;
+            ORIGIN.store(0, Ordering::Relaxed);
+            art::forget()?;
         }
 
-        terminal::enable_raw_mode()?;
-        let enhancement = terminal::supports_keyboard_enhancement()?;
+        // Load `current` once so that it doesn't have to be loaded over and over
+        // again by different UI components.
+        let current = player.current.load();
+        let current = current.as_ref();
+
+        if let Some(current) = current {
+            let new = playing.as_ref() != Some(&current.full_path);
+            if new {
+                started = frame;
+                announce(&mut window, current, cue, playing.is_none())?;
+            }
 
-        if enhancement {
-            crossterm::execute!(
-                lock,
-                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
-            )?;
+            // The art has to be drawn again after a resize, since the whole screen was cleared.
+            if art && (new || resized) {
+                art::show(current.art.as_deref())?;
+            }
+
+            playing = Some(current.full_path.clone());
         }
 
-        Ok(Self {
-            enhancement,
-            alternate,
-        })
-    }
+        let bookmarked = if let Some(current) = current {
+            player.bookmarks.contains(&current.full_path).await
+        } else {
+            false
+        };
 
-    /// Uses the information collected from initialization to safely close down
-    /// the terminal & restore it to it's previous state.
-    pub fn cleanup(&self) -> eyre::Result<()> {
-        let mut lock = stdout().lock();
+        // The buffer indicator sits in the top right corner, next to the action bar.
+        let buffered = player.buffered().await;
+        let size = player.buffer_size();
+        let buffer = components::buffer(buffered, size, theme);
+        let action = components::action(
+            &player,
+            current,
+            bookmarked,
+            frame,
+            marquee.then(|| frame.wrapping_sub(started) / MARQUEE_DELAY),
+            width.saturating_sub(size + 1),
+            theme,
+        );
+        let action = format!("{action} {buffer}");
+
+        let middle = middle(&player, current, locale, width, theme);
 
-        if self.alternate {
-            crossterm::execute!(lock, LeaveAlternateScreen)?;
+        menu.clear();
+        if let Some(title) = &title {
+            let snapshot = player.snapshot().await;
+            menu.push(components::title(&title.render(&snapshot, locale), width));
         }
 
-        crossterm::execute!(lock, Clear(ClearType::FromCursorDown), Show)?;
+        menu.push(action);
+        let artist = components::artist(current, width, theme);
+        ARTIST.store(artist.is_some(), Ordering::Relaxed);
+        menu.extend(artist);
+        menu.push(middle);
+        menu.extend(bottom(&player, width, theme));
 
-        if self.enhancement {
-            crossterm::execute!(lock, PopKeyboardEnhancementFlags)?;
+        if let Some(visualizer) = &player.visualizer {
+            menu.push(visualize(&player, visualizer, &mut levels, width, theme));
         }
 
-        terminal::disable_raw_mode()?;
+        if UPCOMING.load(Ordering::Relaxed) {
+            menu.extend(upcoming(&player, width, theme).await);
+        }
 
-        eprintln!("bye! :)");
+        if HISTORY.load(Ordering::Relaxed) {
+            menu.extend(history(&player, width, theme).await);
+        }
 
-        Ok(())
-    }
-}
+        window.draw(&menu)?;
+        frame = frame.wrapping_add(1);
 
-impl Drop for Environment {
-    /// Just a wrapper for [`Environment::cleanup`] which ignores any errors thrown.
-    fn drop(&mut self) {
-        // Well, we're dropping it, so it doesn't really matter if there's an error.
-        let _ = self.cleanup();
+        wait(animating(&player, current, marquee, &window, &levels)).await;
     }
 }
 
@@ -232,18 +822,161 @@ impl Drop for Environment {
 ///
 /// `alternate` controls whether to use [`EnterAlternateScreen`] in order to hide
 /// previous terminal history.
-pub async fn start(player: Arc<Player>, sender: Sender<Messages>, args: Args) -> eyre::Result<()> {
-    let environment = Environment::ready(args.alternate)?;
-    let interface = task::spawn(interface(
-        Arc::clone(&player),
-        args.minimalist,
-        21 + args.width.min(32) * 2,
-    ));
-
-    input::listen(sender.clone()).await?;
+///
+/// The flags in `args` take priority over the preferences in `config`,
+/// so `--theme` replaces the theme from `config.toml` entirely.
+/// Either way, the theme is degraded to what the terminal can show.
+/// Changes to `config.toml` are applied straight away, with the flags still taking priority.
+pub async fn start(
+    player: Arc<Player>,
+    sender: Sender<Messages>,
+    args: Args,
+    config: Config,
+) -> eyre::Result<()> {
+    // The title is parsed before touching the terminal, so that mistakes are printed normally.
+    let (preferences, receiver) = watch::channel(Preferences::apply(&args, &config)?);
+
+    let environment = Environment::ready(args.alternate, !args.no_mouse)?;
+    let width = match terminal::size() {
+        Ok((columns, _)) if args.auto_width => fit(columns),
+        _ => MIN_WIDTH + args.width.min(32) * 2,
+    };
+    WIDTH.store(width, Ordering::Relaxed);
+
+    let locale = Locale::detect(args.locale.as_deref());
+    let interface = task::spawn(interface(Arc::clone(&player), locale, receiver, args.art));
+
+    let auto_width = args.auto_width;
+    let reload = task::spawn(reload(Arc::clone(&player), args, preferences));
+
+    input::listen(sender.clone(), locale, auto_width).await?;
     interface.abort();
+    reload.abort();
 
     environment.cleanup()?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{fmt::Write, time::Instant};
+
+    use crossterm::style::Stylize;
+
+    use super::{
+        environment::CAPABILITIES,
+        theme::{Preset, Theme},
+        Window,
+    };
+
+    /// How the window was rendered before it had a reusable buffer, which the
+    /// output of [`Window::render`] has to stay byte for byte identical to.
+    fn baseline(
+        width: usize,
+        borderless: bool,
+        theme: Theme,
+        flashing: bool,
+        content: Vec<String>,
+    ) -> String {
+        let border = |text: &str| {
+            if flashing {
+                text.reverse().to_string()
+            } else {
+                text.to_owned()
+            }
+        };
+
+        let glyphs = CAPABILITIES.glyphs();
+        let [top_left, top_right, bottom_left, bottom_right] = glyphs.corners;
+        let edge = glyphs.horizontal.to_string().repeat(width + 2);
+        let side = glyphs.vertical;
+
+        let (left, right) = if borderless {
+            (String::new(), String::new())
+        } else {
+            (
+                border(&theme.border(format!("{side} "))),
+                border(&theme.border(format!(" {side}"))),
+            )
+        };
+
+        let menu: String = content.into_iter().fold(String::new(), |mut output, x| {
+            write!(output, "{left}{}{right}\r\n", x.reset()).unwrap();
+
+            output
+        });
+
+        if borderless {
+            menu.trim_end_matches("\r\n").to_owned()
+        } else {
+            let top = format!(
+                "{}\r\n",
+                theme.border(format!("{top_left}{edge}{top_right}"))
+            );
+            let bottom = theme.border(format!("{bottom_left}{edge}{bottom_right}"));
+            format!("{}{menu}{}", border(&top), border(&bottom))
+        }
+    }
+
+    /// Some lines like the ones the player shows, including styles & unicode.
+    fn content() -> Vec<String> {
+        vec![
+            format!("{} {}", "playing".bold(), "Café Au Lait"),
+            "[#######.............] 01:02/03:04".to_owned(),
+            format!(
+                "{}kip  {}ause  {}uit",
+                "[s]".bold(),
+                "[p]".bold(),
+                "[q]".bold()
+            ),
+        ]
+    }
+
+    #[test]
+    fn render() {
+        for preset in [Preset::Default, Preset::Plain, Preset::Ocean] {
+            for borderless in [false, true] {
+                for flashing in [false, true] {
+                    for content in [content(), vec!["one line".to_owned()], Vec::new()] {
+                        let theme = preset.theme();
+                        let mut window = Window::new(34, borderless, theme);
+                        if flashing {
+                            window.flash();
+                        }
+
+                        window.render(&content);
+                        let expected = baseline(34, borderless, theme, flashing, content);
+                        assert_eq!(window.buffer, expected);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Compares how long rendering takes against the baseline, which can be run with
+    /// `cargo test --release render_speed -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "this is a benchmark, rather than a test"]
+    fn render_speed() {
+        const FRAMES: u32 = 100_000;
+
+        let theme = Preset::Ocean.theme();
+        let content = content();
+
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            baseline(34, false, theme, false, content.clone());
+        }
+        let before = (start.elapsed() / FRAMES).as_nanos();
+
+        let mut window = Window::new(34, false, theme);
+        let start = Instant::now();
+        for _ in 0..FRAMES {
+            window.render(&content);
+        }
+        let after = (start.elapsed() / FRAMES).as_nanos();
+
+        println!("baseline: {before}ns per frame, now: {after}ns per frame");
+    }
+}
@@ -0,0 +1,252 @@
+//! Draws the album art above the window with `--art`, in terminals which can show images.
+
+use std::{
+    fmt::Write,
+    io::stdout,
+    iter, str,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use crossterm::{
+    cursor::{MoveDown, MoveUp, RestorePosition, SavePosition},
+    style::Print,
+    terminal::{self, Clear, ClearType},
+};
+use image::imageops::FilterType;
+use lazy_static::lazy_static;
+
+use super::{
+    environment::{Images, CAPABILITIES},
+    ORIGIN,
+};
+use crate::tracks::tags::Art;
+
+/// How many rows the art takes up.
+const HEIGHT: u16 = 8;
+
+/// How many columns the art takes up, which is twice [`HEIGHT`]
+/// since terminal cells are about twice as tall as they are wide.
+const WIDTH: u16 = HEIGHT * 2;
+
+/// The most base64 that can be sent in one kitty escape sequence.
+const CHUNK: usize = 4096;
+
+/// The ID that the art is given with the kitty protocol, so that it can be deleted later.
+const KITTY_ID: u32 = 0x6c6f;
+
+/// The size of a terminal cell in pixels, if the terminal doesn't say what it is.
+const CELL: (u32, u32) = (10, 20);
+
+/// How many levels each channel has in the sixel palette, which is a cube of colors.
+const LEVELS: u32 = 6;
+
+lazy_static! {
+    /// Whether there's art above the window right now.
+    static ref SHOWN: AtomicBool = AtomicBool::new(false);
+}
+
+/// Encodes `art` as the escape sequence which shows it with `images`.
+///
+/// This is [`None`] if `art` isn't in a format that the protocol can take.
+fn encode(images: Images, art: &Art) -> Option<String> {
+    let data = STANDARD.encode(&art.data);
+
+    match images {
+        Images::Iterm => Some(format!(
+            "\x1B]1337;File=inline=1;size={};width={WIDTH};height={HEIGHT};preserveAspectRatio=1:{data}\x07",
+            art.data.len()
+        )),
+        Images::Kitty if art.media_type == "image/png" => {
+            let mut encoded = String::new();
+            let chunks: Vec<&[u8]> = data.as_bytes().chunks(CHUNK).collect();
+
+            for (i, chunk) in chunks.iter().enumerate() {
+                // Only the first chunk has the options, and `m` says whether there's more to come.
+                // `C=1` stops the cursor from moving, and `q=2` stops the terminal from replying.
+                let options = if i == 0 {
+                    format!("a=T,f=100,i={KITTY_ID},c={WIDTH},r={HEIGHT},C=1,q=2,")
+                } else {
+                    String::new()
+                };
+
+                let more = u8::from(i + 1 < chunks.len());
+                let chunk = str::from_utf8(chunk).unwrap_or_default();
+                write!(encoded, "\x1B_G{options}m={more};{chunk}\x1B\\").unwrap();
+            }
+
+            Some(encoded)
+        }
+        Images::Kitty => None,
+        Images::Sixel => sixel(art),
+    }
+}
+
+/// Gets how big a terminal cell is in pixels, which is what sixel images are measured in.
+fn cell() -> (u32, u32) {
+    match terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            u32::from(size.width / size.columns).max(1),
+            u32::from(size.height / size.rows).max(1),
+        ),
+        _ => CELL,
+    }
+}
+
+/// Gets the register of the closest color in the palette to `pixel`.
+fn register(pixel: [u8; 3]) -> usize {
+    pixel.into_iter().fold(0, |register, channel| {
+        let level = (u32::from(channel) * (LEVELS - 1) + 127) / 255;
+        register * LEVELS as usize + level as usize
+    })
+}
+
+/// Writes `count` of the sixel `char`, squashing it into a repeat if there's enough of them.
+fn repeat(encoded: &mut String, sixel: char, count: usize) {
+    if count > 3 {
+        write!(encoded, "!{count}{sixel}").unwrap();
+    } else {
+        encoded.extend(iter::repeat(sixel).take(count));
+    }
+}
+
+/// Decodes `art` & encodes it as sixels, which is [`None`] if it couldn't be decoded.
+///
+/// Sixel images are just pixels drawn with a palette, so each one
+/// is swapped for the closest color in a cube of [`LEVELS`] levels.
+fn sixel(art: &Art) -> Option<String> {
+    let (width, height) = cell();
+    let image = image::load_from_memory(&art.data)
+        .ok()?
+        .resize(
+            u32::from(WIDTH) * width,
+            u32::from(HEIGHT) * height,
+            FilterType::Triangle,
+        )
+        .into_rgb8();
+
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    let registers: Vec<usize> = image.pixels().map(|x| register(x.0)).collect();
+
+    // `"1;1` makes the pixels square, and the palette is set up before anything is drawn.
+    let mut encoded = format!("\x1BPq\"1;1;{width};{height}");
+    for register in 0..LEVELS.pow(3) {
+        let [red, green, blue] = [register / LEVELS / LEVELS, register / LEVELS, register]
+            .map(|x| x % LEVELS * 100 / (LEVELS - 1));
+        write!(encoded, "#{register};2;{red};{green};{blue}").unwrap();
+    }
+
+    // Each sixel is a column of six pixels, so the image is drawn in bands of six rows,
+    // going over the band once for every color in it.
+    for top in (0..height).step_by(6) {
+        let rows = top..(top + 6).min(height);
+        let mut colors: Vec<usize> = rows
+            .clone()
+            .flat_map(|y| &registers[y * width..(y + 1) * width])
+            .copied()
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for (i, color) in colors.into_iter().enumerate() {
+            // `$` goes back to the start of the band, so that the next color is drawn over it.
+            if i > 0 {
+                encoded.push('$');
+            }
+            write!(encoded, "#{color}").unwrap();
+
+            let mut run = ('?', 0);
+            for x in 0..width {
+                let bits = rows
+                    .clone()
+                    .filter(|y| registers[y * width + x] == color)
+                    .fold(0u8, |bits, y| bits | (1 << (y - top)));
+                let sixel = char::from(63 + bits);
+
+                if sixel == run.0 {
+                    run.1 += 1;
+                } else {
+                    repeat(&mut encoded, run.0, run.1);
+                    run = (sixel, 1);
+                }
+            }
+
+            // Blank sixels at the end of the row don't need to be drawn at all.
+            if run.0 != '?' {
+                repeat(&mut encoded, run.0, run.1);
+            }
+        }
+
+        encoded.push('-');
+    }
+
+    encoded.push_str("\x1B\\");
+    Some(encoded)
+}
+
+/// Shows `art` above the window, replacing whatever art was there before.
+///
+/// The cursor has to be at the top of the window, and it's moved down to make room.
+/// If there's no art, or the terminal can't show it, then the window is just left as is.
+pub fn show(art: Option<&Art>) -> eyre::Result<()> {
+    hide()?;
+
+    let Some(images) = CAPABILITIES.protocol() else {
+        return Ok(());
+    };
+
+    let Some(image) = art.and_then(|x| encode(images, x)) else {
+        return Ok(());
+    };
+
+    // Room is made first, so that if the terminal has to scroll, it does so before the image is drawn.
+    crossterm::execute!(
+        stdout(),
+        Print("\r\n".repeat(usize::from(HEIGHT))),
+        MoveUp(HEIGHT),
+        SavePosition,
+        Print(image),
+        RestorePosition,
+        MoveDown(HEIGHT),
+    )?;
+
+    ORIGIN.fetch_add(HEIGHT, Ordering::Relaxed);
+    SHOWN.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// Removes the art from above the window, if there is any, leaving the cursor where it was.
+///
+/// The cursor has to be at the top of the window, and it's moved up to where the art was.
+pub fn hide() -> eyre::Result<()> {
+    if !SHOWN.swap(false, Ordering::Relaxed) {
+        return Ok(());
+    }
+
+    crossterm::execute!(stdout(), MoveUp(HEIGHT), Clear(ClearType::FromCursorDown))?;
+    forget()?;
+
+    // This can't actually fail, since the closure always returns `Some`.
+    let _ = ORIGIN.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+        Some(x.saturating_sub(HEIGHT))
+    });
+
+    Ok(())
+}
+
+/// Forgets about the art after the screen was cleared, so that it isn't removed twice.
+///
+/// Kitty keeps images around after the text under them is cleared, so they're deleted too.
+pub fn forget() -> eyre::Result<()> {
+    SHOWN.store(false, Ordering::Relaxed);
+
+    if CAPABILITIES.protocol() == Some(Images::Kitty) {
+        crossterm::execute!(
+            stdout(),
+            Print(format!("\x1B_Ga=d,d=I,i={KITTY_ID},q=2\x1B\\"))
+        )?;
+    }
+
+    Ok(())
+}
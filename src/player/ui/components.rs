@@ -1,26 +1,44 @@
 //! Various different individual components that
 //! appear in lowfi's UI, like the progress bar.
 
-use std::{ops::Deref, sync::Arc, time::Duration};
+use std::{fmt::Write, ops::Deref, sync::Arc, time::Duration};
 
-use crossterm::style::Stylize;
 use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use crate::{player::Player, tracks::Info};
+use crate::{
+    player::{retry::Status, Messages, Player},
+    tracks::Info,
+};
 
-/// Small helper function to format durations.
-pub fn format_duration(duration: &Duration) -> String {
-    let seconds = duration.as_secs() % 60;
-    let minutes = duration.as_secs() / 60;
+use super::{
+    environment::CAPABILITIES,
+    format::{self, Locale},
+    theme::Theme,
+};
 
-    format!("{minutes:02}:{seconds:02}")
+/// Gets the width of the progress bar itself, in a window whose contents are `width` wide.
+///
+/// If `remaining` is set, the time takes up an extra column for the `-`.
+pub const fn progress_width(width: usize, remaining: bool) -> usize {
+    width - 16 - remaining as usize
 }
 
 /// Creates the progress bar, as well as all the padding needed.
-pub fn progress_bar(player: &Player, current: Option<&Arc<Info>>, width: usize) -> String {
+///
+/// If `remaining` is set, this shows how long is left of the track instead of how long
+/// it's played for. Like the audio bar, `width` is the width of the whole window.
+pub fn progress_bar(
+    player: &Player,
+    current: Option<&Arc<Info>>,
+    remaining: bool,
+    width: usize,
+    theme: Theme,
+) -> String {
+    let width = progress_width(width, remaining);
     let mut duration = Duration::new(0, 0);
     let elapsed = if current.is_some() {
-        player.sink.get_pos()
+        player.elapsed()
     } else {
         Duration::new(0, 0)
     };
@@ -36,27 +54,88 @@ pub fn progress_bar(player: &Player, current: Option<&Arc<Info>>, width: usize)
     };
 
     format!(
-        " [{}{}] {}/{} ",
-        "/".repeat(filled),
-        " ".repeat(width.saturating_sub(filled)),
-        format_duration(&elapsed),
-        format_duration(&duration),
+        " [{}{:padding$}] {} ",
+        theme.bar("/".repeat(filled)),
+        "",
+        theme.faint(if remaining {
+            format!(
+                "-{}/{}",
+                format::duration(&duration.saturating_sub(elapsed)),
+                format::duration(&duration)
+            )
+        } else {
+            format!(
+                "{}/{}",
+                format::duration(&elapsed),
+                format::duration(&duration)
+            )
+        }),
+        padding = width.saturating_sub(filled),
     )
 }
 
+/// The amount of columns before the audio bar itself, which is ` volume: [`.
+const AUDIO_BAR_START: usize = 10;
+
+/// Gets the width of the audio bar itself, in a window whose contents are `width` wide.
+pub fn audio_width(width: usize, locale: Locale) -> usize {
+    width.saturating_sub(AUDIO_BAR_START + 3 + locale.percentage_width())
+}
+
 /// Creates the audio bar, as well as all the padding needed.
-pub fn audio_bar(volume: f32, percentage: &str, width: usize) -> String {
+///
+/// Unlike the other bars, `width` is the width of the whole window,
+/// since the width of the percentage depends on the [Locale].
+pub fn audio_bar(volume: f32, locale: Locale, width: usize, theme: Theme) -> String {
+    let percentage = locale.percentage(volume);
+    let padding = locale.percentage_width();
+
+    let width = audio_width(width, locale);
     let audio = (volume * width as f32).round() as usize;
 
     format!(
-        " volume: [{}{}] {}{} ",
-        "/".repeat(audio),
-        " ".repeat(width.saturating_sub(audio)),
-        " ".repeat(4usize.saturating_sub(percentage.len())),
-        percentage,
+        " volume: [{}{:empty$}] {percentage:>padding$} ",
+        theme.bar("/".repeat(audio)),
+        "",
+        empty = width.saturating_sub(audio),
     )
 }
 
+/// Creates the visualizer row, with one bar for each of the `levels`, which go from 0 to 1.
+pub fn visualizer(levels: &[f32], theme: Theme) -> String {
+    let glyphs = CAPABILITIES.glyphs().levels;
+    let top = (glyphs.len() - 1) as f32;
+
+    theme.bar(
+        levels
+            .iter()
+            .map(|x| glyphs[(x.clamp(0.0, 1.0) * top).round() as usize])
+            .collect::<String>(),
+    )
+}
+
+/// Creates the buffer health indicator, which has a dot for each of the `size` tracks
+/// in the buffer, filled in for each one that's been downloaded and is waiting to be played.
+///
+/// If it's completely empty, then skipping will mean waiting for a download.
+/// The indicator is `size` columns wide.
+pub fn buffer(buffered: usize, size: usize, theme: Theme) -> String {
+    let buffered = buffered.min(size);
+    let glyphs = CAPABILITIES.glyphs();
+
+    format!(
+        "{}{}",
+        theme.bar(glyphs.filled.repeat(buffered)),
+        theme.faint(glyphs.empty.repeat(size - buffered))
+    )
+}
+
+/// Gets the frame of the loading spinner for the render tick `frame`.
+fn spinner(frame: usize) -> &'static str {
+    let spinner = CAPABILITIES.glyphs().spinner;
+    spinner[frame % spinner.len()]
+}
+
 /// This represents the main "action" bars state.
 enum ActionBar {
     /// When the app is currently displaying "paused".
@@ -65,59 +144,323 @@ enum ActionBar {
     /// When the app is currently displaying "playing".
     Playing(Info),
 
-    /// When the app is currently displaying "loading".
-    Loading,
+    /// When the app is currently displaying "loading",
+    /// along with the current render tick for the spinner
+    /// and the retry that's in progress, if there is one.
+    Loading(usize, Option<Arc<Status>>),
 }
 
 impl ActionBar {
     /// Formats the action bar to be displayed.
     /// The second value is the character length of the result.
-    fn format(&self) -> (String, usize) {
-        let (word, subject) = match self {
-            Self::Playing(x) => ("playing", Some((x.name.clone(), x.width))),
-            Self::Paused(x) => ("paused", Some((x.name.clone(), x.width))),
-            Self::Loading => ("loading", None),
+    ///
+    /// `bookmarked` will add a small marker after the track name.
+    /// If `scroll` is set and the name doesn't fit in `width`, then it's
+    /// scrolled along by that many characters instead of being cut off.
+    fn format(
+        &self,
+        bookmarked: bool,
+        scroll: Option<usize>,
+        width: usize,
+        theme: Theme,
+    ) -> (String, usize) {
+        let (word, info) = match self {
+            Self::Playing(x) => ("playing", x),
+            Self::Paused(x) => ("paused", x),
+            Self::Loading(frame, retrying) => {
+                let spinner = spinner(*frame);
+                let Some(retrying) = retrying else {
+                    return (
+                        format!("loading {spinner}"),
+                        "loading ".len() + spinner.width(),
+                    );
+                };
+
+                let attempt = retrying.max.map_or_else(
+                    || format!("attempt {}", retrying.attempt),
+                    |max| format!("attempt {}/{max}", retrying.attempt),
+                );
+
+                // Round up, so that it doesn't say "0s" while it's still waiting.
+                let remaining = retrying.remaining().as_secs_f32().ceil();
+                let main = if remaining > 0.0 {
+                    format!("retrying in {remaining}s ({attempt})")
+                } else {
+                    format!("loading {spinner} ({attempt})")
+                };
+
+                let len = main.width();
+                return (main, len);
+            }
         };
 
-        subject.map_or_else(
-            || (word.to_owned(), word.len()),
-            |(subject, len)| (format!("{} {}", word, subject.bold()), word.len() + 1 + len),
+        let marker = if bookmarked { " *" } else { "" };
+
+        let room = width.saturating_sub(word.len() + 1 + marker.len());
+        if let Some(offset) = scroll.filter(|_| info.width > room) {
+            let name = marquee(&info.name, offset, room);
+            return (format!("{} {}{}", word, theme.strong(name), marker), width);
+        }
+
+        (
+            format!("{} {}{}", word, theme.strong(&info.name), marker),
+            word.len() + 1 + info.width + marker.len(),
         )
     }
 }
 
+/// The space in between the end of a scrolling track name and the start of it coming around again.
+const MARQUEE_GAP: &str = "   ";
+
+/// Gets the part of `text` which is visible after it's scrolled along by `offset` characters,
+/// wrapping back around to the start, and fits it to `width` columns.
+fn marquee(text: &str, offset: usize, width: usize) -> String {
+    let looped = format!("{text}{MARQUEE_GAP}");
+    let graphemes: Vec<&str> = looped.graphemes(true).collect();
+    let (end, start) = graphemes.split_at(offset % graphemes.len());
+
+    fit(&[start, end].concat().concat(), width)
+}
+
+/// Whether the name of `info` is too long for an action bar that's `width` wide,
+/// in which case it scrolls with `--marquee`.
+///
+/// This assumes the longest status & a bookmark marker, so it might say
+/// a name scrolls when it just barely fits, but never the other way around.
+pub const fn scrolls(info: &Info, width: usize) -> bool {
+    info.width > width.saturating_sub("playing ".len() + " *".len())
+}
+
 /// Creates the top/action bar, which has the name of the track and it's status.
 /// This also creates all the needed padding.
-pub fn action(player: &Player, current: Option<&Arc<Info>>, width: usize) -> String {
+///
+/// `frame` is the current render tick, which animates the loading spinner.
+/// While loading, this also shows any retry that's in progress.
+/// `scroll` is how far along a long track name should be scrolled, with `--marquee`.
+pub fn action(
+    player: &Player,
+    current: Option<&Arc<Info>>,
+    bookmarked: bool,
+    frame: usize,
+    scroll: Option<usize>,
+    width: usize,
+    theme: Theme,
+) -> String {
     let (main, len) = current
-        .map_or(ActionBar::Loading, |info| {
-            let info = info.deref().clone();
+        .map_or_else(
+            || ActionBar::Loading(frame, player.retrying.load_full()),
+            |info| {
+                let info = info.deref().clone();
 
-            if player.sink.is_paused() {
-                ActionBar::Paused(info)
-            } else {
-                ActionBar::Playing(info)
-            }
-        })
-        .format();
+                if player.sink.is_paused() {
+                    ActionBar::Paused(info)
+                } else {
+                    ActionBar::Playing(info)
+                }
+            },
+        )
+        .format(bookmarked, scroll, width, theme);
 
     if len > width {
         let chopped: String = main.graphemes(true).take(width + 1).collect();
 
         format!("{}...", chopped)
     } else {
-        format!("{}{}", main, " ".repeat(width - len))
+        format!("{main}{:1$}", "", width - len)
     }
 }
 
-/// Creates the bottom controls bar, and also spaces it properly.
-pub fn controls(width: usize) -> String {
-    let controls = [["[s]", "kip"], ["[p]", "ause"], ["[q]", "uit"]];
+/// Cuts `text` down to `width` columns, or pads it with spaces to fill them.
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut len = 0;
+
+    for grapheme in text.graphemes(true) {
+        let grapheme_width = grapheme.width();
+        if len + grapheme_width > width {
+            break;
+        }
+
+        fitted.push_str(grapheme);
+        len += grapheme_width;
+    }
+
+    write!(fitted, "{:1$}", "", width - len).unwrap();
+    fitted
+}
+
+/// Creates the dimmed line under the track name with the artist & album from its tags,
+/// or [`None`] if there isn't a track or it doesn't have an artist.
+pub fn artist(current: Option<&Arc<Info>>, width: usize, theme: Theme) -> Option<String> {
+    let current = current?;
+    let artist = current.artist.as_ref()?;
+    let line = current
+        .album
+        .as_ref()
+        .map_or_else(|| artist.clone(), |album| format!("{artist} - {album}"));
+
+    Some(theme.faint(fit(&line, width)))
+}
+
+/// Creates the line which says what went wrong when a track fails, cut off if it's too long.
+pub fn failure(message: &str, width: usize, theme: Theme) -> String {
+    theme.accent(fit(message, width))
+}
 
-    let len: usize = controls.concat().iter().map(|x| x.len()).sum();
-    let controls = controls.map(|x| format!("{}{}", x[0].bold(), x[1]));
+/// Creates the title line from `--title`, which is cut off if it's too long.
+pub fn title(text: &str, width: usize) -> String {
+    fit(text, width)
+}
+
+/// Creates the line which is shown instead of the window when the terminal
+/// is smaller than `columns` by `rows`, which is cut off to fit in `width`.
+pub fn cramped(columns: usize, rows: u16, width: usize) -> String {
+    fit(
+        &format!("terminal too small, needs {columns}x{rows}"),
+        width,
+    )
+}
+
+/// A track listed in a [`panel`], with what's known about it.
+pub struct Entry {
+    /// The display name of the track.
+    pub name: String,
+
+    /// Whether the track is bookmarked.
+    pub bookmarked: bool,
+
+    /// Whether the track was skipped earlier in this session.
+    pub skipped: bool,
+}
+
+impl Entry {
+    /// The markers shown after the name, which are `*` if it's bookmarked
+    /// like in the action bar, and `~` if it was skipped.
+    fn markers(&self) -> String {
+        let mut markers = String::new();
+        if self.bookmarked {
+            markers.push_str(" *");
+        }
+
+        if self.skipped {
+            markers.push_str(" ~");
+        }
+
+        markers
+    }
+}
+
+/// The amount of tracks which are shown in a panel at once.
+const PANEL_HEIGHT: usize = 5;
+
+/// Creates the lines of a panel under the window, which has a `heading` and
+/// then lists `entries`, with the one at `selected` highlighted if there is one.
+///
+/// Only a few entries are shown at once, and it scrolls to keep `selected` visible.
+/// If there aren't any entries, then `empty` is shown instead.
+pub fn panel(
+    heading: &str,
+    empty: &str,
+    entries: &[Entry],
+    selected: Option<usize>,
+    width: usize,
+    theme: Theme,
+) -> Vec<String> {
+    let mut lines = vec![theme.faint(fit(heading, width))];
+    if entries.is_empty() {
+        lines.push(format!("  {}", fit(empty, width - 2)));
+        return lines;
+    }
+
+    let start = selected.map_or(0, |x| x.saturating_sub(PANEL_HEIGHT - 1));
+    lines.extend(
+        entries
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(PANEL_HEIGHT)
+            .map(|(i, entry)| {
+                let marker = if selected == Some(i) {
+                    theme.accent(">")
+                } else {
+                    " ".to_owned()
+                };
+
+                // The markers are kept even if the name has to be cut off.
+                let markers = entry.markers();
+                let name = fit(&entry.name, width.saturating_sub(2 + markers.len()));
+                format!("{marker} {name}{}", theme.faint(markers))
+            }),
+    );
+
+    lines
+}
+
+/// Gets which part of the progress bar was clicked on, from 0 to 1,
+/// where `column` is relative to the start of the window's contents.
+///
+/// `width` is the width of the bar itself, from [`progress_width`].
+pub fn seek(column: usize, width: usize) -> Option<f32> {
+    // The bar starts after the leading " [".
+    let column = column.checked_sub(2).filter(|x| *x < width)?;
+
+    Some((column as f32 + 0.5) / width as f32)
+}
+
+/// Whether `column` is on the audio bar, including the brackets around it.
+///
+/// `width` is the width of the bar itself, from [`audio_width`].
+pub const fn on_audio_bar(column: usize, width: usize) -> bool {
+    column + 1 >= AUDIO_BAR_START && column <= AUDIO_BAR_START + width
+}
+
+/// Gets the volume that clicking or dragging to `column` sets, from 0 to 1.
+///
+/// Clicking on part of the bar fills it up to and including that part, and going
+/// past either end of the bar while dragging sets the volume to 0 or 1.
+pub fn volume(column: usize, width: usize) -> f32 {
+    let filled = column as f32 + 1.0 - AUDIO_BAR_START as f32;
+    (filled / width as f32).clamp(0.0, 1.0)
+}
+
+/// The controls in the bottom bar, along with what clicking on them does.
+const CONTROLS: [([&str; 2], Messages); 3] = [
+    (["[s]", "kip"], Messages::Next),
+    (["[p]", "ause"], Messages::PlayPause),
+    (["[q]", "uit"], Messages::Quit),
+];
+
+/// Gets the amount of space in between each of the controls.
+fn controls_gap(width: usize) -> usize {
+    let len: usize = CONTROLS.iter().flat_map(|(x, _)| x).map(|x| x.len()).sum();
+
+    (width - len) / (CONTROLS.len() - 1)
+}
+
+/// Gets the control that was clicked on, where `column` is
+/// relative to the start of the window's contents.
+pub fn control(column: usize, width: usize) -> Option<Messages> {
+    let gap = controls_gap(width);
+    let mut start = 0;
+
+    for ([key, rest], message) in CONTROLS {
+        let end = start + key.len() + rest.len();
+        if (start..end).contains(&column) {
+            return Some(message);
+        }
+
+        start = end + gap;
+    }
+
+    None
+}
+
+/// Creates the bottom controls bar, and also spaces it properly.
+pub fn controls(width: usize, theme: Theme) -> String {
+    let controls =
+        CONTROLS.map(|([key, rest], _)| format!("{}{}", theme.accent(key), theme.faint(rest)));
 
-    let mut controls = controls.join(&" ".repeat((width - len) / (controls.len() - 1)));
+    let mut controls = controls.join(&" ".repeat(controls_gap(width)));
     // This is needed because changing the above line
     // only works for when the width is even
     controls.push_str(match width % 2 {
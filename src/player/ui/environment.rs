@@ -0,0 +1,372 @@
+//! Contains the [`Environment`], which sets up & restores the terminal,
+//! as well as the [`Capabilities`] of the terminal that lowfi is running in.
+
+use std::{
+    env, fmt,
+    io::stdout,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        RwLock,
+    },
+};
+
+use clap::ValueEnum;
+use crossterm::{
+    cursor::{self, Hide, MoveTo, Show},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    style::Color,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+    Command,
+};
+use lazy_static::lazy_static;
+
+use super::{art, theme::Theme, ORIGIN};
+
+lazy_static! {
+    /// The capabilities of the terminal, which are only detected once.
+    pub static ref CAPABILITIES: Capabilities = Capabilities::detect();
+
+    /// Whether `--ascii` was set, which means only ASCII is drawn
+    /// no matter what the terminal is detected as supporting.
+    static ref ASCII: AtomicBool = AtomicBool::new(false);
+
+    /// The image protocol from `--art-backend`, which is used instead of the guessed one.
+    static ref IMAGES: RwLock<Option<Images>> = RwLock::new(None);
+}
+
+/// Makes the UI only use ASCII, like the terminal doesn't support unicode.
+pub fn force_ascii() {
+    ASCII.store(true, Ordering::Relaxed);
+}
+
+/// Makes the art use `images`, no matter what the terminal is guessed to support.
+pub fn force_images(images: Images) {
+    *IMAGES.write().unwrap() = Some(images);
+}
+
+/// The characters used to draw the UI, which depend on whether the terminal supports unicode.
+pub struct Glyphs {
+    /// The top left, top right, bottom left & bottom right corners of the window.
+    pub corners: [char; 4],
+
+    /// The top & bottom edges of the window.
+    pub horizontal: char,
+
+    /// The sides of the window.
+    pub vertical: char,
+
+    /// The frames of the spinner which is shown while loading.
+    pub spinner: &'static [&'static str],
+
+    /// A track in the buffer health indicator which has been downloaded.
+    pub filled: &'static str,
+
+    /// An empty slot in the buffer health indicator.
+    pub empty: &'static str,
+
+    /// The bars of the visualizer, from quietest to loudest.
+    pub levels: &'static [&'static str],
+}
+
+impl Glyphs {
+    /// The usual box drawing characters, braille spinner & dots.
+    const UNICODE: Self = Self {
+        corners: ['┌', '┐', '└', '┘'],
+        horizontal: '─',
+        vertical: '│',
+        spinner: &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+        filled: "●",
+        empty: "○",
+        levels: &[" ", "▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"],
+    };
+
+    /// Plain ASCII replacements, which look fine on just about anything.
+    const ASCII: Self = Self {
+        corners: ['+', '+', '+', '+'],
+        horizontal: '-',
+        vertical: '|',
+        spinner: &["|", "/", "-", "\\"],
+        filled: "*",
+        empty: ".",
+        levels: &[" ", ".", ":", "|"],
+    };
+}
+
+/// A protocol which the terminal can show images with.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Images {
+    /// The kitty graphics protocol, which only takes PNGs without decoding them ourselves.
+    Kitty,
+
+    /// iTerm2's inline images, which take just about any format.
+    Iterm,
+
+    /// Sixel graphics, for terminals like xterm & foot.
+    /// The art is decoded & drawn with a fixed palette, so it's a bit grainy.
+    Sixel,
+}
+
+/// What the terminal is able to display.
+///
+/// There isn't a reliable way to ask the terminal itself, so this is
+/// guessed from the environment variables that most terminals set.
+pub struct Capabilities {
+    /// Whether the terminal can show colors at all.
+    pub color: bool,
+
+    /// Whether the terminal can show arbitrary RGB colors, rather than just a palette.
+    pub truecolor: bool,
+
+    /// Whether the terminal can show characters outside of ASCII, like box drawing characters.
+    pub unicode: bool,
+
+    /// How the terminal can show images, if it can at all.
+    pub images: Option<Images>,
+}
+
+impl Capabilities {
+    /// Gets the value of the environment variable `key`, ignoring it if it's empty.
+    fn var(key: &str) -> Option<String> {
+        env::var(key).ok().filter(|x| !x.is_empty())
+    }
+
+    /// Guesses which image protocol the terminal supports from `TERM` & `TERM_PROGRAM`.
+    ///
+    /// This is only done for terminals which are known to support one, and never inside
+    /// of tmux, which doesn't pass images through to the terminal by default.
+    fn images(term: &str) -> Option<Images> {
+        if Self::var("TMUX").is_some() {
+            return None;
+        }
+
+        match Self::var("TERM_PROGRAM").as_deref() {
+            Some("iTerm.app" | "WezTerm") => Some(Images::Iterm),
+            Some("ghostty") => Some(Images::Kitty),
+            _ if term == "xterm-kitty" || term == "xterm-ghostty" => Some(Images::Kitty),
+            _ if Self::var("KITTY_WINDOW_ID").is_some() => Some(Images::Kitty),
+            _ if term == "foot" || term.starts_with("foot-") => Some(Images::Sixel),
+            _ => None,
+        }
+    }
+
+    /// Guesses the capabilities of the terminal from `TERM`, `COLORTERM`, `NO_COLOR` & the locale.
+    fn detect() -> Self {
+        let term = Self::var("TERM").unwrap_or_default();
+        let dumb = term == "dumb";
+
+        let truecolor = Self::var("COLORTERM")
+            .is_some_and(|x| x == "truecolor" || x == "24bit")
+            || term.ends_with("-direct")
+            // Windows Terminal supports truecolor, but doesn't set `COLORTERM`.
+            || (cfg!(windows) && Self::var("WT_SESSION").is_some());
+
+        // The locale is what decides whether unicode can be shown, and the first one which
+        // is set wins. If none are, then it's probably a minimal container, which is assumed to be fine.
+        let unicode = cfg!(windows)
+            || ["LC_ALL", "LC_CTYPE", "LANG"]
+                .into_iter()
+                .find_map(Self::var)
+                .map_or(true, |x| {
+                    let x = x.to_lowercase();
+                    x.contains("utf-8") || x.contains("utf8")
+                });
+
+        Self {
+            color: !dumb && Self::var("NO_COLOR").is_none(),
+            truecolor,
+            unicode: unicode && !dumb,
+            images: Self::images(&term),
+        }
+    }
+
+    /// Gets the protocol that the art is shown with, which is either from `--art-backend` or guessed.
+    pub fn protocol(&self) -> Option<Images> {
+        IMAGES.read().unwrap().or(self.images)
+    }
+
+    /// Gets the characters that the UI should be drawn with.
+    pub fn glyphs(&self) -> &'static Glyphs {
+        if self.unicode && !ASCII.load(Ordering::Relaxed) {
+            &Glyphs::UNICODE
+        } else {
+            &Glyphs::ASCII
+        }
+    }
+
+    /// Replaces `color` with something the terminal can actually show.
+    ///
+    /// RGB colors are swapped for the closest one in the 256 color palette
+    /// if truecolor isn't supported, and colors are dropped entirely if
+    /// the terminal doesn't support them at all.
+    fn color(&self, color: Option<Color>) -> Option<Color> {
+        /// Gets the closest level of the 6x6x6 color cube for one channel.
+        const fn level(value: u8) -> u8 {
+            match value {
+                0..=47 => 0,
+                48..=114 => 1,
+                _ => (value - 35) / 40,
+            }
+        }
+
+        match color? {
+            _ if !self.color => None,
+            Color::Rgb {
+                r: red,
+                g: green,
+                b: blue,
+            } if !self.truecolor => Some(Color::AnsiValue(
+                16 + 36 * level(red) + 6 * level(green) + level(blue),
+            )),
+            color => Some(color),
+        }
+    }
+
+    /// Degrades `theme` so that it only uses colors the terminal can show.
+    pub fn theme(&self, theme: Theme) -> Theme {
+        Theme {
+            border: self.color(theme.border),
+            accent: self.color(theme.accent),
+            bar: self.color(theme.bar),
+            ..theme
+        }
+    }
+}
+
+/// Saves the terminal's window title, so that it can be brought back with [`RestoreTitle`].
+///
+/// This uses the title stack from xterm, which most terminals support.
+/// The ones that don't just ignore it, and keep whatever title was set last.
+struct SaveTitle;
+
+impl Command for SaveTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1B[22;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        // The old Windows console doesn't have a title stack.
+        Ok(())
+    }
+}
+
+/// Restores the window title which was saved with [`SaveTitle`].
+struct RestoreTitle;
+
+impl Command for RestoreTitle {
+    fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+        f.write_str("\x1B[23;0t")
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sets the terminal's window title to show `track`, which is what taskbars & tmux display.
+pub fn title(track: &str) -> eyre::Result<()> {
+    // Track names come from the list, so control characters are dropped
+    // to stop them from ending the title early & messing with the terminal.
+    let track: String = track.chars().filter(|x| !x.is_control()).collect();
+    crossterm::execute!(stdout(), SetTitle(format!("{track} (lowfi)")))?;
+
+    Ok(())
+}
+
+/// Represents the terminal environment, and is used to properly
+/// initialize and clean up the terminal, including the window title.
+pub struct Environment {
+    /// Whether keyboard enhancements are enabled.
+    enhancement: bool,
+
+    /// Whether the terminal is in an alternate screen or not.
+    alternate: bool,
+
+    /// Whether mouse capture is enabled.
+    mouse: bool,
+}
+
+impl Environment {
+    /// This prepares the terminal, returning an [Environment] helpful
+    /// for cleaning up afterwards.
+    ///
+    /// If `mouse` is true, this also captures the mouse and finds out
+    /// where the window is going to be drawn.
+    pub fn ready(alternate: bool, mouse: bool) -> eyre::Result<Self> {
+        let mut lock = stdout().lock();
+
+        crossterm::execute!(lock, Hide, SaveTitle)?;
+
+        if alternate {
+            crossterm::execute!(lock, EnterAlternateScreen, MoveTo(0, 0))?;
+        }
+
+        terminal::enable_raw_mode()?;
+        let enhancement = terminal::supports_keyboard_enhancement()?;
+
+        if enhancement {
+            crossterm::execute!(
+                lock,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES)
+            )?;
+        }
+
+        if mouse {
+            crossterm::execute!(lock, EnableMouseCapture)?;
+
+            // This has to be done before listening for input, since otherwise the
+            // input listener would swallow the terminal's response.
+            ORIGIN.store(
+                cursor::position().map_or(0, |(_, row)| row),
+                Ordering::Relaxed,
+            );
+        }
+
+        Ok(Self {
+            enhancement,
+            alternate,
+            mouse,
+        })
+    }
+
+    /// Uses the information collected from initialization to safely close down
+    /// the terminal & restore it to it's previous state.
+    pub fn cleanup(&self) -> eyre::Result<()> {
+        // The art is above the window, so it has to go first.
+        art::hide()?;
+
+        let mut lock = stdout().lock();
+
+        if self.alternate {
+            crossterm::execute!(lock, LeaveAlternateScreen)?;
+        }
+
+        crossterm::execute!(lock, Clear(ClearType::FromCursorDown), Show, RestoreTitle)?;
+
+        if self.enhancement {
+            crossterm::execute!(lock, PopKeyboardEnhancementFlags)?;
+        }
+
+        if self.mouse {
+            crossterm::execute!(lock, DisableMouseCapture)?;
+        }
+
+        terminal::disable_raw_mode()?;
+
+        eprintln!("bye! :)");
+
+        Ok(())
+    }
+}
+
+impl Drop for Environment {
+    /// Just a wrapper for [`Environment::cleanup`] which ignores any errors thrown.
+    fn drop(&mut self) {
+        // Well, we're dropping it, so it doesn't really matter if there's an error.
+        let _ = self.cleanup();
+    }
+}
@@ -0,0 +1,134 @@
+//! Helpers for formatting numbers & durations in the UI,
+//! in a way that respects the user's locale where reasonable.
+
+use std::{env, time::Duration};
+
+use unicode_width::UnicodeWidthStr;
+
+/// Languages which put a space between a number and the percent sign, like `100 %`.
+const SPACED_PERCENT: [&str; 15] = [
+    "cs", "da", "de", "es", "fi", "fr", "it", "nb", "nn", "no", "pl", "ru", "sk", "sv", "uk",
+];
+
+/// Regions which use a 12-hour clock, like `3:05 PM`, rather than a 24-hour one.
+const TWELVE_HOUR: [&str; 9] = ["AU", "CA", "EG", "IN", "NZ", "PH", "PK", "SA", "US"];
+
+/// Formats a duration as `mm:ss`.
+///
+/// This is the same in every locale, since it's the way track
+/// lengths are written almost everywhere.
+pub fn duration(duration: &Duration) -> String {
+    let seconds = duration.as_secs() % 60;
+    let minutes = duration.as_secs() / 60;
+
+    format!("{minutes:02}:{seconds:02}")
+}
+
+/// The amount of bytes in a kilobyte, which is 1024 to match `--max-bandwidth`.
+const KILOBYTE: f64 = 1024.0;
+
+/// Formats a size in bytes, like `4.2 MB`.
+pub fn size(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = "B";
+
+    for next in ["KB", "MB", "GB"] {
+        if size < KILOBYTE {
+            break;
+        }
+
+        size /= KILOBYTE;
+        unit = next;
+    }
+
+    if unit == "B" {
+        format!("{bytes} B")
+    } else {
+        format!("{size:.1} {unit}")
+    }
+}
+
+/// The formatting conventions of a locale.
+#[derive(Clone, Copy, Debug)]
+pub struct Locale {
+    /// Whether there should be a (non-breaking) space before the percent sign.
+    spaced_percent: bool,
+
+    /// Whether the time of day uses a 12-hour clock.
+    twelve_hour: bool,
+}
+
+impl Locale {
+    /// Gets the language code of a locale string like `fr_FR.UTF-8`.
+    fn language(locale: &str) -> &str {
+        locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// Gets the region code of a locale string like `fr_FR.UTF-8`, if it has one.
+    fn region(locale: &str) -> Option<&str> {
+        locale.split(['.', '@']).next()?.split(['_', '-']).nth(1)
+    }
+
+    /// Creates a [Locale] from a locale string, like `de_DE.UTF-8` or `en-US`.
+    pub fn new(locale: &str) -> Self {
+        let language = Self::language(locale).to_lowercase();
+        let region = Self::region(locale).unwrap_or_default().to_uppercase();
+
+        Self {
+            spaced_percent: SPACED_PERCENT.contains(&language.as_str()),
+            // French speaking Canada uses a 24-hour clock, unlike the rest of the country.
+            twelve_hour: TWELVE_HOUR.contains(&region.as_str()) && language != "fr",
+        }
+    }
+
+    /// Detects the locale, either from `preferred` if it's specified,
+    /// or from the `LC_ALL`, `LC_NUMERIC` & `LANG` environment variables.
+    pub fn detect(preferred: Option<&str>) -> Self {
+        let detected = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|x| env::var(x).ok())
+            .find(|x| !x.is_empty());
+
+        preferred
+            .map(ToOwned::to_owned)
+            .or(detected)
+            .map_or_else(|| Self::new("C"), |x| Self::new(&x))
+    }
+
+    /// Formats a fraction from 0 to 1 as a percentage.
+    pub fn percentage(self, fraction: f32) -> String {
+        let percentage = (fraction * 100.0).round().abs();
+
+        if self.spaced_percent {
+            format!("{percentage}\u{a0}%")
+        } else {
+            format!("{percentage}%")
+        }
+    }
+
+    /// Formats the time of day, where `minutes` is how long it's been since midnight.
+    ///
+    /// This is either `hh:mm`, or like `3:05 PM` in locales which use a 12-hour clock.
+    pub fn time(self, minutes: u64) -> String {
+        let (hours, minutes) = ((minutes / 60) % 24, minutes % 60);
+        if !self.twelve_hour {
+            return format!("{hours:02}:{minutes:02}");
+        }
+
+        let period = if hours < 12 { "AM" } else { "PM" };
+        let hours = match hours % 12 {
+            0 => 12,
+            hours => hours,
+        };
+
+        format!("{hours}:{minutes:02} {period}")
+    }
+
+    /// The maximum width of a formatted percentage, which is useful for padding.
+    pub fn percentage_width(self) -> usize {
+        self.percentage(1.0).width()
+    }
+}
@@ -3,70 +3,233 @@
 
 use std::sync::atomic::Ordering;
 
-use crossterm::event::{self, EventStream, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::event::{
+    self, Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton,
+    MouseEvent, MouseEventKind,
+};
 use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc::Sender;
 
-use crate::player::Messages;
+#[cfg(feature = "equalizer")]
+use crate::player::equalizer::Change;
+use crate::player::{ambience, Messages};
 
-use super::VOLUME_TIMER;
+use super::{
+    components, fit, format::Locale, redraw, ARTIST, BORDERLESS, CRAMPED, DRAGGING, FAILING,
+    HISTORY, MINIMALIST, ORIGIN, REMAINING, RESIZED, SELECTED, TITLED, UPCOMING, VOLUME_TIMER,
+    WIDTH,
+};
+
+/// Works out what a key press should do, if anything.
+fn key(event: KeyEvent) -> Option<Messages> {
+    if event.kind == KeyEventKind::Release {
+        return None;
+    }
+
+    // Some keys do something different while the history panel is open.
+    if HISTORY.load(Ordering::Relaxed) {
+        match event.code {
+            KeyCode::Up => {
+                let _ = SELECTED.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| {
+                    Some(x.saturating_sub(1))
+                });
+                return None;
+            }
+            KeyCode::Down => {
+                SELECTED.fetch_add(1, Ordering::Relaxed);
+                return None;
+            }
+            KeyCode::Enter => {
+                HISTORY.store(false, Ordering::Relaxed);
+                return Some(Messages::Replay(SELECTED.load(Ordering::Relaxed)));
+            }
+            KeyCode::Esc => {
+                HISTORY.store(false, Ordering::Relaxed);
+                return None;
+            }
+            _ => (),
+        }
+    }
+
+    let messages = match event.code {
+        // Arrow key volume controls.
+        KeyCode::Up => Messages::ChangeVolume(0.1),
+        KeyCode::Right => Messages::ChangeVolume(0.01),
+        KeyCode::Down => Messages::ChangeVolume(-0.1),
+        KeyCode::Left => Messages::ChangeVolume(-0.01),
+        KeyCode::Char(character) => match character.to_ascii_lowercase() {
+            // Ctrl+C
+            'c' if event.modifiers == KeyModifiers::CONTROL => Messages::Quit,
+
+            // Quit
+            'q' => Messages::Quit,
+
+            // Skip/Next
+            's' | 'n' => Messages::Next,
+
+            // Pause
+            'p' => Messages::PlayPause,
+
+            // Bookmark
+            'b' => Messages::Bookmark,
+
+            // Equalizer toggle & bass boost
+            #[cfg(feature = "equalizer")]
+            'e' => Messages::Equalize(Change::Toggle),
+            #[cfg(feature = "equalizer")]
+            ']' => Messages::Equalize(Change::Bass(1.0)),
+            #[cfg(feature = "equalizer")]
+            '[' => Messages::Equalize(Change::Bass(-1.0)),
+
+            // Lofi filter
+            'f' => Messages::ToggleLofi,
+
+            // Ambience toggle & volume
+            'a' => Messages::Ambience(ambience::Change::Toggle),
+            '<' | ',' => Messages::Ambience(ambience::Change::Volume(-0.1)),
+            '>' | '.' => Messages::Ambience(ambience::Change::Volume(0.1)),
+
+            // UI toggles, which don't concern the audio server at all.
+            'm' => {
+                MINIMALIST.fetch_xor(true, Ordering::Relaxed);
+                return None;
+            }
+            'o' => {
+                BORDERLESS.fetch_xor(true, Ordering::Relaxed);
+                return None;
+            }
+            'r' => {
+                REMAINING.fetch_xor(true, Ordering::Relaxed);
+                return None;
+            }
+            'u' => {
+                UPCOMING.fetch_xor(true, Ordering::Relaxed);
+                return None;
+            }
+            'h' => {
+                HISTORY.fetch_xor(true, Ordering::Relaxed);
+                SELECTED.store(0, Ordering::Relaxed);
+                return None;
+            }
+
+            // Volume up & down
+            '+' | '=' => Messages::ChangeVolume(0.1),
+            '-' | '_' => Messages::ChangeVolume(-0.1),
+
+            _ => return None,
+        },
+        // Media keys
+        KeyCode::Media(media) => match media {
+            event::MediaKeyCode::Pause
+            | event::MediaKeyCode::Play
+            | event::MediaKeyCode::PlayPause => Messages::PlayPause,
+            event::MediaKeyCode::Stop => Messages::Pause,
+            event::MediaKeyCode::TrackNext => Messages::Next,
+            event::MediaKeyCode::LowerVolume => Messages::ChangeVolume(-0.1),
+            event::MediaKeyCode::RaiseVolume => Messages::ChangeVolume(0.1),
+            event::MediaKeyCode::MuteVolume => Messages::ChangeVolume(-1.0),
+            _ => return None,
+        },
+        _ => return None,
+    };
+
+    Some(messages)
+}
+
+/// Works out what clicking on `column` & `row` should do, based on where everything is drawn.
+///
+/// Clicking on the audio bar while it's shown also starts dragging it.
+fn click(column: u16, row: u16, locale: Locale) -> Option<Messages> {
+    let width = WIDTH.load(Ordering::Relaxed);
+    let border = usize::from(!BORDERLESS.load(Ordering::Relaxed));
+    let title = usize::from(TITLED.load(Ordering::Relaxed));
+    // Everything from the progress bar down moves along with the artist line, and
+    // subtracting it as well means that clicking the track name doesn't do anything.
+    let artist = usize::from(ARTIST.load(Ordering::Relaxed));
+    let row = usize::from(row.checked_sub(ORIGIN.load(Ordering::Relaxed))?)
+        .checked_sub(border + title + artist)?;
+    let column = usize::from(column).checked_sub(border * 2)?;
+
+    match row {
+        1 if VOLUME_TIMER.load(Ordering::Relaxed) > 0 => {
+            let bar = components::audio_width(width, locale);
+            let clicked = components::on_audio_bar(column, bar);
+            DRAGGING.store(clicked, Ordering::Relaxed);
+
+            clicked.then(|| Messages::SetVolume(components::volume(column, bar)))
+        }
+        1 => {
+            let bar = components::progress_width(width, REMAINING.load(Ordering::Relaxed));
+            components::seek(column, bar).map(Messages::Seek)
+        }
+        2 if !MINIMALIST.load(Ordering::Relaxed) && !FAILING.load(Ordering::Relaxed) => {
+            components::control(column, width)
+        }
+        _ => None,
+    }
+}
+
+/// Works out what a mouse event should do, if anything.
+fn mouse(event: MouseEvent, locale: Locale) -> Option<Messages> {
+    match event.kind {
+        // While the placeholder is shown, nothing on the window can be clicked.
+        MouseEventKind::Down(MouseButton::Left) if CRAMPED.load(Ordering::Relaxed) => None,
+        MouseEventKind::Down(MouseButton::Left) => click(event.column, event.row, locale),
+        // Once the audio bar is being dragged, only the column matters, so it
+        // doesn't stop if the mouse wanders off of the row a little bit.
+        MouseEventKind::Drag(MouseButton::Left) if DRAGGING.load(Ordering::Relaxed) => {
+            let border = usize::from(!BORDERLESS.load(Ordering::Relaxed));
+            let column = usize::from(event.column).saturating_sub(border * 2);
+            let bar = components::audio_width(WIDTH.load(Ordering::Relaxed), locale);
+
+            Some(Messages::SetVolume(components::volume(column, bar)))
+        }
+        MouseEventKind::Up(MouseButton::Left) => {
+            DRAGGING.store(false, Ordering::Relaxed);
+            None
+        }
+        MouseEventKind::ScrollUp => Some(Messages::ChangeVolume(0.05)),
+        MouseEventKind::ScrollDown => Some(Messages::ChangeVolume(-0.05)),
+        _ => None,
+    }
+}
 
 /// Starts the listener to recieve input from the terminal for various events.
-pub async fn listen(sender: Sender<Messages>) -> eyre::Result<()> {
+///
+/// `locale` is needed to work out where the audio bar is, and if `auto_width`
+/// is set, then the window is resized to fit whenever the terminal is.
+pub async fn listen(
+    sender: Sender<Messages>,
+    locale: Locale,
+    auto_width: bool,
+) -> eyre::Result<()> {
     let mut reader = EventStream::new();
 
     loop {
-        let Some(Ok(event::Event::Key(event))) = reader.next().fuse().await else {
-            continue;
+        let messages = match reader.next().fuse().await {
+            Some(Ok(Event::Key(event))) => key(event),
+            Some(Ok(Event::Mouse(event))) => mouse(event, locale),
+            Some(Ok(Event::Resize(columns, _))) => {
+                if auto_width {
+                    WIDTH.store(fit(columns), Ordering::Relaxed);
+                }
+
+                RESIZED.store(true, Ordering::Relaxed);
+                None
+            }
+            _ => None,
         };
 
-        if event.kind == KeyEventKind::Release {
-            continue;
-        }
+        // Whatever happened probably changed something in the window, even if it's only in the UI.
+        redraw();
 
-        let messages = match event.code {
-            // Arrow key volume controls.
-            KeyCode::Up => Messages::ChangeVolume(0.1),
-            KeyCode::Right => Messages::ChangeVolume(0.01),
-            KeyCode::Down => Messages::ChangeVolume(-0.1),
-            KeyCode::Left => Messages::ChangeVolume(-0.01),
-            KeyCode::Char(character) => match character.to_ascii_lowercase() {
-                // Ctrl+C
-                'c' if event.modifiers == KeyModifiers::CONTROL => Messages::Quit,
-
-                // Quit
-                'q' => Messages::Quit,
-
-                // Skip/Next
-                's' | 'n' => Messages::Next,
-
-                // Pause
-                'p' => Messages::PlayPause,
-
-                // Volume up & down
-                '+' | '=' => Messages::ChangeVolume(0.1),
-                '-' | '_' => Messages::ChangeVolume(-0.1),
-
-                _ => continue,
-            },
-            // Media keys
-            KeyCode::Media(media) => match media {
-                event::MediaKeyCode::Pause
-                | event::MediaKeyCode::Play
-                | event::MediaKeyCode::PlayPause => Messages::PlayPause,
-                event::MediaKeyCode::Stop => Messages::Pause,
-                event::MediaKeyCode::TrackNext => Messages::Next,
-                event::MediaKeyCode::LowerVolume => Messages::ChangeVolume(-0.1),
-                event::MediaKeyCode::RaiseVolume => Messages::ChangeVolume(0.1),
-                event::MediaKeyCode::MuteVolume => Messages::ChangeVolume(-1.0),
-                _ => continue,
-            },
-            _ => continue,
+        let Some(messages) = messages else {
+            continue;
         };
 
         // If it's modifying the volume, then we'll set the `VOLUME_TIMER` to 1
         // so that the UI thread will know that it should show the audio bar.
-        if let Messages::ChangeVolume(_) = messages {
+        if let Messages::ChangeVolume(_) | Messages::SetVolume(_) = messages {
             VOLUME_TIMER.store(1, Ordering::Relaxed);
         }
 
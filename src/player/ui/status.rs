@@ -0,0 +1,94 @@
+//! Contains `--status-line`, which takes the place of the window and prints a line of JSON
+//! whenever something changes, in the format that waybar's custom modules expect.
+
+use std::sync::Arc;
+
+use serde::Serialize;
+
+use crate::{
+    player::{snapshot::Snapshot, Player},
+    Args,
+};
+
+use super::{format::Locale, title::Template, wait};
+
+/// A single line of output, which waybar reads as a custom module.
+///
+/// i3status-rust & polybar can read it too, with a little help from `jq`.
+#[derive(PartialEq, Eq, Serialize)]
+struct Line {
+    /// What's shown in the bar.
+    text: String,
+
+    /// What's shown when hovering over the module.
+    tooltip: String,
+
+    /// Either `loading`, `playing` or `paused`, which can be styled in waybar's CSS.
+    class: &'static str,
+
+    /// The same as `class`, which waybar uses to pick from the `format-icons`.
+    alt: &'static str,
+}
+
+/// Prints a [`Line`] whenever something changes, instead of drawing the window.
+pub struct Printer {
+    /// The template from `--title`, which replaces the track name if it's set.
+    title: Option<Template>,
+
+    /// How the volume is formatted in the tooltip, as well as in the `title`.
+    locale: Locale,
+}
+
+impl Printer {
+    /// Parses `--title`, so that mistakes are caught before lowfi starts playing.
+    pub fn new(args: &Args) -> eyre::Result<Self> {
+        Ok(Self {
+            title: args.title.as_deref().map(Template::parse).transpose()?,
+            locale: Locale::detect(args.locale.as_deref()),
+        })
+    }
+
+    /// Works out the [`Line`] for a [`Snapshot`] of the player.
+    fn line(&self, snapshot: &Snapshot) -> Line {
+        let class = match (&snapshot.track, snapshot.paused) {
+            (None, _) => "loading",
+            (Some(_), true) => "paused",
+            (Some(_), false) => "playing",
+        };
+
+        let text = match (&self.title, &snapshot.track, &snapshot.artist) {
+            (Some(title), _, _) => title.render(snapshot, self.locale),
+            (None, Some(track), Some(artist)) => format!("{artist} - {track}"),
+            (None, Some(track), None) => track.clone(),
+            (None, None, _) => "loading".to_owned(),
+        };
+
+        let tooltip = format!(
+            "{} · {}",
+            snapshot.list,
+            self.locale.percentage(snapshot.volume)
+        );
+
+        Line {
+            text,
+            tooltip,
+            class,
+            alt: class,
+        }
+    }
+
+    /// Prints a [`Line`] straight away, and then again each time it changes.
+    pub async fn run(self, player: Arc<Player>) -> eyre::Result<()> {
+        let mut last = None;
+
+        loop {
+            let line = self.line(&player.snapshot().await);
+            if last.as_ref() != Some(&line) {
+                println!("{}", serde_json::to_string(&line)?);
+                last = Some(line);
+            }
+
+            wait(false).await;
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Contains the [`Theme`], which controls the colors & styles used by the UI,
+//! as well as the built in [`Preset`] themes which can be picked with `--theme`.
+
+use std::fmt::Display;
+
+use clap::ValueEnum;
+use crossterm::style::{Color, Stylize};
+use serde::Deserialize;
+
+/// The colors & styles used to draw the UI.
+///
+/// This can be set in the `[theme]` section of `config.toml`, where colors are
+/// either names like `dark_cyan`, or hex codes like `#7aa2f7`.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// The color of the window's borders.
+    pub border: Option<Color>,
+
+    /// The color of the key hints in the controls bar, like `[s]`.
+    pub accent: Option<Color>,
+
+    /// The color of the filled in part of the progress & volume bars.
+    pub bar: Option<Color>,
+
+    /// Whether the track name & key hints are bold.
+    pub bold: bool,
+
+    /// Whether less important text, like the rest of the controls, is dimmed.
+    pub dim: bool,
+}
+
+impl Default for Theme {
+    /// The plain, monochrome look that lowfi has always had.
+    fn default() -> Self {
+        Preset::Default.theme()
+    }
+}
+
+impl Theme {
+    /// Styles `text` with `color` if there is one, and makes it bold if `bold` is set.
+    fn paint(text: impl Display, color: Option<Color>, bold: bool) -> String {
+        let mut styled = text.to_string().stylize();
+        if let Some(color) = color {
+            styled = styled.with(color);
+        }
+
+        if bold {
+            styled = styled.bold();
+        }
+
+        styled.to_string()
+    }
+
+    /// Styles part of the window's borders.
+    pub fn border(&self, text: impl Display) -> String {
+        Self::paint(text, self.border, false)
+    }
+
+    /// Styles a key hint, like `[s]`.
+    pub fn accent(&self, text: impl Display) -> String {
+        Self::paint(text, self.accent, self.bold)
+    }
+
+    /// Styles the filled in part of a bar.
+    pub fn bar(&self, text: impl Display) -> String {
+        Self::paint(text, self.bar, false)
+    }
+
+    /// Styles important text, like the name of the track.
+    pub fn strong(&self, text: impl Display) -> String {
+        Self::paint(text, None, self.bold)
+    }
+
+    /// Styles less important text, which is dimmed if `dim` is set.
+    pub fn faint(&self, text: impl Display) -> String {
+        if self.dim {
+            text.to_string().dim().to_string()
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// The themes which are built into lowfi.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Preset {
+    /// Monochrome, with bold text.
+    Default,
+
+    /// No colors or styles at all.
+    Plain,
+
+    /// Blues & cyans.
+    Ocean,
+
+    /// Greens.
+    Forest,
+
+    /// Magentas & yellows.
+    Sunset,
+}
+
+impl Preset {
+    /// Gets the actual [`Theme`] for the preset.
+    pub const fn theme(self) -> Theme {
+        let (border, accent, bar) = match self {
+            Self::Default | Self::Plain => (None, None, None),
+            Self::Ocean => (
+                Some(Color::DarkBlue),
+                Some(Color::Cyan),
+                Some(Color::DarkCyan),
+            ),
+            Self::Forest => (
+                Some(Color::DarkGreen),
+                Some(Color::Green),
+                Some(Color::DarkGreen),
+            ),
+            Self::Sunset => (
+                Some(Color::DarkMagenta),
+                Some(Color::Yellow),
+                Some(Color::Magenta),
+            ),
+        };
+
+        Theme {
+            border,
+            accent,
+            bar,
+            bold: !matches!(self, Self::Plain),
+            dim: matches!(self, Self::Ocean | Self::Forest | Self::Sunset),
+        }
+    }
+}
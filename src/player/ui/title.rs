@@ -0,0 +1,228 @@
+//! Contains the [`Template`] for `--title`, which is rendered as an extra line at the top of the window.
+//!
+//! The same templates are used for `lowfi now-playing --format`.
+
+use std::{
+    mem,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::eyre;
+
+use crate::player::snapshot::Snapshot;
+
+use super::format::{self, Locale};
+
+/// A variable which can be used in a [`Template`], like `{time}`.
+#[derive(Clone, Copy)]
+enum Variable {
+    /// The name of the track list.
+    List,
+
+    /// The current time of day, as `hh:mm` or with AM & PM depending on the locale.
+    Time,
+
+    /// How many tracks have been played during this session.
+    Played,
+
+    /// The volume, as a percentage.
+    Volume,
+
+    /// The name of the current track, or `loading` if there isn't one.
+    Track,
+
+    /// The artist of the current track, if its tags have one.
+    Artist,
+
+    /// The album of the current track, if its tags have one.
+    Album,
+
+    /// The codec of the current track, like `MP3`, if it's known.
+    Codec,
+
+    /// How long the current track has played for, as `mm:ss`.
+    Elapsed,
+
+    /// How long the current track is, as `mm:ss`, or `--:--` if that isn't known.
+    Duration,
+}
+
+impl Variable {
+    /// Every variable, used to list them in errors.
+    const ALL: [Self; 10] = [
+        Self::List,
+        Self::Time,
+        Self::Played,
+        Self::Volume,
+        Self::Track,
+        Self::Artist,
+        Self::Album,
+        Self::Codec,
+        Self::Elapsed,
+        Self::Duration,
+    ];
+
+    /// The name of the variable, which goes in between the braces.
+    const fn name(self) -> &'static str {
+        match self {
+            Self::List => "list",
+            Self::Time => "time",
+            Self::Played => "played",
+            Self::Volume => "volume",
+            Self::Track => "track",
+            Self::Artist => "artist",
+            Self::Album => "album",
+            Self::Codec => "codec",
+            Self::Elapsed => "elapsed",
+            Self::Duration => "duration",
+        }
+    }
+
+    /// Finds the variable called `name`, which can also be `title` for the track.
+    fn find(name: &str) -> Option<Self> {
+        if name == "title" {
+            return Some(Self::Track);
+        }
+
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+/// Part of a [`Template`].
+enum Part {
+    /// Text which is shown as is.
+    Text(String),
+
+    /// A [`Variable`] which is filled in each frame.
+    Variable(Variable),
+}
+
+/// A template like `{list} · {time}`, which is parsed once and then rendered every frame.
+///
+/// Braces can be written as `{{` & `}}` if they should be shown as is.
+pub struct Template(Vec<Part>);
+
+impl Template {
+    /// Parses `template`, failing if it has unknown variables or unmatched braces.
+    pub fn parse(template: &str) -> eyre::Result<Self> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = template.chars();
+
+        while let Some(char) = chars.next() {
+            match char {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let (name, rest) = chars
+                        .as_str()
+                        .split_once('}')
+                        .ok_or_else(|| eyre!("unclosed brace in the template"))?;
+
+                    let variable = Variable::find(name).ok_or_else(|| {
+                        let names: Vec<String> = Variable::ALL
+                            .into_iter()
+                            .map(|x| format!("{{{}}}", x.name()))
+                            .collect();
+
+                        eyre!(
+                            "unknown variable {{{name}}}, try one of {}",
+                            names.join(", ")
+                        )
+                    })?;
+
+                    if !text.is_empty() {
+                        parts.push(Part::Text(mem::take(&mut text)));
+                    }
+
+                    parts.push(Part::Variable(variable));
+                    chars = rest.chars();
+                }
+                '}' => return Err(eyre!("unmatched brace in the template")),
+                _ => text.push(char),
+            }
+        }
+
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+
+        Ok(Self(parts))
+    }
+
+    /// Fills in the variables from a [`Snapshot`] of the player.
+    pub fn render(&self, snapshot: &Snapshot, locale: Locale) -> String {
+        let mut rendered = String::new();
+
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Variable(Variable::List) => rendered.push_str(&snapshot.list),
+                Part::Variable(Variable::Time) => rendered.push_str(&locale.time(time())),
+                Part::Variable(Variable::Played) => {
+                    rendered.push_str(&snapshot.played.to_string());
+                }
+                Part::Variable(Variable::Volume) => {
+                    rendered.push_str(&locale.percentage(snapshot.volume));
+                }
+                Part::Variable(Variable::Track) => {
+                    rendered.push_str(snapshot.track.as_deref().unwrap_or("loading"));
+                }
+                Part::Variable(Variable::Artist) => {
+                    rendered.push_str(snapshot.artist.as_deref().unwrap_or_default());
+                }
+                Part::Variable(Variable::Album) => {
+                    rendered.push_str(snapshot.album.as_deref().unwrap_or_default());
+                }
+                Part::Variable(Variable::Codec) => {
+                    rendered.push_str(snapshot.codec.as_deref().unwrap_or_default());
+                }
+                Part::Variable(Variable::Elapsed) => {
+                    rendered.push_str(&format::duration(&snapshot.elapsed));
+                }
+                Part::Variable(Variable::Duration) => match &snapshot.duration {
+                    Some(duration) => rendered.push_str(&format::duration(duration)),
+                    None => rendered.push_str("--:--"),
+                },
+            }
+        }
+
+        rendered
+    }
+}
+
+/// Gets the current time of day, in minutes since midnight.
+///
+/// This is in local time on unix, and UTC elsewhere, since there isn't
+/// a way to get the timezone in the standard library.
+fn time() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+
+    #[cfg(unix)]
+    let now = {
+        use std::ptr;
+
+        let time = libc::time_t::try_from(now).unwrap_or_default();
+        // SAFETY: `tm` is just a plain struct of numbers, so all zeroes is valid.
+        let mut local: libc::tm = unsafe { mem::zeroed() };
+
+        // SAFETY: Both pointers are valid for the whole call.
+        if unsafe { libc::localtime_r(ptr::addr_of!(time), ptr::addr_of_mut!(local)) }.is_null() {
+            now
+        } else {
+            // The offset is only an `i32` on some 32-bit platforms, but it's already an `i64` on most.
+            #[allow(clippy::useless_conversion)]
+            now.saturating_add_signed(i64::from(local.tm_gmtoff))
+        }
+    };
+
+    now / 60
+}
@@ -0,0 +1,199 @@
+//! Contains the [`Visualizer`], which keeps the most recently played samples
+//! around so that the UI can draw a small spectrum of them with `--visualizer`.
+
+use std::{
+    f32::consts::PI,
+    iter,
+    sync::{
+        atomic::{AtomicU32, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Sample, Source};
+
+/// How many samples the spectrum is worked out from, which is about 20ms of audio.
+const WINDOW: usize = 1024;
+
+/// The lowest frequency shown in the spectrum, in Hz.
+const LOWEST: f32 = 40.0;
+
+/// The highest frequency shown in the spectrum, in Hz.
+const HIGHEST: f32 = 16000.0;
+
+/// How quiet a band has to be, in decibels, to not show up at all.
+const FLOOR: f32 = -60.0;
+
+/// Stores the last [`WINDOW`] samples that were played, mixed down to mono.
+///
+/// The samples are stored as the bits of an [`f32`] in atomics, so that the audio
+/// thread never has to wait on a lock. The UI might read a few samples while they're
+/// being overwritten, but that's not going to be noticeable in a visualizer.
+pub struct Visualizer {
+    /// The samples themselves, which are a ring buffer.
+    samples: Box<[AtomicU32]>,
+
+    /// Where the next sample will be written to in `samples`.
+    position: AtomicUsize,
+
+    /// The sample rate of the track that's being played.
+    rate: AtomicU32,
+}
+
+impl Visualizer {
+    /// Creates a new, silent [`Visualizer`].
+    pub fn new() -> Self {
+        Self {
+            samples: iter::repeat_with(|| AtomicU32::new(0))
+                .take(WINDOW)
+                .collect(),
+            position: AtomicUsize::new(0),
+            rate: AtomicU32::new(44100),
+        }
+    }
+
+    /// Wraps `source` so that the samples are copied into the [`Visualizer`] as they're played.
+    pub fn tap<S: Source>(self: &Arc<Self>, source: S) -> Tap<S>
+    where
+        S::Item: Sample,
+    {
+        self.rate.store(source.sample_rate(), Ordering::Relaxed);
+
+        Tap {
+            source,
+            visualizer: Arc::clone(self),
+            channel: 0,
+            sum: 0.0,
+        }
+    }
+
+    /// Adds a single mono sample to the ring buffer.
+    fn push(&self, sample: f32) {
+        let position = self.position.fetch_add(1, Ordering::Relaxed) % WINDOW;
+        self.samples[position].store(sample.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Gets how loud each of `count` frequency bands is, from 0 to 1.
+    ///
+    /// The bands are spaced out logarithmically between [`LOWEST`] & [`HIGHEST`],
+    /// which is closer to how we actually hear, and each one is worked out with the
+    /// Goertzel algorithm. That's just a single bin of a DFT, which is much cheaper
+    /// than a full FFT when only a handful of bins are needed.
+    pub fn bands(&self, count: usize) -> Vec<f32> {
+        let start = self.position.load(Ordering::Relaxed);
+        let rate = self.rate.load(Ordering::Relaxed) as f32;
+
+        // Oldest first, with a Hann window to stop the edges from smearing into every band.
+        let samples: Vec<f32> = (0..WINDOW)
+            .map(|i| {
+                let sample =
+                    f32::from_bits(self.samples[(start + i) % WINDOW].load(Ordering::Relaxed));
+                let hann =
+                    (-0.5f32).mul_add((2.0 * PI * i as f32 / (WINDOW - 1) as f32).cos(), 0.5);
+
+                sample * hann
+            })
+            .collect();
+
+        let highest = HIGHEST.min(rate / 2.0);
+        (0..count)
+            .map(|band| {
+                let position = band as f32 / count.saturating_sub(1).max(1) as f32;
+                let frequency = LOWEST * (highest / LOWEST).powf(position);
+                let power = power(&samples, frequency, rate);
+
+                // A full scale sine wave comes out as 1, since the Hann window halves the amplitude.
+                let magnitude = power.sqrt() / (WINDOW as f32 / 4.0);
+                let decibels = 20.0 * magnitude.max(f32::EPSILON).log10();
+
+                (1.0 - decibels / FLOOR).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+/// Works out how much of `frequency` there is in `samples` using the Goertzel algorithm,
+/// as the squared magnitude of that one bin of a DFT.
+pub fn power(samples: &[f32], frequency: f32, rate: f32) -> f32 {
+    let coefficient = 2.0 * (2.0 * PI * frequency / rate).cos();
+
+    let (previous, last) = samples.iter().fold((0.0, 0.0), |(previous, last), x| {
+        (last, x + coefficient * last - previous)
+    });
+
+    coefficient
+        .mul_add(-last * previous, last.mul_add(last, previous * previous))
+        .max(0.0)
+}
+
+/// A [`Source`] which copies the samples of another one into a [`Visualizer`].
+pub struct Tap<S> {
+    /// The source that's actually being played.
+    source: S,
+
+    /// Where the samples are copied to.
+    visualizer: Arc<Visualizer>,
+
+    /// Which channel the next sample belongs to.
+    channel: u16,
+
+    /// The sum of the samples in the current frame, which are averaged into one.
+    sum: f32,
+}
+
+impl<S: Source> Iterator for Tap<S>
+where
+    S::Item: Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sample = self.source.next()?;
+        let channels = self.source.channels().max(1);
+
+        self.sum += sample.to_f32();
+        self.channel += 1;
+
+        if self.channel >= channels {
+            self.visualizer.push(self.sum / f32::from(channels));
+            self.channel = 0;
+            self.sum = 0.0;
+        }
+
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.source.size_hint()
+    }
+}
+
+impl<S: Source> Source for Tap<S>
+where
+    S::Item: Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.source.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // Seeking always lands at the start of a frame.
+        self.channel = 0;
+        self.sum = 0.0;
+
+        self.source.try_seek(pos)
+    }
+}
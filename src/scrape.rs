@@ -3,88 +3,472 @@
 //! This command is completely optional, and as such isn't subject to the same
 //! quality standards as the rest of the codebase.
 
-use futures::{stream::FuturesOrdered, StreamExt};
-use lazy_static::lazy_static;
-use scraper::{Html, Selector};
+use std::{collections::HashSet, path::PathBuf, time::Duration};
 
-const BASE_URL: &str = "https://lofigirl.com/wp-content/uploads/";
+use clap::{
+    builder::{PossibleValue, PossibleValuesParser},
+    ValueEnum,
+};
+use eyre::eyre;
+use futures::{future::BoxFuture, stream, StreamExt};
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::{fs, time::sleep};
 
-lazy_static! {
-    static ref SELECTOR: Selector = Selector::parse("html > body > pre > a").unwrap();
+use crate::{play, tracks::list::List};
+
+mod archive;
+mod index;
+mod jamendo;
+mod lofigirl;
+mod radio;
+
+/// A place that tracks can be scraped from.
+///
+/// Each one lives in its own module, and is added to [`SCRAPERS`],
+/// which is where the sources on the command line come from.
+pub trait Scraper: Sync {
+    /// The name of the source on the command line, like `jamendo`.
+    fn name(&self) -> &'static str;
+
+    /// A short description of the source, which is shown in `lowfi help scrape`.
+    fn about(&self) -> &'static str;
+
+    /// Where the tracks come from, unless it's given on the command line.
+    fn url(&self) -> Option<&'static str>;
+
+    /// Scrapes the tracks, using whichever `options` the source needs,
+    /// with the concurrency & delay from `pace`.
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>>;
+}
+
+/// Every source that can be scraped, where the first one is the default.
+const SCRAPERS: &[&dyn Scraper] = &[
+    &lofigirl::Lofigirl,
+    &jamendo::Jamendo,
+    &radio::Radio,
+    &index::Index,
+    &archive::Archive,
+];
+
+/// Makes the parser for the `source` argument, so that `lowfi help scrape` lists every source.
+fn sources() -> PossibleValuesParser {
+    PossibleValuesParser::new(SCRAPERS.iter().map(|x| {
+        let help = match x.url() {
+            Some(url) => format!("{} ({url})", x.about()),
+            None => x.about().to_owned(),
+        };
+
+        PossibleValue::new(x.name()).help(help)
+    }))
+}
+
+/// How the scraped tracks are printed.
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum Format {
+    /// A plain text list, with `path!name` lines.
+    #[default]
+    Text,
+
+    /// A structured JSON list, where each track also has its metadata.
+    Json,
+}
+
+/// A month, like `2023-06`, which is used for `--since`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Month {
+    /// The year.
+    year: u32,
+
+    /// The month, from 1 to 12.
+    month: u32,
+}
+
+impl Month {
+    /// Parses a month written like `2023-06`, for `--since`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let (year, month) = text
+            .split_once('-')
+            .ok_or_else(|| format!("expected a month like 2023-06, not {text:?}"))?;
+
+        let year = year.parse().map_err(|_| format!("{year:?} isn't a year"))?;
+        let month = month
+            .parse()
+            .ok()
+            .filter(|x| (1..=12).contains(x))
+            .ok_or_else(|| format!("{month:?} isn't a month"))?;
+
+        Ok(Self { year, month })
+    }
+}
+
+/// How quickly to scrape, which is a tradeoff between speed & being polite to the server.
+#[derive(Clone, Copy)]
+pub struct Pace {
+    /// How many requests can be made at the same time, for sources which do several at once.
+    concurrency: usize,
+
+    /// How long to wait before each request.
+    delay: Duration,
 }
 
-async fn parse(path: &str) -> eyre::Result<Vec<String>> {
-    let response = reqwest::get(format!("{}{}", BASE_URL, path)).await?;
-    let document = response.text().await?;
+/// The options of the `scrape` command.
+#[derive(clap::Args)]
+pub struct Options {
+    /// Where to scrape tracks from.
+    #[clap(default_value = SCRAPERS[0].name(), value_parser = sources())]
+    source: String,
+
+    /// The URL of the directory listing to crawl for `index`,
+    /// or the identifier of the item or collection for `archive`.
+    target: Option<String>,
+
+    /// Whether to print a plain text list, or a JSON list with each track's metadata.
+    #[clap(long, value_enum, default_value_t)]
+    format: Format,
+
+    /// The file extension to search for, defaults to mp3.
+    #[clap(long, short, visible_alias = "ext", default_value = "mp3")]
+    extension: String,
+
+    /// The CSS selector for the links in a directory listing, for `index`.
+    #[clap(long, default_value = "pre > a")]
+    selector: String,
+
+    /// Whether to include the full HTTP URL or just the distinguishing part.
+    #[clap(long, short)]
+    include_full: bool,
+
+    /// The client ID to use Jamendo's API with, which you can get for free.
+    #[clap(long)]
+    client_id: Option<String>,
+
+    /// The tags to search Jamendo or radio-browser for, with a `+` in between each one.
+    #[clap(long, default_value = "lofi")]
+    tags: String,
+
+    /// Only scrapes tracks from this month onwards, like `2023-06`.
+    /// This doesn't work with radio-browser, since stations aren't released.
+    #[clap(long, value_parser = Month::parse)]
+    since: Option<Month>,
+
+    /// Adds the tracks which aren't already in this list to the end of it,
+    /// instead of printing them. The list is made if it doesn't exist yet.
+    #[clap(long, value_name = "FILE")]
+    merge_into: Option<PathBuf>,
+
+    /// Saves the list in the data directory with this name, instead of printing it,
+    /// so that it can be played with `--tracks <name>` straight away.
+    #[clap(long, value_name = "NAME", conflicts_with = "merge_into")]
+    save: Option<String>,
+
+    /// How many requests to make at the same time.
+    /// Only the Lofi Girl file server, directory listings & archive.org collections
+    /// are scraped concurrently.
+    #[clap(long, default_value_t = 8, value_parser = clap::value_parser!(u8).range(1..))]
+    concurrency: u8,
+
+    /// How long to wait before each request, in milliseconds.
+    #[clap(long, default_value_t = 0)]
+    delay_ms: u64,
+
+    /// Checks every track with a HEAD request, and leaves out the ones which
+    /// are missing or stuck in a redirect loop.
+    #[clap(long)]
+    verify: bool,
+}
+
+/// A single track that was scraped, with whatever is known about it.
+#[derive(Serialize)]
+pub struct Record {
+    /// The path of the track, which works the same as in any other list.
+    path: String,
+
+    /// The display name, if it shouldn't be worked out from the path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+
+    /// The title of the track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+
+    /// The artist of the track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    artist: Option<String>,
+
+    /// The album of the track.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    album: Option<String>,
+
+    /// A URL to the cover art.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    art: Option<String>,
+}
+
+impl Record {
+    /// Creates a [`Record`] with nothing known about it besides the `path`.
+    const fn new(path: String) -> Self {
+        Self {
+            path,
+            name: None,
+            title: None,
+            artist: None,
+            album: None,
+            art: None,
+        }
+    }
+
+    /// Formats the record as a line of a plain text list.
+    fn line(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{}!{name}", self.path),
+            None => self.path.clone(),
+        }
+    }
+}
 
-    let html = Html::parse_document(&document);
-    Ok(html
-        .select(&SELECTOR)
-        .skip(5)
-        .map(|x| String::from(x.attr("href").unwrap()))
-        .collect())
+/// Gets the full path of a track at `path` in a list with `base`, like lowfi does.
+fn full(base: &str, path: &str) -> String {
+    if path.contains("://") {
+        path.to_owned()
+    } else {
+        format!("{base}{path}")
+    }
 }
 
-/// This function basically just scans the entire file server, and returns a list of paths to mp3 files.
+/// Whether the track at `url` is still there, which is checked with a `HEAD` request.
 ///
-/// It's a bit hacky, and basically works by checking all of the years, then months, and then all of the files.
-/// This is done as a way to avoid recursion, since async rust really hates recursive functions.
-async fn scan(extension: &str, include_full: bool) -> eyre::Result<Vec<String>> {
-    let extension = &format!(".{}", extension);
+/// Only missing tracks & redirect loops count as dead, since some servers don't
+/// allow `HEAD` requests at all, and those tracks might still play just fine.
+async fn alive(client: &Client, url: &str) -> bool {
+    match client.head(url).send().await {
+        Ok(response) => !matches!(response.status(), StatusCode::NOT_FOUND | StatusCode::GONE),
+        Err(error) => !error.is_redirect(),
+    }
+}
 
-    let items = parse("").await?;
+/// Everything that was scraped from a [`Scraper`].
+pub struct Scraped {
+    /// The base URL of the list.
+    base: String,
 
-    let mut years: Vec<u32> = items
-        .iter()
-        .filter_map(|x| {
-            let year = x.strip_suffix("/")?;
-            year.parse().ok()
+    /// A comment to put under the base of plain text lists, like where the tracks came from.
+    comment: Option<String>,
+
+    /// The tracks themselves.
+    records: Vec<Record>,
+}
+
+impl Scraped {
+    /// Formats the whole list in `format`.
+    ///
+    /// JSON lists can be played as is, since the extra metadata is ignored by lowfi.
+    fn render(&self, format: Format) -> eyre::Result<String> {
+        Ok(match format {
+            Format::Text => {
+                let mut text = format!("{}\n", self.base);
+                if let Some(comment) = &self.comment {
+                    text.push_str(&format!("# {comment}\n"));
+                }
+
+                for record in &self.records {
+                    text.push_str(&record.line());
+                    text.push('\n');
+                }
+
+                text
+            }
+            Format::Json => {
+                let list = json!({ "base": self.base, "tracks": self.records });
+                serde_json::to_string_pretty(&list)? + "\n"
+            }
         })
-        .collect();
-
-    years.sort();
-
-    // A little bit of async to run all of the months concurrently.
-    let mut futures = FuturesOrdered::new();
-
-    for year in years {
-        let months = parse(&year.to_string()).await?;
-
-        for month in months {
-            futures.push_back(async move {
-                let path = format!("{}/{}", year, month);
-
-                let items = parse(&path).await.unwrap();
-                items
-                    .into_iter()
-                    .filter_map(|x| {
-                        if x.ends_with(extension) {
-                            if include_full {
-                                Some(format!("{BASE_URL}{path}{x}"))
-                            } else {
-                                Some(format!("{path}{x}"))
-                            }
-                        } else {
-                            None
-                        }
-                    })
-                    .collect::<Vec<String>>()
-            });
+    }
+
+    /// Checks every record with [`alive`], with the concurrency & delay from `pace`,
+    /// and removes the dead ones, returning how many there were.
+    async fn verify(&mut self, pace: Pace) -> usize {
+        let client = Client::new();
+        let checks = self.records.iter().map(|record| {
+            // This is the URL that lowfi would actually request.
+            let url = if record.path.contains("://") {
+                record.path.clone()
+            } else {
+                format!("{}{}", self.base, List::encode(&record.path))
+            };
+
+            let client = &client;
+            async move {
+                sleep(pace.delay).await;
+                alive(client, &url).await
+            }
+        });
+
+        let alive: Vec<bool> = stream::iter(checks)
+            .buffered(pace.concurrency)
+            .collect()
+            .await;
+
+        let before = self.records.len();
+        let mut alive = alive.into_iter();
+        self.records.retain(|_| alive.next().unwrap_or(true));
+
+        before - self.records.len()
+    }
+
+    /// Adds the records which aren't already in the list at `path` to the end of it,
+    /// keeping everything else in the list the way it was.
+    ///
+    /// Whether it's a JSON or plain text list depends on the extension, like with `--tracks`.
+    /// If the list has a different base, then the new tracks are written as full URLs.
+    ///
+    /// This returns how many tracks were added.
+    async fn merge(mut self, path: &PathBuf) -> eyre::Result<usize> {
+        let json = path.extension().is_some_and(|x| x == "json");
+        if !path.exists() {
+            let format = if json { Format::Json } else { Format::Text };
+            play::write(path, self.render(format)?).await?;
+            return Ok(self.records.len());
         }
+
+        let contents = fs::read_to_string(path).await?;
+        let mut list: Option<Value> = if json {
+            Some(serde_json::from_str(&contents)?)
+        } else {
+            None
+        };
+
+        // The base & every track that's already in the list, as full paths.
+        let (base, known): (String, HashSet<String>) = match &list {
+            Some(list) => {
+                let base = list["base"].as_str().unwrap_or_default().to_owned();
+                let tracks = list["tracks"].as_array().into_iter().flatten();
+                let known = tracks
+                    .filter_map(|x| x.as_str().or_else(|| x["path"].as_str()))
+                    .map(|x| full(&base, x))
+                    .collect();
+
+                (base, known)
+            }
+            None => {
+                let mut lines = contents.lines();
+                let base = lines.next().unwrap_or_default().trim().to_owned();
+                let known = lines
+                    .filter(|x| !List::is_directive(x) && !x.trim().is_empty())
+                    .map(|x| x.trim_start().trim_start_matches(['#', ';']))
+                    .map(|x| full(&base, List::split_entry(x).0))
+                    .collect();
+
+                (base, known)
+            }
+        };
+
+        self.records
+            .retain(|x| !known.contains(&full(&self.base, &x.path)));
+        if base != self.base {
+            for record in &mut self.records {
+                record.path = full(&self.base, &record.path);
+            }
+        }
+
+        let contents = match &mut list {
+            Some(list) => {
+                let tracks = list["tracks"]
+                    .as_array_mut()
+                    .ok_or_else(|| eyre!("the list at {} has no tracks", path.display()))?;
+                tracks.extend(self.records.iter().map(|x| json!(x)));
+
+                serde_json::to_string_pretty(list)? + "\n"
+            }
+            None => {
+                let mut contents = contents;
+                if !contents.ends_with('\n') {
+                    contents.push('\n');
+                }
+
+                for record in &self.records {
+                    contents.push_str(&record.line());
+                    contents.push('\n');
+                }
+
+                contents
+            }
+        };
+
+        play::write(path, contents).await?;
+
+        Ok(self.records.len())
     }
+}
 
-    let mut files = Vec::new();
-    while let Some(mut result) = futures.next().await {
-        files.append(&mut result);
+/// Saves `scraped` in the data directory as a list called `name`, written in `format`.
+///
+/// This won't replace a list that's already there, since `--merge-into` is for that.
+async fn save(scraped: &Scraped, name: &str, format: Format) -> eyre::Result<()> {
+    let text = List::data_path(name)?;
+    if let Some(existing) = ["txt", "toml", "json"]
+        .into_iter()
+        .map(|x| text.with_extension(x))
+        .find(|x| x.exists())
+    {
+        return Err(eyre!(
+            "there's already a list called {name}, use --merge-into {} to add to it",
+            existing.display()
+        ));
     }
 
-    eyre::Result::Ok(files)
+    let path = match format {
+        Format::Text => text,
+        Format::Json => text.with_extension("json"),
+    };
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    play::write(&path, scraped.render(format)?).await?;
+    println!(
+        "saved {} tracks to {}, which you can play with `lowfi --tracks {name}`",
+        scraped.records.len(),
+        path.display()
+    );
+
+    Ok(())
 }
 
-pub async fn scrape(extension: String, include_full: bool) -> eyre::Result<()> {
-    let files = scan(&extension, include_full).await?;
-    for file in files {
-        println!("{file}");
+/// Scrapes the source in `options`, and then prints the tracks that were found,
+/// merges them into a list if `--merge-into` was used, or saves them with `--save`.
+pub async fn scrape(options: Options) -> eyre::Result<()> {
+    let pace = Pace {
+        concurrency: usize::from(options.concurrency),
+        delay: Duration::from_millis(options.delay_ms),
+    };
+
+    // The parser makes sure that this is one of the sources.
+    let scraper = SCRAPERS
+        .iter()
+        .find(|x| x.name() == options.source)
+        .ok_or_else(|| eyre!("there's no source called {}", options.source))?;
+
+    let mut scraped = scraper.scrape(&options, pace).await?;
+
+    if options.verify {
+        let dead = scraped.verify(pace).await;
+        eprintln!("left out {dead} dead tracks");
+    }
+
+    if let Some(path) = options.merge_into {
+        let added = scraped.merge(&path).await?;
+        println!("added {added} new tracks to {}", path.display());
+    } else if let Some(name) = options.save {
+        save(&scraped, &name, options.format).await?;
+    } else {
+        print!("{}", scraped.render(options.format)?);
     }
 
     Ok(())
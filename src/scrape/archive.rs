@@ -0,0 +1,234 @@
+//! Scrapes items & collections on archive.org with its metadata API,
+//! which is where a lot of Creative Commons music ends up.
+
+use eyre::{bail, eyre};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::time::sleep;
+
+use super::{Options, Pace, Record, Scraped, Scraper};
+
+/// Where the metadata of an item is found.
+const METADATA: &str = "https://archive.org/metadata/";
+
+/// Where the items in a collection are searched for.
+const SEARCH: &str = "https://archive.org/services/search/v1/scrape";
+
+/// Where files are downloaded from, which is the base of the list.
+const BASE: &str = "https://archive.org/download/";
+
+/// The most items that the search API gives back for a single request.
+const PAGE: usize = 1000;
+
+/// A single file in an item.
+#[derive(Deserialize)]
+struct File {
+    /// The path of the file in the item.
+    name: String,
+
+    /// The title of the track, if it's tagged.
+    title: Option<String>,
+
+    /// The artist of the track, if it's tagged.
+    #[serde(alias = "creator")]
+    artist: Option<String>,
+
+    /// The album of the track, if it's tagged.
+    album: Option<String>,
+}
+
+/// The metadata of an item, which could also be a collection of other items.
+#[derive(Deserialize)]
+struct Item {
+    /// Every file in the item, which is empty for collections & items that don't exist.
+    #[serde(default)]
+    files: Vec<File>,
+
+    /// Everything else about the item, which is left as is since
+    /// some fields can either be a single string or a list of them.
+    #[serde(default)]
+    metadata: Value,
+}
+
+impl Item {
+    /// Whether the item is a collection, in which case its files aren't the music.
+    fn is_collection(&self) -> bool {
+        self.metadata["mediatype"] == "collection"
+    }
+}
+
+/// A page of search results.
+#[derive(Deserialize)]
+struct Search {
+    /// The items on this page.
+    items: Vec<Identifier>,
+
+    /// Where the next page starts, if there is one.
+    cursor: Option<String>,
+}
+
+/// A single search result.
+#[derive(Deserialize)]
+struct Identifier {
+    /// The identifier of the item.
+    identifier: String,
+}
+
+/// Gets the metadata of the item called `identifier`.
+async fn item(client: &Client, identifier: &str) -> eyre::Result<Item> {
+    let text = client
+        .get(format!("{METADATA}{identifier}"))
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Ok(serde_json::from_str(&text)?)
+}
+
+/// Gets the identifier of every item in the collection called `collection`.
+async fn search(client: &Client, collection: &str, pace: Pace) -> eyre::Result<Vec<String>> {
+    let query = format!("collection:{collection}");
+    let mut identifiers = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let mut request = client.get(SEARCH).query(&[
+            ("q", query.as_str()),
+            ("fields", "identifier"),
+            ("count", &PAGE.to_string()),
+        ]);
+
+        if let Some(cursor) = &cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+
+        let text = request.send().await?.error_for_status()?.text().await?;
+        let page: Search = serde_json::from_str(&text)?;
+        identifiers.extend(page.items.into_iter().map(|x| x.identifier));
+
+        match page.cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+
+        sleep(pace.delay).await;
+    }
+
+    Ok(identifiers)
+}
+
+/// Converts the files in `item`, which is called `identifier`, into records,
+/// keeping only the ones that end in `extension`.
+///
+/// archive.org makes MP3 copies of most audio, so there might be several
+/// versions of a track, but only the ones with the right extension are kept.
+fn records(identifier: &str, item: Item, extension: &str) -> Vec<Record> {
+    let art = format!("https://archive.org/services/img/{identifier}");
+    let album = item.metadata["title"].as_str().map(str::to_owned);
+
+    item.files
+        .into_iter()
+        .filter(|x| x.name.to_lowercase().ends_with(extension))
+        .map(|file| {
+            // A `!` would otherwise be read as the start of the name.
+            let path = format!("{identifier}/{}", file.name.replace('!', "%21"));
+            let name = match (&file.artist, &file.title) {
+                (Some(artist), Some(title)) => Some(format!("{artist} - {title}")),
+                (None, Some(title)) => Some(title.clone()),
+                _ => None,
+            };
+
+            Record {
+                name: name.map(|x| x.replace(['\n', '\r'], " ")),
+                title: file.title,
+                artist: file.artist,
+                album: file.album.or_else(|| album.clone()),
+                art: Some(art.clone()),
+                ..Record::new(path)
+            }
+        })
+        .collect()
+}
+
+/// Gets every file ending in `extension` from the item called `identifier`,
+/// or from every item in it, if it's a collection.
+///
+/// The items of a collection are requested with the concurrency & delay from `pace`.
+async fn scrape(identifier: &str, extension: &str, pace: Pace) -> eyre::Result<Scraped> {
+    let client = Client::new();
+    let extension = format!(".{}", extension.to_lowercase());
+
+    let root = item(&client, identifier).await?;
+    let records = if root.is_collection() {
+        let identifiers = search(&client, identifier, pace).await?;
+        let (client, extension) = (&client, &extension);
+        let items: Vec<_> = stream::iter(identifiers.into_iter().map(|identifier| async move {
+            sleep(pace.delay).await;
+            let item = item(client, &identifier).await?;
+
+            eyre::Ok(records(&identifier, item, extension))
+        }))
+        .buffered(pace.concurrency)
+        .collect()
+        .await;
+
+        items
+            .into_iter()
+            .collect::<eyre::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .collect()
+    } else if root.files.is_empty() {
+        bail!("there's no item on archive.org called {identifier}");
+    } else {
+        records(identifier, root, &extension)
+    };
+
+    Ok(Scraped {
+        base: BASE.to_owned(),
+        comment: Some(format!(
+            "Scraped from https://archive.org/details/{identifier}, check its license before sharing."
+        )),
+        records,
+    })
+}
+
+/// Any item or collection on archive.org, using its metadata API.
+pub struct Archive;
+
+impl Scraper for Archive {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn about(&self) -> &'static str {
+        "Any item or collection on archive.org, using its metadata API"
+    }
+
+    fn url(&self) -> Option<&'static str> {
+        Some("https://archive.org")
+    }
+
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>> {
+        async move {
+            if options.since.is_some() {
+                bail!("archive.org doesn't support --since");
+            }
+
+            let identifier = options.target.as_deref().ok_or_else(|| {
+                eyre!("archive needs the identifier of an item or collection on archive.org")
+            })?;
+
+            scrape(identifier, &options.extension, pace).await
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,136 @@
+//! Crawls directory listings, like the autoindex pages that nginx & Apache make,
+//! which is how a lot of self hosted music archives are shared.
+
+use std::collections::HashSet;
+
+use eyre::{bail, eyre};
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use reqwest::{Client, Url};
+use scraper::{Html, Selector};
+use tokio::time::sleep;
+
+use super::{Options, Pace, Record, Scraped, Scraper};
+
+/// Finds every link in `document` which matches `selector`, relative to the page at `url`.
+///
+/// Queries & fragments are removed, since those are just used for sorting listings.
+fn links(document: &str, selector: &Selector, url: &Url) -> Vec<Url> {
+    Html::parse_document(document)
+        .select(selector)
+        .filter_map(|x| x.attr("href"))
+        .filter_map(|x| url.join(x).ok())
+        .map(|mut x| {
+            x.set_query(None);
+            x.set_fragment(None);
+            x
+        })
+        .collect()
+}
+
+/// Gets the links on the page at `url`.
+async fn page(client: &Client, url: Url, selector: &Selector) -> eyre::Result<Vec<Url>> {
+    let document = client
+        .get(url.clone())
+        .send()
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    Ok(links(&document, selector, &url))
+}
+
+/// Crawls the listing at `url` & every directory under it, looking for files ending in `extension`.
+///
+/// Links which match `selector` & end in a `/` are treated as directories,
+/// but only if they're under `url`, so that parent directories aren't crawled.
+async fn scrape(url: &str, selector: &str, extension: &str, pace: Pace) -> eyre::Result<Scraped> {
+    let selector = Selector::parse(selector)
+        .map_err(|error| eyre!("invalid selector {selector:?}: {error}"))?;
+
+    let mut base = Url::parse(url)?;
+    if !base.path().ends_with('/') {
+        base.set_path(&format!("{}/", base.path()));
+    }
+
+    let extension = format!(".{}", extension.to_lowercase());
+    let client = Client::new();
+
+    let mut visited = HashSet::from([base.clone()]);
+    let mut pending = vec![base.clone()];
+    let mut files = Vec::new();
+
+    // Each level of directories is crawled at once, which avoids having to recurse.
+    while !pending.is_empty() {
+        let pages: Vec<_> = stream::iter(pending.drain(..).map(|url| async {
+            sleep(pace.delay).await;
+            page(&client, url, &selector).await
+        }))
+        .buffered(pace.concurrency)
+        .collect()
+        .await;
+
+        for link in pages
+            .into_iter()
+            .collect::<eyre::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+        {
+            let Some(path) = link.as_str().strip_prefix(base.as_str()) else {
+                continue;
+            };
+
+            if path.ends_with('/') {
+                if visited.insert(link.clone()) {
+                    pending.push(link);
+                }
+            } else if path.to_lowercase().ends_with(&extension) && !files.iter().any(|x| x == path)
+            {
+                files.push(path.to_owned());
+            }
+        }
+    }
+
+    Ok(Scraped {
+        base: base.to_string(),
+        comment: Some(format!("Crawled from {base}.")),
+        records: files.into_iter().map(Record::new).collect(),
+    })
+}
+
+/// Any directory listing, like the ones nginx & Apache make, which is crawled recursively.
+pub struct Index;
+
+impl Scraper for Index {
+    fn name(&self) -> &'static str {
+        "index"
+    }
+
+    fn about(&self) -> &'static str {
+        "Any directory listing, like the ones nginx & Apache make, which is crawled recursively"
+    }
+
+    fn url(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>> {
+        async move {
+            if options.since.is_some() {
+                bail!("directory listings don't support --since");
+            }
+
+            let url = options
+                .target
+                .as_deref()
+                .ok_or_else(|| eyre!("index needs the URL of a directory listing"))?;
+
+            scrape(url, &options.selector, &options.extension, pace).await
+        }
+        .boxed()
+    }
+}
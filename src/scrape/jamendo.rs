@@ -0,0 +1,196 @@
+//! Scrapes Jamendo's API, which only has Creative Commons tracks,
+//! so the list that comes out of it is fine to share & play anywhere.
+
+use eyre::{bail, eyre};
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use tokio::time::sleep;
+
+use super::{Month, Options, Pace, Record, Scraped, Scraper};
+
+/// Where tracks are searched for.
+const API: &str = "https://api.jamendo.com/v3.0/tracks/";
+
+/// Where tracks are downloaded from, which is the base of the list.
+const BASE: &str = "https://prod-1.storage.jamendo.com/download/track/";
+
+/// The most tracks that Jamendo gives back for a single request.
+const PAGE: usize = 200;
+
+/// The status of a response from Jamendo.
+#[derive(Deserialize)]
+struct Headers {
+    /// Either `success` or `failed`.
+    status: String,
+
+    /// What went wrong, if the request failed.
+    #[serde(default)]
+    error_message: String,
+}
+
+/// A single track in a response from Jamendo.
+#[derive(Deserialize)]
+struct Track {
+    /// The title of the track.
+    name: String,
+
+    /// The name of the artist.
+    artist_name: String,
+
+    /// The name of the album, which is empty for singles.
+    #[serde(default)]
+    album_name: String,
+
+    /// A URL to the cover art.
+    #[serde(default)]
+    image: String,
+
+    /// Where the track can be downloaded from, as an mp3.
+    audiodownload: String,
+
+    /// Whether the artist allows the track to be downloaded, which is needed to play it.
+    audiodownload_allowed: bool,
+}
+
+/// A page of tracks from Jamendo.
+#[derive(Deserialize)]
+struct Response {
+    /// Whether the request worked.
+    headers: Headers,
+
+    /// The tracks on this page.
+    results: Vec<Track>,
+}
+
+/// Gets a single page of tracks with `tags`, starting from `offset`.
+///
+/// Only tracks released in or after `since` are included, if it's set.
+async fn page(
+    client: &Client,
+    client_id: &str,
+    tags: &str,
+    since: Option<Month>,
+    offset: usize,
+) -> eyre::Result<Vec<Track>> {
+    let mut request = client.get(API).query(&[
+        ("client_id", client_id),
+        ("format", "json"),
+        ("tags", tags),
+        ("audiodlformat", "mp32"),
+        ("order", "popularity_total"),
+        ("limit", &PAGE.to_string()),
+        ("offset", &offset.to_string()),
+    ]);
+
+    // Jamendo only takes a range of dates, so the end is just far enough away.
+    if let Some(since) = since {
+        let dates = format!("{:04}-{:02}-01_9999-12-31", since.year, since.month);
+        request = request.query(&[("datebetween", dates)]);
+    }
+
+    let text = request.send().await?.error_for_status()?.text().await?;
+
+    let response: Response = serde_json::from_str(&text)?;
+    if response.headers.status != "success" {
+        bail!("Jamendo said: {}", response.headers.error_message);
+    }
+
+    Ok(response.results)
+}
+
+/// Converts `track` into a [`Record`] with `Artist - Title` as its name,
+/// or returns [`None`] if it can't be downloaded.
+fn record(track: Track) -> Option<Record> {
+    if !track.audiodownload_allowed || track.audiodownload.is_empty() {
+        return None;
+    }
+
+    // Newlines would split the track over several lines of the list.
+    let name = format!("{} - {}", track.artist_name, track.name).replace(['\n', '\r'], " ");
+    let path = track
+        .audiodownload
+        .strip_prefix(BASE)
+        .unwrap_or(&track.audiodownload);
+
+    // Empty strings are how Jamendo says that it doesn't know something.
+    let known = |x: String| (!x.is_empty()).then_some(x);
+
+    Some(Record {
+        path: path.to_owned(),
+        name: Some(name),
+        title: Some(track.name),
+        artist: Some(track.artist_name),
+        album: known(track.album_name),
+        art: known(track.image),
+    })
+}
+
+/// Gets every downloadable track with `tags`, like `lofi`, from the most popular down.
+///
+/// Several tags can be given with a `+` in between, like `lofi+chillout`,
+/// in which case tracks have to have all of them.
+///
+/// Pages are requested one at a time, with the delay from `pace` in between.
+async fn scrape(
+    client_id: &str,
+    tags: &str,
+    since: Option<Month>,
+    pace: Pace,
+) -> eyre::Result<Scraped> {
+    let client = Client::new();
+    let mut records = Vec::new();
+
+    let mut offset = 0;
+    loop {
+        let tracks = page(&client, client_id, tags, since, offset).await?;
+        let last = tracks.len() < PAGE;
+        records.extend(tracks.into_iter().filter_map(record));
+
+        if last {
+            break;
+        }
+
+        offset += PAGE;
+        sleep(pace.delay).await;
+    }
+
+    Ok(Scraped {
+        base: BASE.to_owned(),
+        comment: Some(format!("Scraped from Jamendo, with the tags {tags}. Every track here is under a Creative Commons license.")),
+        records,
+    })
+}
+
+/// Jamendo's API, which only has Creative Commons tracks.
+pub struct Jamendo;
+
+impl Scraper for Jamendo {
+    fn name(&self) -> &'static str {
+        "jamendo"
+    }
+
+    fn about(&self) -> &'static str {
+        "Jamendo's API, which only has Creative Commons tracks"
+    }
+
+    fn url(&self) -> Option<&'static str> {
+        Some("https://www.jamendo.com")
+    }
+
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>> {
+        async move {
+            let client_id = options.client_id.as_deref().ok_or_else(|| {
+                eyre!("Jamendo needs a --client-id, which you can get for free at https://devportal.jamendo.com")
+            })?;
+
+            scrape(client_id, &options.tags, options.since, pace).await
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,151 @@
+//! Scrapes the Lofi Girl file server, which is where the built in list comes from.
+
+use futures::{future::BoxFuture, stream, FutureExt, StreamExt};
+use lazy_static::lazy_static;
+use scraper::{Html, Selector};
+
+use tokio::time::sleep;
+
+use super::{Month, Options, Pace, Record, Scraped, Scraper};
+
+const BASE_URL: &str = "https://lofigirl.com/wp-content/uploads/";
+
+lazy_static! {
+    static ref SELECTOR: Selector = Selector::parse("html > body > pre > a").unwrap();
+}
+
+async fn parse(path: &str) -> eyre::Result<Vec<String>> {
+    let response = reqwest::get(format!("{}{}", BASE_URL, path)).await?;
+    let document = response.text().await?;
+
+    let html = Html::parse_document(&document);
+    Ok(html
+        .select(&SELECTOR)
+        .skip(5)
+        .map(|x| String::from(x.attr("href").unwrap()))
+        .collect())
+}
+
+/// This function basically just scans the entire file server, and returns a list of paths to mp3 files.
+///
+/// It's a bit hacky, and basically works by checking all of the years, then months, and then all of the files.
+/// This is done as a way to avoid recursion, since async rust really hates recursive functions.
+///
+/// Months before `since` are skipped entirely, which saves a lot of requests.
+async fn scan(
+    extension: &str,
+    include_full: bool,
+    since: Option<Month>,
+    pace: Pace,
+) -> eyre::Result<Vec<String>> {
+    let extension = &format!(".{}", extension);
+
+    let items = parse("").await?;
+
+    let mut years: Vec<u32> = items
+        .iter()
+        .filter_map(|x| {
+            let year = x.strip_suffix("/")?;
+            year.parse().ok()
+        })
+        .filter(|x| since.map_or(true, |since| *x >= since.year))
+        .collect();
+
+    years.sort();
+
+    // A little bit of async to run several months concurrently.
+    let mut futures = Vec::new();
+
+    for year in years {
+        sleep(pace.delay).await;
+        let months = parse(&year.to_string()).await?;
+
+        for month in months {
+            // Months are listed like `06/`.
+            let number = month.trim_end_matches('/').parse().unwrap_or_default();
+            if since.is_some_and(|since| {
+                Month {
+                    year,
+                    month: number,
+                } < since
+            }) {
+                continue;
+            }
+
+            futures.push(async move {
+                sleep(pace.delay).await;
+                let path = format!("{}/{}", year, month);
+
+                let items = parse(&path).await.unwrap();
+                items
+                    .into_iter()
+                    .filter_map(|x| {
+                        if x.ends_with(extension) {
+                            if include_full {
+                                Some(format!("{BASE_URL}{path}{x}"))
+                            } else {
+                                Some(format!("{path}{x}"))
+                            }
+                        } else {
+                            None
+                        }
+                    })
+                    .collect::<Vec<String>>()
+            });
+        }
+    }
+
+    let mut files = Vec::new();
+    let mut results = stream::iter(futures).buffered(pace.concurrency);
+    while let Some(mut result) = results.next().await {
+        files.append(&mut result);
+    }
+
+    eyre::Result::Ok(files)
+}
+
+async fn scrape(
+    extension: &str,
+    include_full: bool,
+    since: Option<Month>,
+    pace: Pace,
+) -> eyre::Result<Scraped> {
+    let files = scan(extension, include_full, since, pace).await?;
+
+    Ok(Scraped {
+        base: BASE_URL.to_owned(),
+        comment: None,
+        records: files.into_iter().map(Record::new).collect(),
+    })
+}
+
+/// The Lofi Girl file server, which the built in list comes from.
+pub struct Lofigirl;
+
+impl Scraper for Lofigirl {
+    fn name(&self) -> &'static str {
+        "lofigirl"
+    }
+
+    fn about(&self) -> &'static str {
+        "The Lofi Girl file server, which the built in list comes from"
+    }
+
+    fn url(&self) -> Option<&'static str> {
+        Some(BASE_URL)
+    }
+
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>> {
+        scrape(
+            &options.extension,
+            options.include_full,
+            options.since,
+            pace,
+        )
+        .boxed()
+    }
+}
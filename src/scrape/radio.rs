@@ -0,0 +1,132 @@
+//! Searches the radio-browser.info directory for internet radio stations by tag.
+
+use eyre::bail;
+use futures::{future::BoxFuture, FutureExt};
+use reqwest::Client;
+use serde::Deserialize;
+
+use tokio::time::sleep;
+
+use super::{Options, Pace, Record, Scraped, Scraper};
+
+/// The radio-browser API, which picks one of its servers by itself.
+const API: &str = "https://all.api.radio-browser.info/json/stations/bytag/";
+
+/// The base of the list, which is never used since every station is a full URL.
+const BASE: &str = "https://www.radio-browser.info/";
+
+/// The most stations to list for each tag.
+const LIMIT: usize = 100;
+
+/// A single station from radio-browser.
+#[derive(Deserialize)]
+struct Station {
+    /// The name of the station.
+    name: String,
+
+    /// The URL of the stream itself, after following any playlists.
+    url_resolved: String,
+
+    /// The codec of the stream, like `MP3`.
+    codec: String,
+
+    /// The bitrate of the stream in kbps, which is 0 if it isn't known.
+    bitrate: u32,
+
+    /// A URL to the station's icon, which is empty if it doesn't have one.
+    #[serde(default)]
+    favicon: String,
+}
+
+/// Gets the most popular working MP3 stations with any of `tags`,
+/// which are separated by `+` like they are for Jamendo.
+///
+/// Each tag is requested one at a time, with the delay from `pace` before each one.
+async fn scrape(tags: &str, pace: Pace) -> eyre::Result<Scraped> {
+    // radio-browser asks for a user agent which says what's using it.
+    let client = Client::builder()
+        .user_agent(concat!("lowfi/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    let mut records: Vec<Record> = Vec::new();
+    for tag in tags.split('+') {
+        sleep(pace.delay).await;
+        let text = client
+            .get(format!("{API}{tag}"))
+            .query(&[
+                ("hidebroken", "true"),
+                ("order", "clickcount"),
+                ("reverse", "true"),
+                ("limit", &LIMIT.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+
+        let stations: Vec<Station> = serde_json::from_str(&text)?;
+
+        // lowfi can only decode MP3, and a station with several tags only needs to be listed once.
+        for station in stations {
+            let seen = records.iter().any(|x| x.path == station.url_resolved);
+            if !station.codec.eq_ignore_ascii_case("mp3") || seen {
+                continue;
+            }
+
+            let title = station.name.trim().replace(['\n', '\r'], " ");
+            let name = if station.bitrate > 0 {
+                format!("{title} ({} kbps)", station.bitrate)
+            } else {
+                title.clone()
+            };
+
+            records.push(Record {
+                name: Some(name),
+                title: Some(title),
+                art: (!station.favicon.is_empty()).then_some(station.favicon),
+                ..Record::new(station.url_resolved)
+            });
+        }
+    }
+
+    Ok(Scraped {
+        base: BASE.to_owned(),
+        comment: Some(format!(
+            "Stations from radio-browser.info, with the tags {tags}."
+        )),
+        records,
+    })
+}
+
+/// The radio-browser.info directory of internet radio stations.
+pub struct Radio;
+
+impl Scraper for Radio {
+    fn name(&self) -> &'static str {
+        "radio"
+    }
+
+    fn about(&self) -> &'static str {
+        "The radio-browser.info directory of internet radio stations"
+    }
+
+    fn url(&self) -> Option<&'static str> {
+        Some(BASE)
+    }
+
+    fn scrape<'a>(
+        &'a self,
+        options: &'a Options,
+        pace: Pace,
+    ) -> BoxFuture<'a, eyre::Result<Scraped>> {
+        async move {
+            if options.since.is_some() {
+                bail!("radio-browser doesn't support --since");
+            }
+
+            scrape(&options.tags, pace).await
+        }
+        .boxed()
+    }
+}
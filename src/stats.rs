@@ -0,0 +1,193 @@
+//! Has the `stats` command, which shows how much has been listened to.
+
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use clap::ValueEnum;
+
+use crate::{
+    info,
+    tracks::{
+        log::{End, Entry, Log},
+        plays::Plays,
+        skips::{Reason, Skips},
+    },
+};
+
+/// How far back `lowfi stats` looks in `history.log`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Range {
+    /// The last 24 hours.
+    Day,
+
+    /// The last 7 days.
+    Week,
+
+    /// The last 30 days.
+    Month,
+
+    /// The last 365 days.
+    Year,
+
+    /// Every session, including the play & skip counts from before there was a `history.log`.
+    #[default]
+    All,
+}
+
+impl Range {
+    /// How long the range is in days, or [`None`] if it goes back forever.
+    const fn days(self) -> Option<u64> {
+        match self {
+            Self::Day => Some(1),
+            Self::Week => Some(7),
+            Self::Month => Some(30),
+            Self::Year => Some(365),
+            Self::All => None,
+        }
+    }
+
+    /// Whether `entry` is recent enough to be in the range.
+    fn contains(self, entry: &Entry, now: u64) -> bool {
+        self.days().map_or(true, |days| {
+            entry.time >= now.saturating_sub(days * 24 * 60 * 60)
+        })
+    }
+}
+
+/// Formats a long `duration`, like `3h 05m`, or just `12m` if it's under an hour.
+fn hours(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    match minutes / 60 {
+        0 => format!("{minutes}m"),
+        hours => format!("{hours}h {:02}m", minutes % 60),
+    }
+}
+
+/// Counts how many times each of `keys` comes up, and then gets the `top` most common ones.
+///
+/// Ties are sorted by name, so that the order doesn't change each time.
+fn ranked<'key>(keys: impl Iterator<Item = &'key str>, top: usize) -> Vec<(&'key str, usize)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for key in keys {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_unstable_by_key(|&(key, count)| (Reverse(count), key));
+    counts.truncate(top);
+
+    counts
+}
+
+/// Gets the play & skip counts from `plays.txt` & `skips.txt`, which go back further than `history.log`.
+///
+/// Each one says which file it's from, since the rest of the stats only come from `history.log`.
+async fn totals() -> eyre::Result<Vec<(&'static str, String)>> {
+    let plays = Plays::load().await?;
+    let skips = Skips::load().await?;
+
+    let (tracks, total) = plays.totals();
+    let mut rows = vec![
+        ("plays", format!("{total}, from plays.txt")),
+        ("tracks", format!("{tracks}, from plays.txt")),
+    ];
+
+    // Like `5 (3 manual, 2 error)`.
+    let skipped: u32 = Reason::ALL.into_iter().map(|x| skips.get(x)).sum();
+    let reasons: Vec<String> = Reason::ALL
+        .into_iter()
+        .map(|reason| format!("{} {}", skips.get(reason), reason.name()))
+        .collect();
+    rows.push((
+        "skips",
+        format!("{skipped} ({}), from skips.txt", reasons.join(", ")),
+    ));
+
+    Ok(rows)
+}
+
+/// Prints how much has been listened to over `range` in a window, along with
+/// the `top` most played tracks & artists, the most skipped tracks, and how much
+/// of each list was played.
+pub async fn stats(range: Range, top: usize) -> eyre::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |x| x.as_secs());
+
+    let entries: Vec<Entry> = Log::load()
+        .await?
+        .into_iter()
+        .filter(|x| range.contains(x, now))
+        .collect();
+
+    let skipped = entries.iter().filter(|x| x.end == End::Skipped);
+    let mut rows = if range == Range::All {
+        totals().await?
+    } else {
+        let tracks = ranked(entries.iter().map(|x| x.path.as_str()), usize::MAX);
+        vec![
+            ("plays", entries.len().to_string()),
+            ("tracks", tracks.len().to_string()),
+            ("skips", skipped.clone().count().to_string()),
+        ]
+    };
+
+    let listened = hours(entries.iter().map(|x| x.played).sum());
+    rows.push(if range == Range::All {
+        ("listened", format!("{listened}, from history.log"))
+    } else {
+        ("listened", listened)
+    });
+
+    // Tracks are counted by their path, since different tracks can have the same name,
+    // and the name that's shown is the one from the last time it was played.
+    let names: HashMap<&str, &str> = entries
+        .iter()
+        .map(|x| (x.path.as_str(), x.name.as_str()))
+        .collect();
+
+    let sections = [
+        (
+            "top track",
+            ranked(entries.iter().map(|x| x.path.as_str()), top),
+        ),
+        (
+            "top artist",
+            ranked(entries.iter().filter_map(|x| x.artist.as_deref()), top),
+        ),
+        (
+            "most skipped",
+            ranked(skipped.map(|x| x.path.as_str()), top),
+        ),
+    ];
+
+    for (label, ranking) in sections {
+        rows.extend(ranking.into_iter().map(|(key, count)| {
+            let name = names.get(key).unwrap_or(&key);
+            (label, format!("{name} ({count})"))
+        }));
+    }
+
+    // Lists are sorted by how long they were listened to, rather than how many tracks were played.
+    let mut lists: HashMap<&str, (Duration, usize)> = HashMap::new();
+    for entry in &entries {
+        let (time, plays) = lists.entry(&entry.list).or_default();
+        *time += entry.played;
+        *plays += 1;
+    }
+
+    let mut lists: Vec<_> = lists.into_iter().collect();
+    lists.sort_unstable_by_key(|&(list, (time, _))| (Reverse(time), list));
+    rows.extend(lists.into_iter().map(|(list, (time, plays))| {
+        let unit = if plays == 1 { "play" } else { "plays" };
+        (
+            "list",
+            format!("{list}, {} over {plays} {unit}", hours(time)),
+        )
+    }));
+
+    info::print(rows).await
+}
@@ -0,0 +1,100 @@
+//! Has the `storage` command, which shows how much space lowfi's data takes up,
+//! and cleans up the cache, since that's the only part that can be downloaded again.
+
+use std::{fs, io, path::Path};
+
+use eyre::bail;
+use tokio::task;
+
+use crate::{config::Config, info, play, player::ui::format, tracks::cache::Cache};
+
+/// The categories that the data directory is split up into, in the order they're shown in.
+const CATEGORIES: [&str; 7] = [
+    "cache",
+    "lists",
+    "decks",
+    "bookmarks",
+    "stats",
+    "session",
+    "other",
+];
+
+/// Gets the category of something directly inside of the data directory, by it's `name`.
+fn category(name: &str) -> &'static str {
+    match name {
+        "cache" => "cache",
+        "decks" => "decks",
+        "bookmarks.txt" => "bookmarks",
+        "plays.txt" | "skips.txt" | "history.log" => "stats",
+        "session.toml" => "session",
+        _ if Path::new(name).extension().is_some_and(|x| x == "txt") => "lists",
+        _ => "other",
+    }
+}
+
+/// Gets how much space `path` takes up, including everything inside of it if it's a directory.
+fn size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+
+    fs::read_dir(path)?.try_fold(0, |total, entry| Ok(total + size(&entry?.path())?))
+}
+
+/// Gets how much space each of the [`CATEGORIES`] takes up in `dir`, in the same order.
+fn usage(dir: &Path) -> io::Result<[u64; CATEGORIES.len()]> {
+    let mut usage = [0; CATEGORIES.len()];
+    if !dir.exists() {
+        return Ok(usage);
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let category = category(&entry.file_name().to_string_lossy());
+        if let Some(i) = CATEGORIES.iter().position(|x| *x == category) {
+            usage[i] += size(&entry.path())?;
+        }
+    }
+
+    Ok(usage)
+}
+
+/// Works out the [`usage`] of the data directory without blocking the runtime.
+async fn measure() -> eyre::Result<[u64; CATEGORIES.len()]> {
+    let dir = play::data_dir()?;
+    Ok(task::spawn_blocking(move || usage(&dir)).await??)
+}
+
+/// Prints how much space each category takes up in the data directory.
+///
+/// If `clean` is set, then the least recently played tracks are removed from the cache
+/// first, until it fits in `cache_size`, or the `cache_size` from `config.toml`.
+pub async fn storage(clean: bool, cache_size: Option<u64>) -> eyre::Result<()> {
+    let freed = if clean {
+        let Some(limit) = cache_size.or(Config::load().await?.cache_size) else {
+            bail!("there's no size to clean the cache down to, set one with --cache-size or cache_size in config.toml");
+        };
+
+        // The cache is the first category, and the only one that's cleaned.
+        let before = measure().await?;
+        Cache::new(false, Some(limit))?.evict().await?;
+        Some(before[0].saturating_sub(measure().await?[0]))
+    } else {
+        None
+    };
+
+    let usage = measure().await?;
+    let mut rows: Vec<(&str, String)> = CATEGORIES
+        .into_iter()
+        .zip(usage)
+        .map(|(category, size)| (category, format::size(size)))
+        .collect();
+
+    rows.push(("total", format::size(usage.iter().sum())));
+    if let Some(freed) = freed {
+        rows.push(("freed", format::size(freed)));
+    }
+
+    info::print(rows).await
+}
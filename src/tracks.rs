@@ -2,18 +2,30 @@
 //! of tracks, as well as downloading them &
 //! finding new ones.
 
-use std::{io::Cursor, time::Duration};
+use std::{sync::Arc, time::Duration};
 
-use bytes::Bytes;
-use inflector::Inflector;
+use eyre::eyre;
+use format::Naming;
+use list::structured::Metadata;
 use rodio::{Decoder, Source};
+use stream::Stream;
+use tags::{Art, Tags};
 use unicode_width::UnicodeWidthStr;
-use url::form_urlencoded;
 
+pub mod cache;
+pub mod codec;
+pub mod deck;
+pub mod fetch;
+pub mod format;
 pub mod list;
+pub mod log;
+pub mod plays;
+pub mod skips;
+pub mod stream;
+pub mod tags;
 
-/// Just a shorthand for a decoded [Bytes].
-pub type DecodedData = Decoder<Cursor<Bytes>>;
+/// Just a shorthand for a decoded [Stream].
+pub type DecodedData = Decoder<Stream>;
 
 /// The [`Info`] struct, which has the name and duration of a track.
 ///
@@ -24,6 +36,10 @@ pub struct Info {
     /// This is a formatted name, so it doesn't include the full path.
     pub name: String,
 
+    /// This is the full downloadable path/url of the track, which is
+    /// used for things like bookmarking.
+    pub full_path: String,
+
     /// This is the *actual* terminal width of the track name, used to make
     /// the UI consistent.
     pub width: usize,
@@ -31,63 +47,46 @@ pub struct Info {
     /// The duration of the track, this is an [Option] because there are
     /// cases where the duration of a track is unknown.
     pub duration: Option<Duration>,
-}
 
-impl Info {
-    /// Decodes a URL string into normal UTF-8.
-    fn decode_url(text: &str) -> String {
-        form_urlencoded::parse(text.as_bytes())
-            .map(|(key, val)| [key, val].concat())
-            .collect()
-    }
+    /// The artist from the list or the track's tags, if either has one.
+    pub artist: Option<String>,
 
-    /// Formats a name with [Inflector].
-    /// This will also strip the first few numbers that are
-    /// usually present on most lofi tracks.
-    fn format_name(name: &str) -> String {
-        let formatted = Self::decode_url(
-            name.split('/')
-                .last()
-                .unwrap()
-                .strip_suffix(".mp3")
-                .unwrap(),
-        )
-        .to_lowercase()
-        .to_title_case()
-        // Inflector doesn't like contractions...
-        // Replaces a few very common ones.
-        // TODO: Properly handle these.
-        .replace(" S ", "'s ")
-        .replace(" T ", "'t ")
-        .replace(" D ", "'d ")
-        .replace(" Ve ", "'ve ")
-        .replace(" Ll ", "'ll ")
-        .replace(" Re ", "'re ")
-        .replace(" M ", "'m ");
-
-        // This is incremented for each digit in front of the song name.
-        let mut skip = 0;
-
-        for character in formatted.as_bytes() {
-            if character.is_ascii_digit() {
-                skip += 1;
-            } else {
-                break;
-            }
-        }
+    /// The album from the list or the track's tags, if either has one.
+    pub album: Option<String>,
 
-        #[allow(clippy::string_slice, /* We've already checked before that the bound is at an ASCII digit. */)]
-        String::from(&formatted[skip..])
-    }
+    /// Where the track came from, which can only be given in structured lists.
+    pub source: Option<String>,
 
-    /// Creates a new [`TrackInfo`] from a raw name & decoded track data.
-    pub fn new(name: &str, decoded: &DecodedData) -> Self {
-        let name = Self::format_name(name);
+    /// The album art from the track's tags, which is only read with `--art`.
+    pub art: Option<Arc<Art>>,
+
+    /// The codec that the track is encoded with, like `MP3` or `AAC`, if it's known.
+    pub codec: Option<&'static str>,
+}
 
+impl Info {
+    /// Creates a new [`TrackInfo`] from a display name, the full path, the
+    /// track's tags & metadata from the list, its codec & decoded track data.
+    ///
+    /// The metadata from the list takes priority over the tags.
+    pub fn new(
+        name: String,
+        full_path: String,
+        tags: Tags,
+        metadata: Metadata,
+        codec: Option<&'static str>,
+        decoded: &DecodedData,
+    ) -> Self {
         Self {
             duration: decoded.total_duration(),
             width: name.width(),
+            full_path,
             name,
+            artist: metadata.artist.or(tags.artist),
+            album: metadata.album.or(tags.album),
+            source: metadata.source,
+            art: tags.art,
+            codec,
         }
     }
 }
@@ -105,9 +104,28 @@ pub struct Decoded {
 impl Decoded {
     /// Creates a new track.
     /// This is equivalent to [`Track::decode`].
-    pub fn new(track: Track) -> eyre::Result<Self> {
-        let data = Decoder::new(Cursor::new(track.data))?;
-        let info = Info::new(&track.name, &data);
+    ///
+    /// If the track is still being streamed, this will block until
+    /// enough of it has been downloaded to start decoding.
+    ///
+    /// The title from the track's tags is used as the name, unless the list gave it a custom one.
+    /// The album art is only kept if `art` is set.
+    pub fn new(mut track: Track, art: bool) -> eyre::Result<Self> {
+        let mut tags = Tags::read(&mut track.data, art)?;
+        let name = match (&track.custom_name, tags.title.take()) {
+            (None, Some(title)) => title,
+            _ => track.display_name(),
+        };
+
+        let codec = codec::sniff(&mut track.data)?;
+        let data = Decoder::new(track.data).map_err(|error| match codec {
+            // Only MP3 can be decoded without the `symphonia` feature.
+            Some(codec) if codec != "MP3" && !cfg!(feature = "symphonia") => {
+                eyre!("can't decode {codec}, lowfi has to be built with the symphonia feature")
+            }
+            _ => error.into(),
+        })?;
+        let info = Info::new(name, track.full_path, tags, track.metadata, codec, &data);
 
         Ok(Self { info, data })
     }
@@ -118,16 +136,38 @@ pub struct Track {
     /// This name is not formatted, and also includes the month & year of the track.
     pub name: String,
 
+    /// A custom display name for the track, specified in the [`list::List`].
+    pub custom_name: Option<String>,
+
+    /// How to format `name` if there isn't a custom one, which depends on the list.
+    pub naming: Arc<Naming>,
+
+    /// The artist, album & source from the [`list::List`], if it's a structured one.
+    pub metadata: Metadata,
+
+    /// The full downloadable path/url of the track.
+    pub full_path: String,
+
     /// The raw data of the track, which is not decoded and
     /// therefore much more memory efficient.
-    pub data: Bytes,
+    ///
+    /// This might still be downloading, if the track is being streamed.
+    pub data: Stream,
 }
 
 impl Track {
+    /// Gets the name that's shown in the UI, which is the custom name if there is one,
+    /// and otherwise `name` formatted with the list's [`Naming`] rules.
+    pub fn display_name(&self) -> String {
+        self.custom_name
+            .clone()
+            .unwrap_or_else(|| self.naming.format(&self.name))
+    }
+
     /// This will actually decode and format the track,
     /// returning a [`DecodedTrack`] which can be played
     /// and also has a duration & formatted name.
-    pub fn decode(self) -> eyre::Result<Decoded> {
-        Decoded::new(self)
+    pub fn decode(self, art: bool) -> eyre::Result<Decoded> {
+        Decoded::new(self, art)
     }
 }
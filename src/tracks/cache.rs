@@ -0,0 +1,216 @@
+//! Contains the [`Cache`], which stores downloaded tracks on disk
+//! so that they don't have to be downloaded again.
+
+use std::{
+    fs::File,
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use bytes::Bytes;
+use tokio::{fs, time::interval};
+
+use super::log::Log;
+use crate::{play, player::ui::format, CacheCommands};
+
+/// How often the cache is evicted in the background, see [`Cache::collect`].
+const COLLECT: Duration = Duration::from_secs(10 * 60);
+
+/// A persistent on-disk cache of raw track data, stored in the data directory.
+///
+/// Each track is stored in a file named after a hash of it's URL.
+/// The modification time of each file is updated whenever it's used,
+/// so that the least recently used tracks can be evicted first.
+#[derive(Clone)]
+pub struct Cache {
+    /// The directory that the tracks are stored in.
+    dir: PathBuf,
+
+    /// Whether newly downloaded tracks should be stored.
+    /// Tracks that are already cached are always used.
+    store: bool,
+
+    /// The maximum size of the cache in bytes, if there is one.
+    limit: Option<u64>,
+}
+
+impl Cache {
+    /// Creates a new [`Cache`] in the data directory.
+    ///
+    /// `limit` is the maximum size of the cache in megabytes.
+    pub fn new(store: bool, limit: Option<u64>) -> eyre::Result<Self> {
+        Ok(Self {
+            dir: play::data_dir()?.join("cache"),
+            store,
+            limit: limit.map(|x| x.saturating_mul(1024 * 1024)),
+        })
+    }
+
+    /// Hashes a URL using 64-bit FNV-1a.
+    ///
+    /// This is used instead of [`std::hash::DefaultHasher`] since the
+    /// cache has to stay valid across different versions of Rust.
+    fn hash(url: &str) -> u64 {
+        url.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+    }
+
+    /// Gets the path that a track would be cached at.
+    fn path(&self, url: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}", Self::hash(url)))
+    }
+
+    /// Gets a track from the cache, if it's there.
+    ///
+    /// This also marks the track as recently used.
+    pub async fn get(&self, url: &str) -> Option<Bytes> {
+        let path = self.path(url);
+        let data = fs::read(&path).await.ok()?;
+
+        // If this fails, then the track is just more likely to be evicted.
+        let _ = File::options()
+            .append(true)
+            .open(path)
+            .and_then(|x| x.set_modified(SystemTime::now()));
+
+        Some(Bytes::from(data))
+    }
+
+    /// Gets the size of a cached track in bytes, if it's there.
+    pub async fn size(&self, url: &str) -> Option<u64> {
+        fs::metadata(self.path(url)).await.ok().map(|x| x.len())
+    }
+
+    /// Stores a track in the cache, if storing is enabled.
+    ///
+    /// This is written atomically, so that the cache
+    /// never contains partially written tracks.
+    pub async fn put(&self, url: &str, data: &Bytes) -> eyre::Result<()> {
+        if !self.store {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir).await?;
+
+        play::write(self.path(url), data).await?;
+
+        self.evict().await
+    }
+
+    /// Gets every cached track, along with it's size & when it was last used.
+    async fn entries(&self) -> eyre::Result<Vec<(PathBuf, u64, SystemTime)>> {
+        let mut entries = Vec::new();
+        if !self.dir.exists() {
+            return Ok(entries);
+        }
+
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().is_some() {
+                continue;
+            }
+
+            let metadata = entry.metadata().await?;
+            entries.push((path, metadata.len(), metadata.modified()?));
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes the temporary files which were left behind by writes that never finished,
+    /// like if lowfi was killed partway through storing a track.
+    ///
+    /// These are never counted towards the size of the cache, so otherwise they'd stay forever.
+    /// Only ones older than [`COLLECT`] are removed, so that writes which are still going aren't.
+    async fn tidy(&self) -> eyre::Result<()> {
+        if !self.dir.exists() {
+            return Ok(());
+        }
+
+        let mut dir = fs::read_dir(&self.dir).await?;
+        while let Some(entry) = dir.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|x| x.to_str()) != Some("part") {
+                continue;
+            }
+
+            // A time in the future would mean it's still being written, so it's kept.
+            let modified = entry.metadata().await?.modified()?;
+            if modified.elapsed().is_ok_and(|x| x > COLLECT) {
+                fs::remove_file(path).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Removes the least recently used tracks until the cache fits in it's size limit.
+    ///
+    /// This is done by [`Cache::collect`], as well as whenever a new track is stored.
+    pub async fn evict(&self) -> eyre::Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+
+        let mut entries = self.entries().await?;
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+
+        entries.sort_by_key(|(_, _, used)| *used);
+        for (path, size, _) in entries {
+            if total <= limit {
+                break;
+            }
+
+            fs::remove_file(path).await?;
+            total -= size;
+        }
+
+        Ok(())
+    }
+
+    /// Tidies & evicts the cache straight away, and then again every [`COLLECT`] until lowfi quits.
+    /// `history.log` is trimmed at the same time, since it's the only other thing that keeps growing.
+    ///
+    /// Tracks are already evicted whenever one is stored, but this also catches
+    /// the cache growing in other ways, like from another lowfi running at the same time.
+    /// If anything fails, then it's just tried again next time.
+    // This runs for as long as lowfi does, and stops when it quits.
+    #[allow(clippy::infinite_loop)]
+    pub async fn collect(self) {
+        let mut ticks = interval(COLLECT);
+
+        loop {
+            ticks.tick().await;
+            let _ = self.tidy().await;
+            let _ = self.evict().await;
+            let _ = Log::trim().await;
+        }
+    }
+
+    /// Removes every track from the cache, returning how many there were
+    /// and how much space they took up.
+    pub async fn purge(&self) -> eyre::Result<(usize, u64)> {
+        let entries = self.entries().await?;
+        let size = entries.iter().map(|(_, size, _)| size).sum();
+
+        if self.dir.exists() {
+            fs::remove_dir_all(&self.dir).await?;
+        }
+
+        Ok((entries.len(), size))
+    }
+}
+
+/// Runs one of the `cache` subcommands.
+pub async fn command(command: CacheCommands) -> eyre::Result<()> {
+    match command {
+        CacheCommands::Purge => {
+            let (count, size) = Cache::new(false, None)?.purge().await?;
+            println!("removed {count} tracks ({})", format::size(size));
+        }
+    }
+
+    Ok(())
+}
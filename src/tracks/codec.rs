@@ -0,0 +1,64 @@
+//! Contains [`sniff`], which works out the codec of a track from the first few bytes of it,
+//! since the decoder doesn't say which one it used.
+
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The length of the header at the start of an `ID3v2` tag.
+const HEADER_SIZE: usize = 10;
+
+/// How much of the track is looked at, in bytes.
+///
+/// MP4 files list their codec in the `moov` box, which is near the start
+/// for anything meant to be streamed, but can also be at the very end.
+const WINDOW: u64 = 16 * 1024;
+
+/// Works out which codec `data` is encoded with, and then seeks back to the start.
+///
+/// This is [`None`] if it's something that lowfi doesn't know about.
+/// If the track is still downloading, this blocks until the start of it has been downloaded.
+pub fn sniff<R: Read + Seek>(data: &mut R) -> io::Result<Option<&'static str>> {
+    // Tags can be in front of both MP3 & AAC, so they're skipped.
+    let mut header = [0; HEADER_SIZE];
+    let offset = match data.read_exact(&mut header) {
+        // Like in `tags`, the size doesn't include the header, and only 7 bits of each byte are used.
+        Ok(()) if header.starts_with(b"ID3") => {
+            header[6..]
+                .iter()
+                .fold(0, |size, x| (size << 7u8) | u64::from(x & 0x7f))
+                + HEADER_SIZE as u64
+        }
+        _ => 0,
+    };
+
+    data.seek(SeekFrom::Start(offset))?;
+    let mut start = Vec::new();
+    data.by_ref().take(WINDOW).read_to_end(&mut start)?;
+    data.seek(SeekFrom::Start(0))?;
+
+    Ok(identify(&start))
+}
+
+/// Works out the codec from the `start` of a track, after any tags.
+fn identify(start: &[u8]) -> Option<&'static str> {
+    let contains = |needle: &[u8]| start.windows(needle.len()).any(|x| x == needle);
+
+    if start.starts_with(b"fLaC") {
+        Some("FLAC")
+    } else if start.starts_with(b"OggS") {
+        Some(if contains(b"OpusHead") {
+            "Opus"
+        } else {
+            "Vorbis"
+        })
+    } else if start.starts_with(b"RIFF") && start.get(8..12) == Some(b"WAVE") {
+        Some("WAV")
+    } else if start.get(4..8) == Some(b"ftyp") {
+        // Almost every M4A file is AAC, so that's assumed if the codec isn't found.
+        Some(if contains(b"alac") { "ALAC" } else { "AAC" })
+    } else if let [0xff, second, ..] = start {
+        // MPEG frames start with eleven set bits, and AAC uses the same ones with a layer of zero.
+        (second & 0xe0 == 0xe0).then_some(if second & 0x06 == 0 { "AAC" } else { "MP3" })
+    } else {
+        None
+    }
+}
@@ -0,0 +1,108 @@
+//! Contains the [`Deck`], which is how tracks are picked with `--shuffle deck`.
+
+use std::{fmt::Write, path::PathBuf, sync::Mutex};
+
+use clap::ValueEnum;
+use rand::{rngs::OsRng, seq::SliceRandom};
+use tokio::fs;
+
+use crate::play;
+
+/// How the next track is picked from the list.
+#[derive(Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Shuffle {
+    /// Each track is picked at random, favoring the ones which have been played less.
+    #[default]
+    Weighted,
+
+    /// The whole list is shuffled like a deck of cards and played through in that order,
+    /// so no track comes up twice until every other one has been played.
+    Deck,
+}
+
+/// The tracks of a list which haven't been drawn yet, in the order they'll be played in.
+///
+/// The deck is saved when lowfi quits, so the next session keeps going through the same one.
+/// `decks/<list name>.txt` has the length of the list on the first line, and then the index
+/// of one remaining track per line, where the last one is drawn first.
+pub struct Deck {
+    /// The name of the list, which the deck is saved under.
+    name: String,
+
+    /// How many tracks the list has.
+    len: usize,
+
+    /// The indices of the tracks which haven't been drawn yet.
+    remaining: Mutex<Vec<usize>>,
+}
+
+impl Deck {
+    /// Retrieves the path to the saved deck of the list called `name`.
+    fn path(name: &str) -> eyre::Result<PathBuf> {
+        Ok(play::data_dir()?.join("decks").join(format!("{name}.txt")))
+    }
+
+    /// Loads the deck of the list called `name`, which has `len` tracks.
+    ///
+    /// If the list has changed length since the deck was saved, then the indices won't line
+    /// up with the right tracks anymore, so the deck is thrown out and shuffled again.
+    pub async fn load(name: &str, len: usize) -> eyre::Result<Self> {
+        let path = Self::path(name)?;
+
+        let remaining = if path.exists() {
+            let contents = fs::read_to_string(path).await?;
+            let mut lines = contents.lines();
+
+            if lines.next().and_then(|x| x.trim().parse().ok()) == Some(len) {
+                lines
+                    .filter_map(|x| x.trim().parse().ok())
+                    .filter(|x| *x < len)
+                    .collect()
+            } else {
+                Vec::new()
+            }
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            name: name.to_owned(),
+            len,
+            remaining: Mutex::new(remaining),
+        })
+    }
+
+    /// Saves the tracks which are left in the deck.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path(&self.name)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let contents = self.remaining.lock().unwrap().iter().fold(
+            format!("{}\n", self.len),
+            |mut output, index| {
+                writeln!(output, "{index}").unwrap();
+                output
+            },
+        );
+
+        play::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    /// Draws the index of the next track, shuffling a new deck if the last one ran out.
+    ///
+    /// The shuffle uses the operating system's random number generator, so
+    /// every order is equally likely, even for lists with lots of tracks.
+    pub fn draw(&self) -> usize {
+        let mut remaining = self.remaining.lock().unwrap();
+        if remaining.is_empty() {
+            remaining.extend(0..self.len);
+            remaining.shuffle(&mut OsRng);
+        }
+
+        remaining.pop().unwrap_or_default()
+    }
+}
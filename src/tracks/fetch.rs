@@ -0,0 +1,217 @@
+//! Contains the [`Fetcher`], which is what actually downloads
+//! the raw data of tracks, either from the web or from the [`Cache`].
+
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use reqwest::{header::RANGE, Client, RequestBuilder, StatusCode};
+use tokio::{
+    task,
+    time::{sleep, Instant},
+};
+
+use super::{cache::Cache, stream::Stream};
+
+/// How many times an interrupted download will be resumed before giving up.
+const MAX_RESUMES: usize = 3;
+
+/// Responsible for downloading the raw data of tracks.
+pub struct Fetcher {
+    /// The web client, which can contain a `UserAgent` & some
+    /// settings that help lowfi work more effectively.
+    client: Client,
+
+    /// The on-disk cache, which is checked before downloading anything.
+    cache: Cache,
+
+    /// The maximum download speed, in bytes per second.
+    max_bandwidth: Option<u64>,
+}
+
+impl Fetcher {
+    /// Creates a new [`Fetcher`].
+    ///
+    /// * `timeout` - How long to wait for a request before giving up.
+    /// * `cache` - The [`Cache`], which is checked before downloading anything.
+    /// * `max_bandwidth` - The maximum download speed in KB/s, if any.
+    pub fn new(timeout: Duration, cache: Cache, max_bandwidth: Option<u64>) -> eyre::Result<Self> {
+        // Throttled & streamed downloads can easily take longer than the timeout,
+        // so it only applies to connecting and to each individual read.
+        let client = Client::builder()
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .connect_timeout(timeout)
+            .read_timeout(timeout)
+            .build()?;
+
+        Ok(Self {
+            client,
+            cache,
+            max_bandwidth: max_bandwidth.map(|x| x.saturating_mul(1024).max(1)),
+        })
+    }
+
+    /// The web client, used to build requests for [`Fetcher::fetch`].
+    pub const fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Fetches the track at `url`, using requests built by `request` if it isn't already cached.
+    ///
+    /// If the download is interrupted partway through, it's resumed from where it
+    /// left off using a `Range` header, up to [`MAX_RESUMES`] times.
+    pub async fn fetch(
+        &self,
+        url: &str,
+        request: impl Fn() -> RequestBuilder,
+    ) -> reqwest::Result<Bytes> {
+        if let Some(data) = self.cache.get(url).await {
+            return Ok(data);
+        }
+
+        let mut data = BytesMut::new();
+        let mut resumes = 0;
+
+        loop {
+            let mut request = request();
+            if !data.is_empty() {
+                request = request.header(RANGE, format!("bytes={}-", data.len()));
+            }
+
+            match self.receive(request, &mut data).await {
+                Ok(()) => break,
+                // Errors with a status code come from the server, so trying again won't help.
+                Err(error)
+                    if error.status().is_none() && !data.is_empty() && resumes < MAX_RESUMES =>
+                {
+                    resumes += 1;
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let data = data.freeze();
+
+        // Failing to cache a track isn't a big deal, since it can
+        // still be played, so we'll just ignore any errors.
+        let _ = self.cache.put(url, &data).await;
+
+        Ok(data)
+    }
+
+    /// Fetches just the first `size` bytes of the track at `url`, which is
+    /// plenty to work out what a track sounds like without downloading all of it.
+    ///
+    /// Cached tracks are just cut short, otherwise a `Range` header asks for the start
+    /// of the track. Servers which ignore it are cut off once `size` bytes have arrived.
+    /// The start of a track isn't cached, since it can't be played on its own.
+    pub async fn prefix(
+        &self,
+        url: &str,
+        request: impl Fn() -> RequestBuilder,
+        size: usize,
+    ) -> reqwest::Result<Bytes> {
+        if let Some(data) = self.cache.get(url).await {
+            return Ok(data.slice(..size.min(data.len())));
+        }
+
+        let mut response = request()
+            .header(RANGE, format!("bytes=0-{}", size.saturating_sub(1)))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut data = BytesMut::new();
+        while data.len() < size {
+            let Some(chunk) = response.chunk().await? else {
+                break;
+            };
+
+            data.extend_from_slice(&chunk);
+        }
+
+        data.truncate(size);
+        Ok(data.freeze())
+    }
+
+    /// Sends `request`, and appends the body of the response to `data` chunk by chunk.
+    ///
+    /// If the server doesn't support `Range` requests and sends the whole track,
+    /// then `data` is cleared first. This also sleeps whenever the download gets
+    /// ahead of the maximum bandwidth.
+    async fn receive(&self, request: RequestBuilder, data: &mut BytesMut) -> reqwest::Result<()> {
+        let mut response = request.send().await?.error_for_status()?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            data.clear();
+        }
+
+        let start = Instant::now();
+        let mut received = 0;
+
+        while let Some(chunk) = response.chunk().await? {
+            data.extend_from_slice(&chunk);
+            received += chunk.len();
+
+            throttle(self.max_bandwidth, start, received).await;
+        }
+
+        Ok(())
+    }
+
+    /// Starts downloading the track at `url` in the background, returning a
+    /// [`Stream`] which can be decoded & played before the download is finished.
+    ///
+    /// Unlike [`Fetcher::fetch`], interrupted downloads aren't resumed, since
+    /// by then the track is usually already playing.
+    pub async fn stream(
+        &self,
+        url: &str,
+        request: impl Fn() -> RequestBuilder,
+    ) -> reqwest::Result<Stream> {
+        if let Some(data) = self.cache.get(url).await {
+            return Ok(Stream::complete(data));
+        }
+
+        let mut response = request().send().await?.error_for_status()?;
+        let (writer, stream) = Stream::new();
+
+        let cache = self.cache.clone();
+        let max_bandwidth = self.max_bandwidth;
+        let url = url.to_owned();
+
+        task::spawn(async move {
+            let start = Instant::now();
+            let mut received = 0;
+
+            loop {
+                match response.chunk().await {
+                    Ok(Some(chunk)) => {
+                        writer.push(&chunk);
+                        received += chunk.len();
+
+                        throttle(max_bandwidth, start, received).await;
+                    }
+                    Ok(None) => break,
+                    // Dropping the writer early lets the stream know that the download failed.
+                    Err(_) => return,
+                }
+            }
+
+            let _ = cache.put(&url, &writer.finish()).await;
+        });
+
+        Ok(stream)
+    }
+}
+
+/// Sleeps whenever `received` bytes since `start` is ahead of `max_bandwidth`,
+/// which is in bytes per second.
+async fn throttle(max_bandwidth: Option<u64>, start: Instant, received: usize) {
+    if let Some(max) = max_bandwidth {
+        let expected = Duration::from_secs_f64(received as f64 / max as f64);
+        sleep(expected.saturating_sub(start.elapsed())).await;
+    }
+}
@@ -0,0 +1,120 @@
+//! Contains the [`Naming`] rules, which control how the display
+//! names of tracks are derived from their paths.
+
+use inflector::Inflector;
+use serde::Deserialize;
+use url::form_urlencoded;
+
+/// The file extensions which are taken off the end of track names.
+const EXTENSIONS: [&str; 6] = [".mp3", ".m4a", ".aac", ".flac", ".ogg", ".wav"];
+
+/// How display names are derived from track paths.
+///
+/// The defaults work well for Lofi Girl's tracks, but they can be changed
+/// per list with `@name` directives, or a `[naming]` table in structured lists.
+#[derive(Clone, Deserialize)]
+#[serde(default)]
+pub struct Naming {
+    /// Whether to strip the numbers which are at the start of most lofi tracks.
+    pub strip_numbers: bool,
+
+    /// Text which is removed from names, ignoring case, like `master`.
+    pub remove: Vec<String>,
+
+    /// The text which separates the artist from the title, like `-`.
+    ///
+    /// If this is set, both halves are formatted separately,
+    /// and then shown as `Artist - Title`.
+    pub artist: Option<String>,
+}
+
+impl Default for Naming {
+    fn default() -> Self {
+        Self {
+            strip_numbers: true,
+            remove: Vec::new(),
+            artist: None,
+        }
+    }
+}
+
+impl Naming {
+    /// Decodes a URL string into normal UTF-8.
+    fn decode_url(text: &str) -> String {
+        form_urlencoded::parse(text.as_bytes())
+            .map(|(key, val)| [key, val].concat())
+            .collect()
+    }
+
+    /// Removes every occurence of `pattern` from `text`, ignoring ASCII case.
+    fn remove(text: &str, pattern: &str) -> String {
+        if pattern.is_empty() {
+            return text.to_owned();
+        }
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(character) = rest.chars().next() {
+            let matched = rest
+                .get(..pattern.len())
+                .is_some_and(|x| x.eq_ignore_ascii_case(pattern));
+
+            let skip = if matched {
+                pattern.len()
+            } else {
+                result.push(character);
+                character.len_utf8()
+            };
+
+            rest = rest.get(skip..).unwrap_or_default();
+        }
+
+        result
+    }
+
+    /// Formats a single part of a name with [Inflector].
+    fn title(text: &str) -> String {
+        text.to_lowercase()
+            .to_title_case()
+            // Inflector doesn't like contractions...
+            // Replaces a few very common ones.
+            // TODO: Properly handle these.
+            .replace(" S ", "'s ")
+            .replace(" T ", "'t ")
+            .replace(" D ", "'d ")
+            .replace(" Ve ", "'ve ")
+            .replace(" Ll ", "'ll ")
+            .replace(" Re ", "'re ")
+            .replace(" M ", "'m ")
+    }
+
+    /// Formats the path of a track into a display name, following the rules.
+    pub fn format(&self, path: &str) -> String {
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let name = EXTENSIONS
+            .iter()
+            .find_map(|x| name.strip_suffix(x))
+            .unwrap_or(name);
+
+        let name = self
+            .remove
+            .iter()
+            .fold(Self::decode_url(name), |name, pattern| {
+                Self::remove(&name, pattern)
+            });
+
+        let formatted = match self.artist.as_deref().and_then(|x| name.split_once(x)) {
+            Some((artist, title)) => format!("{} - {}", Self::title(artist), Self::title(title)),
+            None => Self::title(&name),
+        };
+
+        if self.strip_numbers {
+            formatted
+                .trim_start_matches(|x: char| x.is_ascii_digit())
+                .to_owned()
+        } else {
+            formatted
+        }
+    }
+}
@@ -1,13 +1,30 @@
 //! The module containing all of the logic behind track lists,
 //! as well as obtaining track names & downloading the raw mp3 data.
 
+use std::{fmt::Write, iter, path::PathBuf, sync::Arc};
+
 use bytes::Bytes;
-use eyre::OptionExt;
-use rand::Rng;
-use reqwest::Client;
+use eyre::{bail, eyre, OptionExt};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    RequestBuilder,
+};
 use tokio::fs;
 
-use super::Track;
+use lines::Lines;
+use structured::{Format, Metadata, Structured};
+
+use super::{deck::Deck, fetch::Fetcher, format::Naming, plays::Plays, stream::Stream, Track};
+use crate::play;
+
+pub mod lines;
+mod remote;
+pub mod structured;
+
+/// The maximum amount of times a track can be picked and rejected
+/// before just going with whatever was picked last.
+const PICK_ATTEMPTS: usize = 16;
 
 /// Represents a list of tracks that can be played.
 ///
@@ -19,86 +36,601 @@ pub struct List {
 
     /// Just the raw file, but seperated by `/n` (newlines).
     /// `lines[0]` is the base, with the rest being tracks.
-    lines: Vec<String>,
+    ///
+    /// These are kept exactly as they're written, so display names
+    /// are only worked out once a track is actually picked.
+    lines: Lines,
+
+    /// Extra HTTP headers which are sent along with every request for this list,
+    /// specified with `@header Name: Value` directives.
+    headers: HeaderMap,
+
+    /// The username & password used for basic authentication,
+    /// specified with an `@auth username:password` directive.
+    auth: Option<(String, Option<String>)>,
+
+    /// How the display names of tracks are derived from their paths,
+    /// which can be changed with `@name` directives.
+    naming: Arc<Naming>,
+
+    /// How likely each track is to be picked, which is only available in
+    /// [`Structured`] lists. `weights` index `0` corresponds to `lines[1]`.
+    weights: Option<WeightedIndex<f32>>,
+
+    /// The artist, album & source of each track, which is also only available
+    /// in [`Structured`] lists. Like `weights`, index `0` corresponds to `lines[1]`.
+    metadata: Option<Arc<[Metadata]>>,
 }
 
 impl List {
     /// Gets the base URL of the [List].
     pub fn base(&self) -> &str {
-        self.lines[0].trim()
+        self.lines.get(0).trim()
     }
 
-    /// Gets the name of a random track.
-    fn random_name(&self) -> String {
+    /// Checks whether a line in a list is a directive, like `@header`, rather than a track.
+    pub fn is_directive(line: &str) -> bool {
+        line.trim_start().starts_with('@')
+    }
+
+    /// Checks whether a line in a list is a `#` comment or a track that's been
+    /// disabled with a `;`, both of which are ignored when playing.
+    pub fn is_comment(line: &str) -> bool {
+        let line = line.trim_start();
+        line.starts_with('#') || line.starts_with(';')
+    }
+
+    /// Applies a single directive line to the [List].
+    fn directive(&mut self, line: &str) -> eyre::Result<()> {
+        let line = line.trim();
+        let (directive, value) = line
+            .split_once(' ')
+            .map_or((line, ""), |(x, y)| (x, y.trim()));
+
+        match directive {
+            "@header" => {
+                let (key, value) = value.split_once(':').ok_or_else(|| {
+                    eyre!("invalid header directive {value:?}, expected `Name: Value`")
+                })?;
+
+                self.headers.insert(
+                    HeaderName::from_bytes(key.trim().as_bytes())?,
+                    HeaderValue::from_str(value.trim())?,
+                );
+            }
+            "@auth" => {
+                self.auth = Some(match value.split_once(':') {
+                    Some((username, password)) => (username.to_owned(), Some(password.to_owned())),
+                    None => (value.to_owned(), None),
+                });
+            }
+            "@name" => self.naming(value)?,
+            _ => bail!("unknown directive {directive:?} in list {:?}", self.name),
+        }
+
+        Ok(())
+    }
+
+    /// Applies a single `@name` rule, like `remove master`, to the [`Naming`].
+    fn naming(&mut self, rule: &str) -> eyre::Result<()> {
+        let (rule, value) = rule
+            .split_once(' ')
+            .map_or((rule, ""), |(x, y)| (x, y.trim()));
+
+        let naming = Arc::make_mut(&mut self.naming);
+        match rule {
+            "keep-numbers" => naming.strip_numbers = false,
+            "remove" if !value.is_empty() => naming.remove.push(value.to_owned()),
+            "artist" if !value.is_empty() => naming.artist = Some(value.to_owned()),
+            "remove" | "artist" => bail!("the {rule:?} naming rule needs a value"),
+            _ => bail!("unknown naming rule {rule:?} in list {:?}", self.name),
+        }
+
+        Ok(())
+    }
+
+    /// Splits a track entry into it's path & custom display name.
+    ///
+    /// Custom names are specified by putting a `!` after the path,
+    /// for example `2023/04/2-In-Front-Of-Me.mp3!In Front of Me`.
+    pub fn split_entry(entry: &str) -> (&str, Option<&str>) {
+        match entry.split_once('!') {
+            Some((path, name)) => (path.trim(), Some(name.trim())),
+            None => (entry.trim(), None),
+        }
+    }
+
+    /// Picks the index of a random track, taking weights into account.
+    fn random_index(&self, rng: &mut impl Rng) -> usize {
         // We're getting from 1 here, since the base is at `self.lines[0]`.
         //
         // We're also not pre-trimming `self.lines` into `base` & `tracks` due to
         // how rust vectors work, sinceslow to drain only a single element from
         // the start, so it's faster to just keep it in & work around it.
-        let random = rand::thread_rng().gen_range(1..self.lines.len());
-        self.lines[random].clone()
+        match &self.weights {
+            Some(weights) => weights.sample(rng) + 1,
+            None => rng.gen_range(1..self.lines.len()),
+        }
     }
 
-    /// Downloads a raw track, but doesn't decode it.
-    async fn download(&self, track: &str, client: &Client) -> reqwest::Result<Bytes> {
+    /// Gets the index of a random track in `lines`.
+    ///
+    /// Tracks which have been played less are more likely to be picked,
+    /// so that long sessions cycle through more of the list.
+    ///
+    /// This works by rejecting picked tracks with a chance based on how
+    /// many times they've been played, which avoids having to go through
+    /// the whole list for every pick.
+    fn pick(&self, plays: &Plays) -> usize {
+        let mut rng = rand::thread_rng();
+        let mut random = self.random_index(&mut rng);
+
+        for _ in 1..PICK_ATTEMPTS {
+            let (name, _) = Self::split_entry(self.lines.get(random));
+            let count = plays.get(&self.path(name));
+
+            if rng.gen_bool(1.0 / f64::from(count + 1)) {
+                break;
+            }
+
+            random = self.random_index(&mut rng);
+        }
+
+        random
+    }
+
+    /// Gets the [`Metadata`] of the track at `index` in `lines`, which is
+    /// always empty for plain text lists.
+    fn metadata(&self, index: usize) -> Metadata {
+        self.metadata
+            .as_ref()
+            .and_then(|x| x.get(index.checked_sub(1)?))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Gets the full path of a track, which is usually
+    /// just the base URL with the track appended.
+    fn path(&self, track: &str) -> String {
         // If the track has a protocol, then we should ignore the base for it.
-        let url = if track.contains("://") {
+        if track.contains("://") {
             track.to_owned()
         } else {
             format!("{}{}", self.base(), track)
-        };
+        }
+    }
+
+    /// Percent-encodes everything in `path` which isn't allowed in the path of a URL,
+    /// like spaces & unicode, as well as `#` & `?` which would otherwise start
+    /// a fragment or query. Anything which is already encoded is left alone.
+    pub fn encode(path: &str) -> String {
+        /// The characters which can be in a path as is, besides letters & numbers.
+        const ALLOWED: &[u8] = b"-._~/!$&'()*+,;=:@";
 
-        let response = client.get(url).send().await?;
-        let data = response.bytes().await?;
+        let bytes = path.as_bytes();
+        let mut encoded = String::with_capacity(path.len());
 
-        Ok(data)
+        for (i, byte) in bytes.iter().copied().enumerate() {
+            let escape = byte == b'%'
+                && bytes
+                    .get(i + 1..i + 3)
+                    .is_some_and(|x| x.iter().all(u8::is_ascii_hexdigit));
+
+            if escape || byte.is_ascii_alphanumeric() || ALLOWED.contains(&byte) {
+                encoded.push(char::from(byte));
+            } else {
+                write!(encoded, "%{byte:02X}").unwrap();
+            }
+        }
+
+        encoded
+    }
+
+    /// Gets the URL that's actually requested for a track, where the part
+    /// after the base is encoded with [`List::encode`].
+    ///
+    /// Full URLs are left as they are, since they might have a real query string.
+    /// This isn't used for the full path itself, so that it stays readable and
+    /// matches what's already in the cache & `plays.txt`.
+    fn url(&self, full_path: &str) -> String {
+        match full_path.strip_prefix(self.base()) {
+            Some(track) if !self.base().is_empty() => {
+                format!("{}{}", self.base(), Self::encode(track))
+            }
+            _ => full_path.to_owned(),
+        }
+    }
+
+    /// Downloads a raw track, but doesn't decode it.
+    ///
+    /// If `stream` is true, this returns as soon as the download has started.
+    ///
+    /// This also applies any extra headers & authentication specified in the list.
+    pub async fn download(
+        &self,
+        url: &str,
+        fetcher: &Fetcher,
+        stream: bool,
+    ) -> reqwest::Result<Stream> {
+        let encoded = self.url(url);
+        let request = || self.request(fetcher, &encoded);
+
+        if stream {
+            fetcher.stream(url, request).await
+        } else {
+            fetcher.fetch(url, request).await.map(Stream::complete)
+        }
+    }
+
+    /// Downloads only the first `size` bytes of a raw track, see [`Fetcher::prefix`].
+    pub async fn prefix(
+        &self,
+        url: &str,
+        fetcher: &Fetcher,
+        size: usize,
+    ) -> reqwest::Result<Bytes> {
+        let encoded = self.url(url);
+        fetcher
+            .prefix(url, || self.request(fetcher, &encoded), size)
+            .await
+    }
+
+    /// Builds a request for the already encoded `url`, with the extra headers & authentication.
+    fn request(&self, fetcher: &Fetcher, url: &str) -> RequestBuilder {
+        let request = fetcher.client().get(url).headers(self.headers.clone());
+        match &self.auth {
+            Some((username, password)) => request.basic_auth(username, password.as_ref()),
+            None => request,
+        }
     }
 
     /// Fetches and downloads a random track from the [List].
-    pub async fn random(&self, client: &Client) -> reqwest::Result<Track> {
-        let name = self.random_name();
-        let data = self.download(&name, client).await?;
+    ///
+    /// If there's a `deck`, then the track is drawn from it. Otherwise, `plays` is used
+    /// to favor tracks which haven't been played as much. `stream` is whether the track
+    /// can be played before it's downloaded.
+    pub async fn random(
+        &self,
+        fetcher: &Fetcher,
+        plays: &Plays,
+        deck: Option<&Deck>,
+        stream: bool,
+    ) -> reqwest::Result<Track> {
+        // The deck doesn't know about the base, which is at `self.lines[0]`.
+        let index = deck.map_or_else(|| self.pick(plays), |deck| deck.draw() + 1);
+        let (name, custom_name) = Self::split_entry(self.lines.get(index));
+        let full_path = self.path(name);
+        let data = self.download(&full_path, fetcher, stream).await?;
+
+        Ok(Track {
+            name: name.to_owned(),
+            custom_name: custom_name.map(ToOwned::to_owned),
+            naming: Arc::clone(&self.naming),
+            metadata: self.metadata(index),
+            full_path,
+            data,
+        })
+    }
 
-        Ok(Track { name, data })
+    /// Downloads the track with the full path `full_path` again, which
+    /// was previously played with the display name `name`.
+    pub async fn track(
+        &self,
+        full_path: &str,
+        name: &str,
+        fetcher: &Fetcher,
+    ) -> reqwest::Result<Track> {
+        let data = self.download(full_path, fetcher, false).await?;
+
+        // Only the full path is known, so the track has to be found again to get its metadata.
+        let metadata = match self.metadata {
+            Some(_) => self
+                .paths()
+                .position(|x| x == full_path)
+                .map(|x| self.metadata(x + 1))
+                .unwrap_or_default(),
+            None => Metadata::default(),
+        };
+
+        Ok(Track {
+            name: full_path.to_owned(),
+            custom_name: Some(name.to_owned()),
+            naming: Arc::clone(&self.naming),
+            metadata,
+            full_path: full_path.to_owned(),
+            data,
+        })
+    }
+
+    /// Returns the amount of tracks in the [List], not counting the base.
+    pub fn len(&self) -> usize {
+        self.lines.len().saturating_sub(1)
+    }
+
+    /// Gets the full paths of every track in the [List].
+    pub fn paths(&self) -> impl Iterator<Item = String> + '_ {
+        self.lines
+            .iter()
+            .skip(1)
+            .map(|entry| self.path(Self::split_entry(entry).0))
+    }
+
+    /// Formats the path of a track into a display name, with the list's naming rules.
+    pub fn display_name(&self, path: &str) -> String {
+        self.naming.format(path)
+    }
+
+    /// The extra HTTP headers which are sent along with every request.
+    pub const fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    /// The username used for basic authentication, if there is one.
+    pub fn username(&self) -> Option<&str> {
+        self.auth.as_ref().map(|(username, _)| username.as_str())
+    }
+
+    /// Returns whether the [List] has no tracks, only a base.
+    pub fn is_empty(&self) -> bool {
+        self.lines.len() <= 1
+    }
+
+    /// Starts an empty [List] called `name`, which doesn't even have a base yet.
+    ///
+    /// Lines are added with [`List::push`], and then [`List::finish`] is called once they're all in.
+    fn named(name: &str) -> Self {
+        Self {
+            lines: Lines::default(),
+            name: name.to_owned(),
+            headers: HeaderMap::new(),
+            auth: None,
+            naming: Arc::default(),
+            weights: None,
+            metadata: None,
+        }
+    }
+
+    /// Adds the next line of a plain text list, see [`List::new`].
+    fn push(&mut self, line: &str) -> eyre::Result<()> {
+        let line = line.trim();
+
+        // The first line is always the base, even if it looks like something else.
+        let base = self.lines.is_empty();
+        if !base && Self::is_directive(line) {
+            return self.directive(line);
+        }
+
+        if base || (!line.is_empty() && !Self::is_comment(line)) {
+            self.lines.push(line);
+        }
+
+        Ok(())
+    }
+
+    /// Finishes a [List] once all of its lines have been pushed,
+    /// making sure that it at least has an empty base.
+    fn finish(mut self) -> Self {
+        if self.lines.is_empty() {
+            self.lines.push("");
+        }
+
+        self.lines.shrink_to_fit();
+        self
     }
 
     /// Parses text into a [List].
-    pub fn new(name: &str, text: &str) -> Self {
-        let lines: Vec<String> = text
-            .split_ascii_whitespace()
-            .map(ToOwned::to_owned)
+    ///
+    /// The first line is always kept as the base, even if it's empty,
+    /// while empty lines, comments & disabled tracks after it are ignored.
+    ///
+    /// Any directives, like `@header`, are applied and aren't treated as tracks.
+    pub fn new(name: &str, text: &str) -> eyre::Result<Self> {
+        let mut list = Self::named(name);
+        for line in text.lines() {
+            list.push(line)?;
+        }
+
+        Ok(list.finish())
+    }
+
+    /// Creates a [List] from tracks which are all full URLs, so no base is needed.
+    ///
+    /// Like in [`List::new`], comments & disabled tracks are skipped.
+    pub fn from_paths(name: &str, paths: &[String]) -> Self {
+        let lines = iter::once("")
+            .chain(
+                paths
+                    .iter()
+                    .map(String::as_str)
+                    .filter(|x| !Self::is_comment(x)),
+            )
             .collect();
 
         Self {
             lines,
             name: name.to_owned(),
+            headers: HeaderMap::new(),
+            auth: None,
+            naming: Arc::default(),
+            weights: None,
+            metadata: None,
         }
     }
 
-    /// Reads a [List] from the filesystem using the CLI argument provided.
+    /// Gets the path of a list with the name `name` in the data directory,
+    /// which is usually `~/.local/share/lowfi/<name>.txt`.
+    pub fn data_path(name: &str) -> eyre::Result<PathBuf> {
+        Ok(play::data_dir()?.join(format!("{name}.txt")))
+    }
+
+    /// Finds a list using the CLI argument provided.
+    ///
+    /// This first checks the data directory for a list with any of the
+    /// supported extensions, and otherwise treats the argument as a path.
+    fn find(arg: &str) -> eyre::Result<PathBuf> {
+        let text = Self::data_path(arg)?;
+
+        for extension in ["txt", "toml", "json"] {
+            let path = text.with_extension(extension);
+            if path.exists() {
+                return Ok(path);
+            }
+        }
+
+        Ok(arg.into())
+    }
+
+    /// Reads a [List] from the filesystem using the CLI argument provided,
+    /// or downloads it if the argument is a URL.
+    ///
+    /// `.toml` & `.json` lists are parsed as [`Structured`] lists,
+    /// while anything else uses the plain text format.
     pub async fn load(tracks: &Option<String>) -> eyre::Result<Self> {
         if let Some(arg) = tracks {
-            // Check if the track is in ~/.local/share/lowfi, in which case we'll load that.
-            let name = dirs::data_dir()
-                .unwrap()
-                .join("lowfi")
-                .join(format!("{}.txt", arg));
+            let url = remote::is_url(arg);
 
-            let name = if name.exists() { name } else { arg.into() };
-
-            let raw = fs::read_to_string(name.clone()).await?;
+            // Check if the track is in ~/.local/share/lowfi, in which case we'll load that.
+            let path = if url {
+                remote::path(arg)?
+            } else {
+                Self::find(arg)?
+            };
 
-            let name = name
+            let name = path
                 .file_stem()
                 .and_then(|x| x.to_str())
                 .ok_or_eyre("invalid track path")?;
 
-            Ok(Self::new(name, &raw))
+            let format = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .and_then(Format::from_extension);
+
+            // Plain text lists are parsed line by line as they're downloaded,
+            // but structured ones have to be downloaded in full first.
+            match (format, url) {
+                (Some(format), true) => {
+                    let mut raw = String::new();
+                    remote::download(arg, |line| {
+                        raw.push_str(line);
+                        raw.push('\n');
+                        Ok(())
+                    })
+                    .await?;
+
+                    Structured::parse(&raw, format)?.into_list(name)
+                }
+                (None, true) => {
+                    let mut list = Self::named(name);
+                    remote::download(arg, |line| list.push(line)).await?;
+
+                    Ok(list.finish())
+                }
+                (Some(format), false) => {
+                    Structured::parse(&fs::read_to_string(&path).await?, format)?.into_list(name)
+                }
+                (None, false) => Self::new(name, &fs::read_to_string(&path).await?),
+            }
         } else {
-            Ok(Self::new(
-                "lofigirl",
-                include_str!("../../data/lofigirl.txt"),
-            ))
+            Self::new("lofigirl", include_str!("../../data/lofigirl.txt"))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, List, Structured};
+
+    #[test]
+    fn comments() {
+        assert!(List::is_comment("# a comment"));
+        assert!(List::is_comment("  # an indented comment"));
+        assert!(List::is_comment(";disabled.mp3"));
+        assert!(List::is_comment("  ; disabled.mp3"));
+        assert!(!List::is_comment("track.mp3"));
+        assert!(!List::is_comment("track#1.mp3"));
+        assert!(!List::is_comment(""));
+    }
+
+    #[test]
+    fn directives() {
+        assert!(List::is_directive("@header Referer: https://example.com"));
+        assert!(List::is_directive("  @auth user:pass"));
+        assert!(!List::is_directive("track@home.mp3"));
+    }
+
+    #[test]
+    fn parse() {
+        let text = "https://example.com/\n\
+            # a comment\n\
+            first.mp3\n\
+            \n\
+            ;disabled.mp3\n\
+            @auth user:pass\n\
+            @header Referer: https://example.com\n  \n\
+            second.mp3!Second\n";
+
+        let list = List::new("test", text).unwrap();
+        assert_eq!(list.base(), "https://example.com/");
+        assert_eq!(list.len(), 2);
+        assert_eq!(
+            list.paths().collect::<Vec<_>>(),
+            [
+                "https://example.com/first.mp3",
+                "https://example.com/second.mp3"
+            ]
+        );
+        assert_eq!(list.username(), Some("user"));
+        assert_eq!(list.headers()["referer"], "https://example.com");
+    }
+
+    #[test]
+    fn empty_base() {
+        let list = List::new("test", "\n# only a comment\n\n").unwrap();
+        assert_eq!(list.base(), "");
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn metadata() {
+        let text = r#"
+            base = "https://example.com/"
+            tracks = [
+                "first.mp3",
+                { path = "second.mp3", artist = "Someone", source = "example.com" },
+            ]
+        "#;
+
+        let list = Structured::parse(text, Format::Toml)
+            .unwrap()
+            .into_list("test")
+            .unwrap();
+        assert!(list.metadata(1).artist.is_none());
+        assert_eq!(list.metadata(2).artist.as_deref(), Some("Someone"));
+        assert_eq!(list.metadata(2).source.as_deref(), Some("example.com"));
+        assert!(list.metadata(2).album.is_none());
+    }
+
+    #[test]
+    fn encode() {
+        assert_eq!(List::encode("2023/01/track.mp3"), "2023/01/track.mp3");
+        assert_eq!(List::encode("Some Track.mp3"), "Some%20Track.mp3");
+        assert_eq!(List::encode("caf\u{e9}.mp3"), "caf%C3%A9.mp3");
+        assert_eq!(List::encode("\u{305f}.mp3"), "%E3%81%9F.mp3");
+        assert_eq!(List::encode("what?#1.mp3"), "what%3F%231.mp3");
+    }
+
+    #[test]
+    fn encode_encoded() {
+        assert_eq!(List::encode("Some%20Track.mp3"), "Some%20Track.mp3");
+        assert_eq!(List::encode("caf%C3%A9.mp3"), "caf%C3%A9.mp3");
+        assert_eq!(List::encode("100%.mp3"), "100%25.mp3");
+        assert_eq!(List::encode("%zz.mp3"), "%25zz.mp3");
+        assert_eq!(List::encode(&List::encode("a b")), "a%20b");
+    }
+
+    #[test]
+    fn unknown_directive() {
+        assert!(List::new("test", "\n@nope value\ntrack.mp3").is_err());
+    }
+}
@@ -0,0 +1,65 @@
+//! Contains [`Lines`], the compact way that the tracks of a [`super::List`] are stored.
+
+/// A list of lines which are all stored in one [`String`], rather than each being their own.
+///
+/// Generated lists can have hundreds of thousands of tracks, and giving each one its
+/// own allocation uses several times more memory than the text itself. Getting a line
+/// by its index is still O(1), since the end of each line is stored.
+#[derive(Clone, Default)]
+pub struct Lines {
+    /// Every line, one after the other without any separators.
+    text: String,
+
+    /// Where each line ends in `text`, so line `i` is from `ends[i - 1]` to `ends[i]`.
+    ends: Vec<usize>,
+}
+
+impl Lines {
+    /// Adds a line to the end.
+    pub fn push(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.ends.push(self.text.len());
+    }
+
+    /// Gets the line at `index`.
+    ///
+    /// This panics if `index` is out of bounds, just like indexing a [`Vec`].
+    pub fn get(&self, index: usize) -> &str {
+        let start = index.checked_sub(1).map_or(0, |x| self.ends[x]);
+        // The ends are always in between characters, so this can't actually fail.
+        self.text.get(start..self.ends[index]).unwrap_or_default()
+    }
+
+    /// Gets how many lines there are.
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Whether there aren't any lines at all.
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Frees up any extra space, once all of the lines have been added.
+    pub fn shrink_to_fit(&mut self) {
+        self.text.shrink_to_fit();
+        self.ends.shrink_to_fit();
+    }
+
+    /// Iterates over all of the lines, in order.
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        (0..self.len()).map(|x| self.get(x))
+    }
+}
+
+impl<S: AsRef<str>> FromIterator<S> for Lines {
+    fn from_iter<T: IntoIterator<Item = S>>(iter: T) -> Self {
+        let mut lines = Self::default();
+        for line in iter {
+            lines.push(line.as_ref());
+        }
+
+        lines.shrink_to_fit();
+        lines
+    }
+}
@@ -0,0 +1,109 @@
+//! Downloads track lists from the web, for when `--tracks` is a URL rather than a path.
+
+use std::{
+    io::{stderr, IsTerminal, Write},
+    path::PathBuf,
+    str,
+    time::Duration,
+};
+
+use eyre::{bail, eyre};
+use reqwest::Client;
+use url::Url;
+
+/// The biggest a remote list is allowed to be, in megabytes.
+///
+/// Even the largest generated lists are a few megabytes, so anything bigger than this
+/// is either a mistake or an attempt to make lowfi run out of memory.
+const MAX_SIZE_MB: usize = 32;
+
+/// [`MAX_SIZE_MB`] in bytes.
+const MAX_SIZE: usize = MAX_SIZE_MB * 1024 * 1024;
+
+/// How long to wait for the server to connect or send more of the list.
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Whether `arg` is a URL, which means the list should be downloaded rather than read.
+pub fn is_url(arg: &str) -> bool {
+    arg.starts_with("https://") || arg.starts_with("http://")
+}
+
+/// Gets the path part of `url`, which the name & format of the list are taken from.
+pub fn path(url: &str) -> eyre::Result<PathBuf> {
+    let url = Url::parse(url)?;
+    Ok(url.path().into())
+}
+
+/// Downloads the list at `url`, a chunk at a time, giving each line to `line`
+/// as soon as it's arrived, so that the whole list never has to be held at once.
+///
+/// This gives up as soon as the list gets bigger than [`MAX_SIZE`], rather than
+/// after it's all been downloaded. If `stderr` is a terminal, then how much
+/// has been downloaded so far is shown while waiting.
+pub async fn download(
+    url: &str,
+    mut line: impl FnMut(&str) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    let client = Client::builder()
+        .user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ))
+        .connect_timeout(TIMEOUT)
+        .read_timeout(TIMEOUT)
+        .build()?;
+
+    let mut response = client.get(url).send().await?.error_for_status()?;
+
+    // The server might be kind enough to say how big the list is up front.
+    if response
+        .content_length()
+        .is_some_and(|x| x > MAX_SIZE as u64)
+    {
+        bail!("the list at {url} is bigger than the limit of {MAX_SIZE_MB} MB");
+    }
+
+    let progress = stderr().is_terminal();
+    let mut size = 0;
+
+    // The end of the last chunk, which might be in the middle of a line.
+    let mut pending = Vec::new();
+
+    while let Some(chunk) = response.chunk().await? {
+        size += chunk.len();
+        if size > MAX_SIZE {
+            bail!("the list at {url} is bigger than the limit of {MAX_SIZE_MB} MB");
+        }
+
+        pending.extend_from_slice(&chunk);
+        if let Some(end) = pending.iter().rposition(|x| *x == b'\n') {
+            let rest = pending.split_off(end + 1);
+            lines(url, &pending, &mut line)?;
+            pending = rest;
+        }
+
+        if progress {
+            eprint!("\rdownloading list... {} KB", size / 1024);
+            stderr().flush()?;
+        }
+    }
+
+    if progress {
+        eprintln!();
+    }
+
+    lines(url, &pending, &mut line)
+}
+
+/// Gives each line in `bytes` to `line`, where `bytes` only has whole lines from the list at `url`.
+fn lines(
+    url: &str,
+    bytes: &[u8],
+    line: &mut impl FnMut(&str) -> eyre::Result<()>,
+) -> eyre::Result<()> {
+    let text = str::from_utf8(bytes)
+        .map_err(|error| eyre!("the list at {url} isn't valid UTF-8: {error}"))?;
+
+    text.lines().try_for_each(line)
+}
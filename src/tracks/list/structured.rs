@@ -0,0 +1,199 @@
+//! Contains the structured track list format, which can be written in either TOML or JSON.
+//!
+//! This is converted into the same internal representation as the plain text format,
+//! so the rest of lowfi doesn't have to care which one was used.
+
+use std::{collections::BTreeMap, iter, sync::Arc};
+
+use eyre::eyre;
+use rand::distributions::WeightedIndex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Deserialize;
+
+use super::List;
+use crate::tracks::format::Naming;
+
+/// The different languages a [`Structured`] list can be written in.
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// A `.toml` file.
+    Toml,
+
+    /// A `.json` file.
+    Json,
+}
+
+impl Format {
+    /// Gets the format from a file extension, returning [`None`] for plain text lists.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// The username & password used for basic authentication.
+#[derive(Deserialize)]
+struct Auth {
+    /// The username.
+    username: String,
+
+    /// The password, which is optional.
+    password: Option<String>,
+}
+
+/// Extra information about a track, which can only be given in a [`Structured`] list.
+///
+/// Like custom names, this takes priority over whatever is in the track's own tags.
+#[derive(Clone, Default)]
+pub struct Metadata {
+    /// The artist who made the track.
+    pub artist: Option<String>,
+
+    /// The album the track is from.
+    pub album: Option<String>,
+
+    /// Where the track came from, like the site it's from or who should be credited for it.
+    pub source: Option<String>,
+}
+
+impl Metadata {
+    /// Whether there isn't anything known about the track.
+    const fn is_empty(&self) -> bool {
+        self.artist.is_none() && self.album.is_none() && self.source.is_none()
+    }
+}
+
+/// A single track in a [`Structured`] list.
+///
+/// This can either be just the path, or a table with extra information.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Entry {
+    /// Just the path of the track.
+    Path(String),
+
+    /// The path of the track along with some metadata.
+    Detailed {
+        /// The path of the track, which works the same as in plain text lists.
+        path: String,
+
+        /// A custom display name for the track.
+        name: Option<String>,
+
+        /// How likely the track is to be picked, relative to the others.
+        /// This defaults to 1.
+        weight: Option<f32>,
+
+        /// The artist who made the track.
+        artist: Option<String>,
+
+        /// The album the track is from.
+        album: Option<String>,
+
+        /// Where the track came from, see [`Metadata::source`].
+        source: Option<String>,
+    },
+}
+
+/// A structured track list.
+#[derive(Deserialize)]
+pub struct Structured {
+    /// The base URL, which is prepended to each track that isn't a full URL.
+    #[serde(default)]
+    base: String,
+
+    /// Extra HTTP headers which are sent along with every request.
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+
+    /// Basic authentication, which is used for every request.
+    auth: Option<Auth>,
+
+    /// How the display names of tracks are derived from their paths.
+    #[serde(default)]
+    naming: Naming,
+
+    /// The actual tracks.
+    tracks: Vec<Entry>,
+}
+
+impl Structured {
+    /// Parses a structured list from `text`, written in `format`.
+    pub fn parse(text: &str, format: Format) -> eyre::Result<Self> {
+        Ok(match format {
+            Format::Toml => toml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+        })
+    }
+
+    /// Converts this into a [List] with the name `name`.
+    pub fn into_list(self, name: &str) -> eyre::Result<List> {
+        let mut headers = HeaderMap::new();
+        for (key, value) in self.headers {
+            headers.insert(
+                HeaderName::from_bytes(key.as_bytes())?,
+                HeaderValue::from_str(&value)?,
+            );
+        }
+
+        let mut weights = Vec::with_capacity(self.tracks.len());
+        let mut metadata = Vec::with_capacity(self.tracks.len());
+        let tracks: Vec<String> = self
+            .tracks
+            .into_iter()
+            .map(|entry| match entry {
+                Entry::Path(path) => {
+                    weights.push(1.0);
+                    metadata.push(Metadata::default());
+                    path
+                }
+                Entry::Detailed {
+                    path,
+                    name,
+                    weight,
+                    artist,
+                    album,
+                    source,
+                } => {
+                    weights.push(weight.unwrap_or(1.0));
+                    metadata.push(Metadata {
+                        artist,
+                        album,
+                        source,
+                    });
+                    name.map_or_else(|| path.clone(), |name| format!("{path}!{name}"))
+                }
+            })
+            .collect();
+
+        // There's no point in weighted picking if every track has the same weight.
+        let weights = if weights.iter().any(|x| (x - 1.0).abs() > f32::EPSILON) {
+            Some(
+                WeightedIndex::new(weights)
+                    .map_err(|error| eyre!("invalid track weights in {name:?}: {error}"))?,
+            )
+        } else {
+            None
+        };
+
+        // Likewise, plain paths don't need any metadata to be kept around.
+        let metadata = if metadata.iter().all(Metadata::is_empty) {
+            None
+        } else {
+            Some(metadata.into())
+        };
+
+        Ok(List {
+            name: name.to_owned(),
+            lines: iter::once(self.base).chain(tracks).collect(),
+            headers,
+            auth: self.auth.map(|x| (x.username, x.password)),
+            naming: Arc::new(self.naming),
+            weights,
+            metadata,
+        })
+    }
+}
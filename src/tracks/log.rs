@@ -0,0 +1,244 @@
+//! Contains the [`Log`], which appends every track that's played to `history.log`
+//! so that they can be looked back on later, like with `lowfi stats`.
+
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex as AsyncMutex};
+
+use super::Info;
+use crate::play;
+
+/// The biggest that `history.log` can get before the oldest tracks are taken out of it,
+/// which is usually somewhere around 70,000 tracks.
+const MAX_SIZE: usize = 8 * 1024 * 1024;
+
+/// Held while `history.log` is written to, so that a track can't
+/// be added to it while it's being trimmed & then get lost.
+static WRITING: AsyncMutex<()> = AsyncMutex::const_new(());
+
+/// How a track stopped playing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum End {
+    /// It played until the end.
+    Finished,
+
+    /// The user skipped it.
+    Skipped,
+
+    /// The user went back to the track before it.
+    Previous,
+
+    /// lowfi quit while it was playing.
+    Quit,
+}
+
+impl End {
+    /// Every way a track can end.
+    const ALL: [Self; 4] = [Self::Finished, Self::Skipped, Self::Previous, Self::Quit];
+
+    /// The name of the end, which is used in `history.log`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Finished => "finished",
+            Self::Skipped => "skipped",
+            Self::Previous => "previous",
+            Self::Quit => "quit",
+        }
+    }
+
+    /// Gets the end with the name `name`, if there is one.
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+/// A single track that was played, which is one line of `history.log`.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    /// When the track stopped playing, in seconds since the Unix epoch.
+    pub time: u64,
+
+    /// The name of the list that the track is from.
+    pub list: String,
+
+    /// How far into the track it got before it stopped.
+    pub played: Duration,
+
+    /// How long the track is, if that's known.
+    pub duration: Option<Duration>,
+
+    /// How the track stopped playing.
+    pub end: End,
+
+    /// The full path of the track.
+    pub path: String,
+
+    /// The display name of the track.
+    pub name: String,
+
+    /// The artist from the track's tags, if it has one.
+    pub artist: Option<String>,
+}
+
+/// Makes `text` safe to put in a single field of a line, since fields are split by tabs.
+fn field(text: &str) -> String {
+    text.replace(['\t', '\n', '\r'], " ")
+}
+
+impl Entry {
+    /// Formats the entry as a line of `history.log`, which is made up of the time, list,
+    /// seconds played, duration in seconds, end, path, name & artist, separated by tabs.
+    ///
+    /// The duration & artist are just left empty if they aren't known.
+    fn line(&self) -> String {
+        let duration = self
+            .duration
+            .map(|x| x.as_secs().to_string())
+            .unwrap_or_default();
+
+        format!(
+            "{}\t{}\t{}\t{duration}\t{}\t{}\t{}\t{}\n",
+            self.time,
+            field(&self.list),
+            self.played.as_secs(),
+            self.end.name(),
+            field(&self.path),
+            field(&self.name),
+            field(self.artist.as_deref().unwrap_or_default()),
+        )
+    }
+
+    /// Parses a line of `history.log`, see [`Entry::line`].
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split('\t');
+        let mut next = || fields.next();
+
+        Some(Self {
+            time: next()?.parse().ok()?,
+            list: next()?.to_owned(),
+            played: Duration::from_secs(next()?.parse().ok()?),
+            duration: next()?.parse().ok().map(Duration::from_secs),
+            end: End::parse(next()?)?,
+            path: next()?.to_owned(),
+            name: next()?.to_owned(),
+            artist: next().filter(|x| !x.is_empty()).map(String::from),
+        })
+    }
+}
+
+/// Appends each track to `history.log` in the data directory once it stops playing.
+///
+/// Unlike the play counts, this is written as it goes rather than on shutdown,
+/// so nothing is lost if lowfi doesn't quit cleanly.
+pub struct Log {
+    /// The last track that was written, so that a track is never written twice,
+    /// like when loading the next one fails and is tried again.
+    last: Mutex<Option<Arc<Info>>>,
+}
+
+impl Log {
+    /// Creates a new [`Log`], which doesn't touch `history.log` until a track is written.
+    pub const fn new() -> Self {
+        Self {
+            last: Mutex::new(None),
+        }
+    }
+
+    /// Retrieves the path to `history.log` in the data directory.
+    fn path() -> eyre::Result<PathBuf> {
+        Ok(play::data_dir()?.join("history.log"))
+    }
+
+    /// Writes `info`, which is from the list called `list` and stopped after
+    /// `played` because of `end`, unless it was the last track written.
+    pub async fn record(
+        &self,
+        info: &Arc<Info>,
+        list: &str,
+        played: Duration,
+        end: End,
+    ) -> eyre::Result<()> {
+        {
+            let mut written = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+            if written.as_ref().is_some_and(|x| Arc::ptr_eq(x, info)) {
+                return Ok(());
+            }
+
+            *written = Some(Arc::clone(info));
+        }
+
+        let entry = Entry {
+            time: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |x| x.as_secs()),
+            list: list.to_owned(),
+            played,
+            duration: info.duration,
+            end,
+            path: info.full_path.clone(),
+            name: info.name.clone(),
+            artist: info.artist.clone(),
+        };
+
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let _writing = WRITING.lock().await;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        file.write_all(entry.line().as_bytes()).await?;
+
+        Ok(())
+    }
+
+    /// Takes the oldest tracks out of `history.log` once it's bigger than [`MAX_SIZE`],
+    /// keeping the newest ones which fit in half of it so that this doesn't happen often.
+    ///
+    /// This is done every so often by [`super::cache::Cache::collect`].
+    pub async fn trim() -> eyre::Result<()> {
+        let path = Self::path()?;
+        let _writing = WRITING.lock().await;
+
+        let Ok(data) = fs::read(&path).await else {
+            return Ok(());
+        };
+
+        if data.len() <= MAX_SIZE {
+            return Ok(());
+        }
+
+        // The cut is moved forward to the start of the next line, so no track is left half written.
+        let cut = data.len() - MAX_SIZE / 2;
+        let start = data[cut..]
+            .iter()
+            .position(|x| *x == b'\n')
+            .map_or(data.len(), |x| cut + x + 1);
+
+        play::write(&path, &data[start..]).await
+    }
+
+    /// Loads every [`Entry`] in `history.log`, oldest first.
+    ///
+    /// Lines which can't be parsed are ignored, like with the play counts.
+    pub async fn load() -> eyre::Result<Vec<Entry>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        Ok(fs::read_to_string(path)
+            .await?
+            .lines()
+            .filter_map(Entry::parse)
+            .collect())
+    }
+}
@@ -0,0 +1,98 @@
+//! Contains the [`Plays`] struct, which keeps count of how many
+//! times each track has been played across sessions.
+
+use std::{collections::HashMap, fmt::Write, path::PathBuf, sync::RwLock};
+
+use tokio::fs;
+
+use crate::play;
+
+/// The play counts of every track that has been played,
+/// which are loaded at startup and saved on shutdown.
+///
+/// `plays.txt` has one track per line, formatted as `<count> <full path>`.
+pub struct Plays {
+    /// The play counts, keyed by the full path of each track.
+    counts: RwLock<HashMap<String, u32>>,
+}
+
+impl Plays {
+    /// Retrieves the path to `plays.txt` in the data directory.
+    fn path() -> eyre::Result<PathBuf> {
+        Ok(play::data_dir()?.join("plays.txt"))
+    }
+
+    /// Loads the [`Plays`] from the data directory.
+    ///
+    /// Lines which can't be parsed are ignored, since the counts aren't very important.
+    pub async fn load() -> eyre::Result<Self> {
+        let path = Self::path()?;
+
+        let counts = if path.exists() {
+            fs::read_to_string(path)
+                .await?
+                .lines()
+                .filter_map(|line| {
+                    let (count, path) = line.trim().split_once(' ')?;
+                    Some((path.to_owned(), count.parse().ok()?))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            counts: RwLock::new(counts),
+        })
+    }
+
+    /// Saves the play counts to `plays.txt`.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let contents =
+            self.counts
+                .read()
+                .unwrap()
+                .iter()
+                .fold(String::new(), |mut output, (path, count)| {
+                    writeln!(output, "{count} {path}").unwrap();
+                    output
+                });
+
+        play::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    /// Gets how many times a track has been played.
+    pub fn get(&self, path: &str) -> u32 {
+        self.counts
+            .read()
+            .unwrap()
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Gets how many different tracks have been played, and how many plays there have been in total.
+    pub fn totals(&self) -> (usize, u64) {
+        let counts = self.counts.read().unwrap();
+        let total = counts.values().copied().map(u64::from).sum();
+
+        (counts.len(), total)
+    }
+
+    /// Records that a track has been played.
+    pub fn increment(&self, path: &str) {
+        *self
+            .counts
+            .write()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_default() += 1;
+    }
+}
@@ -0,0 +1,112 @@
+//! Contains the [`Skips`] struct, which keeps count of how many
+//! tracks have been skipped across sessions, and why.
+
+use std::{collections::HashMap, fmt::Write, path::PathBuf, sync::RwLock};
+
+use tokio::fs;
+
+use crate::play;
+
+/// Why a track was skipped, rather than being played until the end.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// The user skipped it, either with a key, the mouse or MPRIS.
+    Manual,
+
+    /// It couldn't be downloaded or decoded, so a different one was picked instead.
+    Error,
+}
+
+impl Reason {
+    /// Every reason, in the order they're shown in.
+    pub const ALL: [Self; 2] = [Self::Manual, Self::Error];
+
+    /// The name of the reason, which is used in `skips.txt`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Manual => "manual",
+            Self::Error => "error",
+        }
+    }
+
+    /// Gets the reason with the name `name`, if there is one.
+    fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|x| x.name() == name)
+    }
+}
+
+/// How many tracks have been skipped for each [`Reason`],
+/// which are loaded at startup and saved on shutdown.
+///
+/// `skips.txt` has one reason per line, formatted as `<count> <reason>`.
+pub struct Skips {
+    /// The skip counts, keyed by the reason.
+    counts: RwLock<HashMap<Reason, u32>>,
+}
+
+impl Skips {
+    /// Retrieves the path to `skips.txt` in the data directory.
+    fn path() -> eyre::Result<PathBuf> {
+        Ok(play::data_dir()?.join("skips.txt"))
+    }
+
+    /// Loads the [`Skips`] from the data directory.
+    ///
+    /// Lines which can't be parsed are ignored, like with the play counts.
+    pub async fn load() -> eyre::Result<Self> {
+        let path = Self::path()?;
+
+        let counts = if path.exists() {
+            fs::read_to_string(path)
+                .await?
+                .lines()
+                .filter_map(|line| {
+                    let (count, reason) = line.trim().split_once(' ')?;
+                    Some((Reason::parse(reason)?, count.parse().ok()?))
+                })
+                .collect()
+        } else {
+            HashMap::new()
+        };
+
+        Ok(Self {
+            counts: RwLock::new(counts),
+        })
+    }
+
+    /// Saves the skip counts to `skips.txt`.
+    pub async fn save(&self) -> eyre::Result<()> {
+        let path = Self::path()?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let contents = Reason::ALL
+            .into_iter()
+            .map(|reason| (reason, self.get(reason)))
+            .filter(|(_, count)| *count > 0)
+            .fold(String::new(), |mut output, (reason, count)| {
+                writeln!(output, "{count} {}", reason.name()).unwrap();
+                output
+            });
+
+        play::write(path, contents).await?;
+
+        Ok(())
+    }
+
+    /// Gets how many tracks have been skipped for `reason`.
+    pub fn get(&self, reason: Reason) -> u32 {
+        self.counts
+            .read()
+            .unwrap()
+            .get(&reason)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Records that a track was skipped because of `reason`.
+    pub fn record(&self, reason: Reason) {
+        *self.counts.write().unwrap().entry(reason).or_default() += 1;
+    }
+}
@@ -0,0 +1,182 @@
+//! Contains the [`Stream`], which lets tracks be decoded
+//! and played while they're still being downloaded.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+};
+
+use bytes::Bytes;
+
+/// The data which is shared between a [`Stream`] & it's [`Writer`].
+#[derive(Default)]
+struct State {
+    /// Everything that has been downloaded so far.
+    data: Vec<u8>,
+
+    /// Whether the download is over, either because it finished or failed.
+    done: bool,
+
+    /// Whether the download stopped before the whole track was downloaded.
+    failed: bool,
+}
+
+/// A [`State`] which can be waited on until more data arrives.
+#[derive(Default)]
+struct Buffer {
+    /// The actual state.
+    state: Mutex<State>,
+
+    /// Notified whenever the [`State`] changes.
+    changed: Condvar,
+}
+
+impl Buffer {
+    /// Locks the [`State`], ignoring whether it's been poisoned,
+    /// since it's always left valid.
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Blocks until `condition` is true, and then returns the locked [`State`].
+    fn wait(&self, condition: impl Fn(&State) -> bool) -> MutexGuard<'_, State> {
+        self.changed
+            .wait_while(self.lock(), |state| !condition(state))
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Changes the [`State`] with `function`, and then wakes up anything waiting on it.
+    fn update(&self, function: impl FnOnce(&mut State)) {
+        function(&mut self.lock());
+        self.changed.notify_all();
+    }
+}
+
+/// The downloading side of a [`Stream`], which is used to add new data.
+///
+/// If this is dropped without calling [`Writer::finish`], then the
+/// download is treated as failed.
+pub struct Writer {
+    /// The buffer shared with the [`Stream`].
+    buffer: Arc<Buffer>,
+}
+
+impl Writer {
+    /// Adds a newly downloaded chunk to the end of the [`Stream`].
+    pub fn push(&self, chunk: &[u8]) {
+        self.buffer
+            .update(|state| state.data.extend_from_slice(chunk));
+    }
+
+    /// Marks the download as finished, returning all of the downloaded data.
+    pub fn finish(self) -> Bytes {
+        let mut data = Bytes::new();
+        self.buffer.update(|state| {
+            state.done = true;
+            data = Bytes::copy_from_slice(&state.data);
+        });
+
+        data
+    }
+}
+
+impl Drop for Writer {
+    /// Makes sure that the [`Stream`] doesn't keep waiting for data
+    /// which will never arrive.
+    fn drop(&mut self) {
+        self.buffer.update(|state| {
+            if !state.done {
+                state.done = true;
+                state.failed = true;
+            }
+        });
+    }
+}
+
+/// A track which might still be downloading.
+///
+/// Reading from this will block until the requested data has been
+/// downloaded, so it should never be read from inside of an async task.
+pub struct Stream {
+    /// The buffer shared with the [`Writer`].
+    buffer: Arc<Buffer>,
+
+    /// The current position of the reader.
+    position: u64,
+}
+
+impl Stream {
+    /// Creates a new, empty [`Stream`], along with the [`Writer`] used to fill it up.
+    pub fn new() -> (Writer, Self) {
+        let buffer = Arc::new(Buffer::default());
+
+        (
+            Writer {
+                buffer: Arc::clone(&buffer),
+            },
+            Self {
+                buffer,
+                position: 0,
+            },
+        )
+    }
+
+    /// Creates a [`Stream`] from a track which has already been fully downloaded.
+    pub fn complete(data: Bytes) -> Self {
+        let state = State {
+            data: data.into(),
+            done: true,
+            failed: false,
+        };
+
+        Self {
+            buffer: Arc::new(Buffer {
+                state: Mutex::new(state),
+                changed: Condvar::new(),
+            }),
+            position: 0,
+        }
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let position = self.position as usize;
+        let state = self
+            .buffer
+            .wait(|state| state.done || state.data.len() > position);
+
+        let available = state.data.get(position..).unwrap_or_default();
+        if available.is_empty() && state.failed {
+            return Err(io::Error::other("the download was interrupted"));
+        }
+
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        drop(state);
+
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl Seek for Stream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+            // The end isn't known until the whole track has been downloaded.
+            SeekFrom::End(offset) => {
+                let state = self.buffer.wait(|state| state.done);
+                (state.data.len() as u64).checked_add_signed(offset)
+            }
+        };
+
+        self.position = position.ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "can't seek before the start")
+        })?;
+
+        Ok(self.position)
+    }
+}
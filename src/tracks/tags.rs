@@ -0,0 +1,118 @@
+//! Contains the [`Tags`] struct, which has the title, artist & album
+//! that some tracks have embedded at the start of them.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    sync::Arc,
+};
+
+use symphonia_core::{
+    io::BufReader,
+    meta::{MetadataBuilder, StandardTagKey, StandardVisualKey},
+};
+use symphonia_metadata::id3v2;
+
+/// The biggest tag that will be read, in bytes.
+///
+/// Tags with album art can easily be a few megabytes,
+/// but anything past this is ignored rather than read into memory.
+const MAX_SIZE: usize = 16 * 1024 * 1024;
+
+/// The length of the header at the start of an `ID3v2` tag.
+const HEADER_SIZE: usize = 10;
+
+/// A picture embedded in a track's tags, which is usually the album art.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Art {
+    /// The format of the picture, like `image/png`.
+    pub media_type: String,
+
+    /// The picture itself, still encoded.
+    pub data: Box<[u8]>,
+}
+
+/// The tags read from the `ID3v2` tag of a track, where each one is [`None`] if it's missing or empty.
+#[derive(Debug, Default, Eq, PartialEq, Clone)]
+pub struct Tags {
+    /// The title of the track.
+    pub title: Option<String>,
+
+    /// The artist who made the track.
+    pub artist: Option<String>,
+
+    /// The album the track is from.
+    pub album: Option<String>,
+
+    /// The album art, preferring the front cover if there are several pictures.
+    pub art: Option<Arc<Art>>,
+}
+
+impl Tags {
+    /// Reads the `ID3v2` tag at the start of `data`, and then seeks back to the start.
+    ///
+    /// Tracks without a tag, or with one which can't be read, just have empty [`Tags`].
+    /// If the track is still downloading, this blocks until the whole tag has been downloaded.
+    /// The album art is only kept if `art` is set, since it can take up a lot of memory.
+    pub fn read<R: Read + Seek>(data: &mut R, art: bool) -> io::Result<Self> {
+        let mut tags = Self::parse(data).unwrap_or_default();
+        if !art {
+            tags.art = None;
+        }
+
+        data.seek(SeekFrom::Start(0))?;
+
+        Ok(tags)
+    }
+
+    /// Parses the `ID3v2` tag at the current position of `data`, if there is one.
+    fn parse(data: &mut impl Read) -> Option<Self> {
+        let mut tag = vec![0; HEADER_SIZE];
+        data.read_exact(&mut tag).ok()?;
+        if !tag.starts_with(b"ID3") {
+            return None;
+        }
+
+        // The size doesn't include the header, and is "syncsafe", so only 7 bits of each byte are used.
+        let size = tag[6..HEADER_SIZE]
+            .iter()
+            .fold(0usize, |size, x| (size << 7u8) | usize::from(x & 0x7f));
+        if size > MAX_SIZE {
+            return None;
+        }
+
+        tag.resize(HEADER_SIZE + size, 0);
+        data.read_exact(&mut tag[HEADER_SIZE..]).ok()?;
+
+        let mut builder = MetadataBuilder::new();
+        id3v2::read_id3v2(&mut BufReader::new(&tag), &mut builder).ok()?;
+        let metadata = builder.metadata();
+
+        let get = |key| {
+            metadata
+                .tags()
+                .iter()
+                .find(|x| x.std_key == Some(key))
+                .map(|x| x.value.to_string().trim().to_owned())
+                .filter(|x| !x.is_empty())
+        };
+
+        let visuals = metadata.visuals();
+        let art = visuals
+            .iter()
+            .find(|x| x.usage == Some(StandardVisualKey::FrontCover))
+            .or_else(|| visuals.first())
+            .map(|x| {
+                Arc::new(Art {
+                    media_type: x.media_type.clone(),
+                    data: x.data.clone(),
+                })
+            });
+
+        Some(Self {
+            title: get(StandardTagKey::TrackTitle),
+            artist: get(StandardTagKey::Artist),
+            album: get(StandardTagKey::Album),
+            art,
+        })
+    }
+}